@@ -1,38 +1,352 @@
 //! This module defines the Abstract Syntax Tree (AST) nodes for the `arith` language.
 //!
-//! While this file contains the `ASTNode` enum, the primary AST representation
-//! currently used throughout the project (in `parser.rs` and `executor.rs`)
-//! is the `Expr` enum defined in `src/parser.rs`.
-//!
-//! This `ASTNode` enum might be a placeholder for future extensions or
-//! an alternative AST structure.
+//! A line of input is parsed into a `Statement`: either a bare expression whose value is
+//! printed, or one of the variable-binding forms (`let`, assignment, compound assignment).
+//! Expressions themselves are represented by the `Expr` enum.
+
+use crate::tokenizer::TokenType;
+use crate::value::ValueType;
+use std::collections::BTreeSet;
+use std::fmt;
 
-pub enum ASTNode {
-    /// Represents a numeric literal in the AST.
+/// Represents a node in the Abstract Syntax Tree (AST).
+///
+/// The AST is a tree representation of the grammatical structure of the
+/// source code. Each variant of this enum corresponds to a different type of
+/// expression in the `arith` language.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// A literal floating-point number, e.g., `42.0`, `3.14`.
     Number(f64),
-    /// Represents a variable. (Currently unused in the main interpreter flow).
+
+    /// A reference to a variable, e.g., `x`.
     Variable(String),
-    /// Represents an addition operation. (Currently unused in the main interpreter flow).
-    Addition {
-        left: Box<ASTNode>,
-        right: Box<ASTNode>,
+
+    /// A reference to an earlier result by its 1-based index, e.g., `$3`.
+    HistoryRef(usize),
+
+    /// A unary operation, e.g., `-5`, `+x`.
+    ///
+    /// It consists of an operator (`op`) and an expression (`expr`) that it
+    /// applies to.
+    UnaryOp { op: TokenType, expr: Box<Expr> },
+
+    /// A postfix operation, e.g., `5!`.
+    ///
+    /// It consists of the expression (`expr`) and the operator (`op`) written after it.
+    Postfix { expr: Box<Expr>, op: TokenType },
+
+    /// A binary operation, e.g., `a + b`, `c * d`, `a < b`.
+    ///
+    /// It consists of a left-hand side expression (`left`), an operator (`op`),
+    /// and a right-hand side expression (`right`).
+    BinaryOp {
+        left: Box<Expr>,
+        op: TokenType,
+        right: Box<Expr>,
     },
-    /// Represents a subtraction operation. (Currently unused in the main interpreter flow).
-    Subtraction {
-        left: Box<ASTNode>,
-        right: Box<ASTNode>,
+
+    /// A conditional expression, e.g., `x > 0 ? x : -x`.
+    ///
+    /// It evaluates to `then` if `cond` is nonzero, and to `else_` otherwise. Only the
+    /// selected branch is evaluated.
+    Conditional {
+        cond: Box<Expr>,
+        then: Box<Expr>,
+        else_: Box<Expr>,
     },
-    /// Represents a multiplication operation. (Currently unused in the main interpreter flow).
-    Multiplication {
-        left: Box<ASTNode>,
-        right: Box<ASTNode>,
+
+    /// A call to a built-in function, e.g., `variance(1, 2, 3)`.
+    ///
+    /// It consists of the function `name` and its argument expressions (`args`),
+    /// in the order they were written.
+    Call { name: String, args: Vec<Expr> },
+
+    /// Represents an empty expression, typically from an empty input string.
+    Empty,
+
+    /// Represents empty parentheses, e.g., `()`. In `arith`, this evaluates to `0`.
+    EmptyParen,
+}
+
+impl Expr {
+    /// Returns the names of the variables the expression reads, e.g. `{x, y}` for
+    /// `x + y * 2`. A constant expression reads none.
+    pub fn free_variables(&self) -> BTreeSet<String> {
+        let mut variables = BTreeSet::new();
+        self.collect_names(&mut variables, &mut BTreeSet::new());
+        variables
+    }
+
+    /// Returns the names of the functions the expression calls, e.g. `{sqrt}` for
+    /// `sqrt(x) + 1`.
+    pub fn called_functions(&self) -> BTreeSet<String> {
+        let mut functions = BTreeSet::new();
+        self.collect_names(&mut BTreeSet::new(), &mut functions);
+        functions
+    }
+
+    /// Adds the variables read and the functions called anywhere in the expression to
+    /// `variables` and `functions`.
+    fn collect_names(&self, variables: &mut BTreeSet<String>, functions: &mut BTreeSet<String>) {
+        match self {
+            Expr::Variable(name) => {
+                variables.insert(name.clone());
+            }
+            Expr::UnaryOp { expr, .. } | Expr::Postfix { expr, .. } => {
+                expr.collect_names(variables, functions)
+            }
+            Expr::BinaryOp { left, right, .. } => {
+                left.collect_names(variables, functions);
+                right.collect_names(variables, functions);
+            }
+            Expr::Conditional { cond, then, else_ } => {
+                cond.collect_names(variables, functions);
+                then.collect_names(variables, functions);
+                else_.collect_names(variables, functions);
+            }
+            Expr::Call { name, args } => {
+                functions.insert(name.clone());
+                for arg in args {
+                    arg.collect_names(variables, functions);
+                }
+            }
+            Expr::Number(_) | Expr::HistoryRef(_) | Expr::Empty | Expr::EmptyParen => {}
+        }
+    }
+
+    /// Renders the expression as a JSON object, for tools that consume the parse tree.
+    ///
+    /// Every node has a `"type"` naming its variant, e.g.
+    /// `{"type":"BinaryOp","op":"+","left":{"type":"Number","value":1},"right":...}`.
+    /// Operators are written as their source symbol, and a number that is not finite
+    /// as `null`.
+    pub fn to_json(&self) -> String {
+        match self {
+            Expr::Number(n) if n.is_finite() => format!(r#"{{"type":"Number","value":{}}}"#, n),
+            Expr::Number(_) => r#"{"type":"Number","value":null}"#.to_string(),
+            Expr::Variable(name) => {
+                format!(r#"{{"type":"Variable","name":{}}}"#, json_string(name))
+            }
+            Expr::HistoryRef(index) => format!(r#"{{"type":"HistoryRef","index":{}}}"#, index),
+            Expr::UnaryOp { op, expr } => format!(
+                r#"{{"type":"UnaryOp","op":{},"expr":{}}}"#,
+                json_string(&operator_symbol(op)),
+                expr.to_json()
+            ),
+            Expr::Postfix { expr, op } => format!(
+                r#"{{"type":"Postfix","op":{},"expr":{}}}"#,
+                json_string(&operator_symbol(op)),
+                expr.to_json()
+            ),
+            Expr::BinaryOp { left, op, right } => format!(
+                r#"{{"type":"BinaryOp","op":{},"left":{},"right":{}}}"#,
+                json_string(&operator_symbol(op)),
+                left.to_json(),
+                right.to_json()
+            ),
+            Expr::Conditional { cond, then, else_ } => format!(
+                r#"{{"type":"Conditional","cond":{},"then":{},"else":{}}}"#,
+                cond.to_json(),
+                then.to_json(),
+                else_.to_json()
+            ),
+            Expr::Call { name, args } => format!(
+                r#"{{"type":"Call","name":{},"args":[{}]}}"#,
+                json_string(name),
+                args.iter().map(Expr::to_json).collect::<Vec<_>>().join(",")
+            ),
+            Expr::Empty => r#"{"type":"Empty"}"#.to_string(),
+            Expr::EmptyParen => r#"{"type":"EmptyParen"}"#.to_string(),
+        }
+    }
+}
+
+/// Represents a single statement, the unit produced by parsing one logical line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    /// A bare expression, e.g., `1 + 2`. Its value is the result of the line.
+    Expression(Expr),
+
+    /// A variable definition, e.g., `let x = 5`.
+    ///
+    /// Defines `name` (or redefines it, if it already exists) with the value of `value`.
+    /// With a type annotation, e.g. `let x: Int = 5`, `type_name` is the declared type
+    /// the value must have.
+    Let {
+        name: String,
+        type_name: Option<ValueType>,
+        value: Expr,
+    },
+
+    /// A reassignment of an existing variable, e.g., `x = 5`.
+    ///
+    /// Assigning to a variable that was never defined with `let` is an error.
+    Assignment { name: String, value: Expr },
+
+    /// A compound assignment to an existing variable, e.g., `x += 1`.
+    ///
+    /// `op` is the arithmetic operator applied (`Plus`, `Minus`, `Mul`, or `Div`),
+    /// so `x += 1` is equivalent to `x = x + 1`.
+    CompoundAssignment {
+        name: String,
+        op: TokenType,
+        value: Expr,
     },
-    /// Represents a division operation. (Currently unused in the main interpreter flow).
-    Division {
-        left: Box<ASTNode>,
-        right: Box<ASTNode>,
+
+    /// A function definition, e.g., `let sq(x) = x * x`.
+    ///
+    /// Defines (or redefines) the function `name`. A call binds each of `params` to the
+    /// corresponding argument and evaluates `body`.
+    FunctionDef {
+        name: String,
+        params: Vec<String>,
+        body: Expr,
     },
-    /// Represents a unary minus operation. (Currently unused in the main interpreter flow).
-    UnaryMinus(Box<ASTNode>),
-    // FunctionCall { name: String, args: Vec<Node> }, // Example of a potential future extension
+}
+
+impl Statement {
+    /// Renders the statement as a JSON object in the same form as `Expr::to_json`, e.g.
+    /// `{"type":"Let","name":"x","type_name":null,"value":{"type":"Number","value":5}}`.
+    pub fn to_json(&self) -> String {
+        match self {
+            Statement::Expression(expr) => {
+                format!(r#"{{"type":"Expression","expr":{}}}"#, expr.to_json())
+            }
+            Statement::Let {
+                name,
+                type_name,
+                value,
+            } => format!(
+                r#"{{"type":"Let","name":{},"type_name":{},"value":{}}}"#,
+                json_string(name),
+                type_name.map_or("null".to_string(), |t| json_string(&t.to_string())),
+                value.to_json()
+            ),
+            Statement::Assignment { name, value } => format!(
+                r#"{{"type":"Assignment","name":{},"value":{}}}"#,
+                json_string(name),
+                value.to_json()
+            ),
+            Statement::CompoundAssignment { name, op, value } => format!(
+                r#"{{"type":"CompoundAssignment","name":{},"op":{},"value":{}}}"#,
+                json_string(name),
+                json_string(&operator_symbol(op)),
+                value.to_json()
+            ),
+            Statement::FunctionDef { name, params, body } => format!(
+                r#"{{"type":"FunctionDef","name":{},"params":[{}],"body":{}}}"#,
+                json_string(name),
+                params
+                    .iter()
+                    .map(|p| json_string(p))
+                    .collect::<Vec<_>>()
+                    .join(","),
+                body.to_json()
+            ),
+        }
+    }
+}
+
+/// Returns `text` as a quoted JSON string, escaping quotes, backslashes, and control
+/// characters.
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Returns the source symbol for an operator token, e.g. `+` for `TokenType::Plus`.
+fn operator_symbol(op: &TokenType) -> String {
+    match op {
+        TokenType::Plus => "+".to_string(),
+        TokenType::Minus => "-".to_string(),
+        TokenType::Mul => "*".to_string(),
+        TokenType::Div => "/".to_string(),
+        TokenType::Pow => "^".to_string(),
+        TokenType::BitAnd => "&".to_string(),
+        TokenType::BitOr => "|".to_string(),
+        TokenType::BitNot => "~".to_string(),
+        TokenType::ShiftLeft => "<<".to_string(),
+        TokenType::ShiftRight => ">>".to_string(),
+        TokenType::Less => "<".to_string(),
+        TokenType::Greater => ">".to_string(),
+        TokenType::LessEqual => "<=".to_string(),
+        TokenType::GreaterEqual => ">=".to_string(),
+        TokenType::Equal => "==".to_string(),
+        TokenType::NotEqual => "!=".to_string(),
+        TokenType::ApproxEqual => "~=".to_string(),
+        TokenType::Bang => "!".to_string(),
+        TokenType::Percent | TokenType::Modulo => "%".to_string(),
+        TokenType::Of => "of".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Implements the `Display` trait for `Expr`, rendering it back to infix source.
+///
+/// Every unary and binary operation is wrapped in parentheses, so the output shows
+/// exactly how the parser grouped the expression, e.g. `1 + 2 * 3` renders as `(1 + (2 * 3))`.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Number(n) => write!(f, "{}", n),
+            Expr::Variable(name) => write!(f, "{}", name),
+            Expr::HistoryRef(index) => write!(f, "${}", index),
+            Expr::UnaryOp { op, expr } => write!(f, "({}{})", operator_symbol(op), expr),
+            Expr::Postfix { expr, op } => write!(f, "({}{})", expr, operator_symbol(op)),
+            Expr::BinaryOp { left, op, right } => {
+                write!(f, "({} {} {})", left, operator_symbol(op), right)
+            }
+            Expr::Conditional { cond, then, else_ } => {
+                write!(f, "({} ? {} : {})", cond, then, else_)
+            }
+            Expr::Call { name, args } => {
+                write!(f, "{}(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Empty => Ok(()),
+            Expr::EmptyParen => write!(f, "()"),
+        }
+    }
+}
+
+/// Implements the `Display` trait for `Statement`, rendering it back to source
+/// using the canonical `Expr` form for its value.
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Statement::Expression(expr) => write!(f, "{}", expr),
+            Statement::Let {
+                name,
+                type_name: Some(type_name),
+                value,
+            } => write!(f, "let {}: {} = {}", name, type_name, value),
+            Statement::Let { name, value, .. } => write!(f, "let {} = {}", name, value),
+            Statement::Assignment { name, value } => write!(f, "{} = {}", name, value),
+            Statement::CompoundAssignment { name, op, value } => {
+                write!(f, "{} {}= {}", name, operator_symbol(op), value)
+            }
+            Statement::FunctionDef { name, params, body } => {
+                write!(f, "let {}({}) = {}", name, params.join(", "), body)
+            }
+        }
+    }
 }
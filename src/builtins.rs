@@ -0,0 +1,789 @@
+//! This module defines the registry of built-in functions available in `arith` expressions.
+//!
+//! Each built-in is described by a `Builtin` entry holding its name, the number of
+//! arguments it accepts, and the Rust function implementing it. The `SimpleExecutor`
+//! looks functions up by name when it executes an `Instr::CallBuiltin` instruction.
+
+use crate::executor::ExecError;
+use crate::format::format_number;
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::f64::consts::TAU;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::str::FromStr;
+
+/// Describes how many arguments a built-in function accepts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    /// The function takes exactly this many arguments.
+    Exact(usize),
+    /// The function is variadic and takes at least this many arguments.
+    AtLeast(usize),
+}
+
+impl Arity {
+    /// Returns `true` if a call with `count` arguments satisfies this arity.
+    pub fn accepts(&self, count: usize) -> bool {
+        match self {
+            Arity::Exact(n) => count == *n,
+            Arity::AtLeast(n) => count >= *n,
+        }
+    }
+}
+
+/// Implements the `Display` trait for `Arity`, producing phrases such as
+/// `exactly 1` or `at least 2` for use in error messages.
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arity::Exact(n) => write!(f, "exactly {}", n),
+            Arity::AtLeast(n) => write!(f, "at least {}", n),
+        }
+    }
+}
+
+/// The unit the trigonometric built-ins measure angles in.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum AngleMode {
+    /// Angles are in radians, so a full turn is `2π`.
+    #[default]
+    Radians,
+    /// Angles are in degrees, so a full turn is `360`.
+    Degrees,
+}
+
+impl AngleMode {
+    /// Converts the angle `x`, measured in this mode, to radians.
+    pub fn to_radians(self, x: f64) -> f64 {
+        match self {
+            AngleMode::Radians => x,
+            AngleMode::Degrees => x.to_radians(),
+        }
+    }
+
+    /// Converts the angle `x`, measured in radians, to this mode.
+    pub fn from_radians(self, x: f64) -> f64 {
+        match self {
+            AngleMode::Radians => x,
+            AngleMode::Degrees => x.to_degrees(),
+        }
+    }
+
+    /// Returns the size of a full turn in this mode.
+    pub fn full_turn(self) -> f64 {
+        match self {
+            AngleMode::Radians => TAU,
+            AngleMode::Degrees => 360.0,
+        }
+    }
+}
+
+impl FromStr for AngleMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "radians" | "rad" => Ok(AngleMode::Radians),
+            "degrees" | "deg" => Ok(AngleMode::Degrees),
+            other => Err(format!(
+                "unknown angle mode '{}' (expected radians or degrees)",
+                other
+            )),
+        }
+    }
+}
+
+/// Implements the `Display` trait for `AngleMode`, producing the name `FromStr` accepts.
+impl fmt::Display for AngleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AngleMode::Radians => write!(f, "radians"),
+            AngleMode::Degrees => write!(f, "degrees"),
+        }
+    }
+}
+
+/// A single entry in the built-in function registry.
+pub struct Builtin {
+    /// The name used to call the function, e.g. `variance`.
+    pub name: &'static str,
+    /// The number of arguments the function accepts.
+    pub arity: Arity,
+    /// The implementation. It receives the already-evaluated arguments in call order and
+    /// the angle mode the call is evaluated in.
+    pub func: fn(&[f64], AngleMode) -> Result<f64, ExecError>,
+}
+
+/// The table of all built-in functions.
+const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "sqrt",
+        arity: Arity::Exact(1),
+        func: |args, _| Ok(args[0].sqrt()),
+    },
+    Builtin {
+        name: "sin",
+        arity: Arity::Exact(1),
+        func: |args, mode| Ok(mode.to_radians(args[0]).sin()),
+    },
+    Builtin {
+        name: "cos",
+        arity: Arity::Exact(1),
+        func: |args, mode| Ok(mode.to_radians(args[0]).cos()),
+    },
+    Builtin {
+        name: "tan",
+        arity: Arity::Exact(1),
+        func: |args, mode| Ok(mode.to_radians(args[0]).tan()),
+    },
+    Builtin {
+        name: "asin",
+        arity: Arity::Exact(1),
+        func: |args, mode| Ok(mode.from_radians(args[0].asin())),
+    },
+    Builtin {
+        name: "acos",
+        arity: Arity::Exact(1),
+        func: |args, mode| Ok(mode.from_radians(args[0].acos())),
+    },
+    Builtin {
+        name: "atan",
+        arity: Arity::Exact(1),
+        func: |args, mode| Ok(mode.from_radians(args[0].atan())),
+    },
+    Builtin {
+        name: "abs",
+        arity: Arity::Exact(1),
+        func: |args, _| Ok(args[0].abs()),
+    },
+    // Unlike `f64::signum`, the sign of zero (and of `-0`) is `0`.
+    Builtin {
+        name: "sign",
+        arity: Arity::Exact(1),
+        func: |args, _| {
+            Ok(if args[0] == 0.0 {
+                0.0
+            } else {
+                args[0].signum()
+            })
+        },
+    },
+    // `f64::hypot` scales its arguments, so it does not overflow where `a^2 + b^2` would.
+    Builtin {
+        name: "hypot",
+        arity: Arity::Exact(2),
+        func: |args, _| Ok(args[0].hypot(args[1])),
+    },
+    Builtin {
+        name: "ln",
+        arity: Arity::Exact(1),
+        func: |args, _| Ok(args[0].ln()),
+    },
+    Builtin {
+        name: "log10",
+        arity: Arity::Exact(1),
+        func: |args, _| Ok(args[0].log10()),
+    },
+    Builtin {
+        name: "exp",
+        arity: Arity::Exact(1),
+        func: |args, _| Ok(args[0].exp()),
+    },
+    Builtin {
+        name: "floor",
+        arity: Arity::Exact(1),
+        func: |args, _| Ok(args[0].floor()),
+    },
+    Builtin {
+        name: "ceil",
+        arity: Arity::Exact(1),
+        func: |args, _| Ok(args[0].ceil()),
+    },
+    // Halfway cases round away from zero, so `round(2.5)` is `3` and `round(-2.5)` is `-3`.
+    Builtin {
+        name: "round",
+        arity: Arity::Exact(1),
+        func: |args, _| Ok(args[0].round()),
+    },
+    Builtin {
+        name: "trunc",
+        arity: Arity::Exact(1),
+        func: |args, _| Ok(args[0].trunc()),
+    },
+    Builtin {
+        name: "copysign",
+        arity: Arity::Exact(2),
+        func: |args, _| Ok(args[0].copysign(args[1])),
+    },
+    Builtin {
+        name: "nextafter",
+        arity: Arity::Exact(2),
+        func: |args, _| nextafter(args),
+    },
+    Builtin {
+        name: "min",
+        arity: Arity::AtLeast(1),
+        func: |args, _| Ok(args.iter().copied().fold(f64::INFINITY, f64::min)),
+    },
+    Builtin {
+        name: "max",
+        arity: Arity::AtLeast(1),
+        func: |args, _| Ok(args.iter().copied().fold(f64::NEG_INFINITY, f64::max)),
+    },
+    Builtin {
+        name: "clamp",
+        arity: Arity::Exact(3),
+        func: |args, _| clamp(args),
+    },
+    Builtin {
+        name: "wrap",
+        arity: Arity::Exact(3),
+        func: |args, _| wrap(args),
+    },
+    Builtin {
+        name: "normalize_angle",
+        arity: Arity::Exact(1),
+        func: |args, mode| Ok(normalize_angle(args[0], mode.full_turn())),
+    },
+    Builtin {
+        name: "ceil_div",
+        arity: Arity::Exact(2),
+        func: |args, _| ceil_div(args),
+    },
+    Builtin {
+        name: "factorial",
+        arity: Arity::Exact(1),
+        func: |args, _| factorial(args),
+    },
+    Builtin {
+        name: "print",
+        arity: Arity::Exact(1),
+        func: |args, _| Ok(args[0]),
+    },
+    Builtin {
+        name: "assert",
+        arity: Arity::Exact(1),
+        func: |args, _| assert(args),
+    },
+    Builtin {
+        name: "assert_eq",
+        arity: Arity::Exact(2),
+        func: |args, _| assert_eq(args),
+    },
+    Builtin {
+        name: "round_sig",
+        arity: Arity::Exact(2),
+        func: |args, _| round_sig(args),
+    },
+    Builtin {
+        name: "popcount",
+        arity: Arity::Exact(1),
+        func: |args, _| popcount("popcount", args[0]),
+    },
+    Builtin {
+        name: "bitcount",
+        arity: Arity::Exact(1),
+        func: |args, _| popcount("bitcount", args[0]),
+    },
+    Builtin {
+        name: "gcd",
+        arity: Arity::AtLeast(1),
+        func: |args, _| gcd(args),
+    },
+    Builtin {
+        name: "lcm",
+        arity: Arity::AtLeast(1),
+        func: |args, _| lcm(args),
+    },
+    Builtin {
+        name: "mod_pow",
+        arity: Arity::Exact(3),
+        func: |args, _| mod_pow(args),
+    },
+    Builtin {
+        name: "sum",
+        arity: Arity::Exact(2),
+        func: |args, _| range_sum(args),
+    },
+    Builtin {
+        name: "product",
+        arity: Arity::Exact(2),
+        func: |args, _| range_product(args),
+    },
+    Builtin {
+        name: "variance",
+        arity: Arity::AtLeast(1),
+        func: |args, _| variance(args),
+    },
+    Builtin {
+        name: "stddev",
+        arity: Arity::AtLeast(1),
+        func: |args, _| stddev(args),
+    },
+    Builtin {
+        name: "sample_variance",
+        arity: Arity::AtLeast(2),
+        func: |args, _| sample_variance(args),
+    },
+    Builtin {
+        name: "sample_stddev",
+        arity: Arity::AtLeast(2),
+        func: |args, _| sample_stddev(args),
+    },
+];
+
+/// Looks up a built-in function by name.
+///
+/// # Returns
+/// The matching `Builtin` entry, or `None` if no built-in has that name.
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name == name)
+}
+
+/// Calls the built-in function `name` with the given arguments.
+///
+/// # Arguments
+/// * `name` - The name of the built-in to call.
+/// * `args` - The evaluated arguments, in call order.
+/// * `mode` - The unit angles are measured in, for the trigonometric built-ins.
+///
+/// # Returns
+/// A `Result` which is `Ok` containing the function's result, or `Err` containing an
+/// `ExecError` if the function does not exist or was called with the wrong number of arguments.
+pub fn call(name: &str, args: &[f64], mode: AngleMode) -> Result<f64, ExecError> {
+    let builtin = lookup(name).ok_or_else(|| ExecError::UnknownFunction {
+        name: name.to_string(),
+    })?;
+    if !builtin.arity.accepts(args.len()) {
+        return Err(ExecError::ArgumentCount {
+            name: name.to_string(),
+            expected: builtin.arity.to_string(),
+            found: args.len(),
+        });
+    }
+    (builtin.func)(args, mode)
+}
+
+/// Returns the next representable `f64` after `x` in the direction of `y`.
+///
+/// Returns `y` when the two are equal, and NaN if either argument is NaN.
+fn nextafter(args: &[f64]) -> Result<f64, ExecError> {
+    let (x, y) = (args[0], args[1]);
+    Ok(if x.is_nan() || y.is_nan() {
+        f64::NAN
+    } else if y > x {
+        x.next_up()
+    } else if y < x {
+        x.next_down()
+    } else {
+        y
+    })
+}
+
+/// The name of the `print` built-in. Its table entry only returns the argument; the
+/// executor writes the line itself, so walking the tree with `Expr::evaluate` to check a
+/// result does not print it a second time.
+pub const PRINT: &str = "print";
+
+/// Writes `x` to stderr on its own line, for the `print` built-in, keeping it out of the
+/// results on stdout, so `print(a) + print(b)` shows both operands.
+///
+/// The line is written as soon as the call executes, so it comes before the result of
+/// the expression containing the call.
+pub fn print(x: f64) {
+    eprintln!("{}", format_number(x, None));
+}
+
+/// The name of the `rand` built-in. It is not in the table, since it draws from the
+/// calling executor's `Rng`; the executor dispatches it to `rand` itself.
+pub const RAND: &str = "rand";
+
+/// A small seedable pseudo-random number generator (SplitMix64) for the `rand` built-in.
+///
+/// The state is a `Cell`, so numbers can be drawn through a shared reference, as when an
+/// expression is evaluated by walking its tree.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: Cell<u64>,
+}
+
+impl Rng {
+    /// Creates a generator that always produces the same sequence for the same `seed`.
+    pub fn new(seed: u64) -> Self {
+        Rng {
+            state: Cell::new(seed),
+        }
+    }
+
+    /// Creates a generator with an unpredictable seed, different from run to run.
+    pub fn from_entropy() -> Self {
+        Self::new(RandomState::new().build_hasher().finish())
+    }
+
+    /// Returns the next number of the sequence, uniform in `[0, 1)`.
+    pub fn next_f64(&self) -> f64 {
+        let state = self.state.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+        self.state.set(state);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        // The top 53 bits fill the mantissa exactly.
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Draws a uniform random number from `rng`: in `[0, 1)` for `rand()`, or in `[lo, hi)`
+/// for `rand(lo, hi)`. `rand(x, x)` is `x`.
+///
+/// `lo` must not be greater than `hi`.
+pub fn rand(args: &[f64], rng: &Rng) -> Result<f64, ExecError> {
+    let (lo, hi) = match *args {
+        [] => (0.0, 1.0),
+        [lo, hi] => (lo, hi),
+        _ => {
+            return Err(ExecError::ArgumentCount {
+                name: RAND.to_string(),
+                expected: "0 or 2".to_string(),
+                found: args.len(),
+            });
+        }
+    };
+    if lo > hi {
+        return Err(ExecError::InvalidArgument {
+            name: RAND.to_string(),
+            expected: format!("a lower bound no greater than the upper bound {}", hi),
+            found: lo,
+        });
+    }
+    let value = lo + (hi - lo) * rng.next_f64();
+    // Rounding can land exactly on the excluded upper bound.
+    Ok(if value >= hi { lo } else { value })
+}
+
+/// Limits `x` to the range `[lo, hi]`, e.g. `clamp(5, 0, 3)` is `3`.
+///
+/// `lo` must not be greater than `hi`.
+fn clamp(args: &[f64]) -> Result<f64, ExecError> {
+    let (x, lo, hi) = (args[0], args[1], args[2]);
+    if lo > hi {
+        return Err(ExecError::InvalidArgument {
+            name: "clamp".to_string(),
+            expected: format!("a lower bound no greater than the upper bound {}", hi),
+            found: lo,
+        });
+    }
+    Ok(x.clamp(lo, hi))
+}
+
+/// Wraps `x` into the half-open range `[lo, hi)`, e.g. `wrap(370, 0, 360)` is `10`
+/// and `wrap(-10, 0, 360)` is `350`.
+///
+/// `lo` must be less than `hi`.
+fn wrap(args: &[f64]) -> Result<f64, ExecError> {
+    let (x, lo, hi) = (args[0], args[1], args[2]);
+    if lo >= hi {
+        return Err(ExecError::InvalidArgument {
+            name: "wrap".to_string(),
+            expected: format!("a lower bound less than the upper bound {}", hi),
+            found: lo,
+        });
+    }
+    let width = hi - lo;
+    let wrapped = lo + (x - lo).rem_euclid(width);
+    // As in `normalize_angle`, rounding can land exactly on the excluded upper bound.
+    Ok(if wrapped >= hi { lo } else { wrapped })
+}
+
+/// Wraps the angle `x` into `[0, full_turn)`, e.g. `370` degrees to `10` and `-90` to `270`.
+fn normalize_angle(x: f64, full_turn: f64) -> f64 {
+    let wrapped = x.rem_euclid(full_turn);
+    // A tiny negative angle can round up to exactly `full_turn`, which is outside the range.
+    if wrapped == full_turn { 0.0 } else { wrapped }
+}
+
+/// Divides `a` by `b` and rounds up, e.g. `ceil_div(7, 2)` is `4` and `ceil_div(-7, 2)` is `-3`.
+fn ceil_div(args: &[f64]) -> Result<f64, ExecError> {
+    let (a, b) = (args[0], args[1]);
+    if b == 0.0 {
+        return Err(ExecError::DivisionByZero);
+    }
+    Ok((a / b).ceil())
+}
+
+/// Converts `x` to a `u64`, requiring it to be a non-negative integer that fits.
+///
+/// `name` is the calling function, used in the error message.
+fn to_u64(name: &str, x: f64) -> Result<u64, ExecError> {
+    if x.fract() == 0.0 && x >= 0.0 && x < u64::MAX as f64 {
+        Ok(x as u64)
+    } else {
+        Err(ExecError::InvalidArgument {
+            name: name.to_string(),
+            expected: "a non-negative integer".to_string(),
+            found: x,
+        })
+    }
+}
+
+/// The largest `n` whose factorial is finite as an `f64`; `171!` overflows to infinity.
+const MAX_FINITE_FACTORIAL: u64 = 170;
+
+/// Returns `n!` for a non-negative integer `n`, also written with the postfix `!` operator.
+fn factorial(args: &[f64]) -> Result<f64, ExecError> {
+    let n = to_u64("factorial", args[0])?;
+    if n > MAX_FINITE_FACTORIAL {
+        return Ok(f64::INFINITY);
+    }
+    Ok((2..=n).map(|k| k as f64).product())
+}
+
+/// The relative tolerance `assert_eq` and the `~=` operator compare with, so
+/// `assert_eq(0.1 + 0.2, 0.3)` passes.
+pub const APPROX_EQ_EPSILON: f64 = 1e-9;
+
+/// Returns `true` if `a` and `b` are equal to within `APPROX_EQ_EPSILON`, relative to
+/// the larger of them, or absolute when both are below 1.
+pub fn approx_eq(a: f64, b: f64) -> bool {
+    let scale = a.abs().max(b.abs()).max(1.0);
+    a == b || (a - b).abs() <= APPROX_EQ_EPSILON * scale
+}
+
+/// Fails with "assertion failed" if the condition is zero; otherwise returns it.
+fn assert(args: &[f64]) -> Result<f64, ExecError> {
+    if args[0] == 0.0 {
+        return Err(ExecError::Other("assertion failed".to_string()));
+    }
+    Ok(args[0])
+}
+
+/// Fails, reporting both values, unless `a` and `b` are equal according to `approx_eq`.
+/// Returns `a` when they match.
+fn assert_eq(args: &[f64]) -> Result<f64, ExecError> {
+    let (a, b) = (args[0], args[1]);
+    if approx_eq(a, b) {
+        Ok(a)
+    } else {
+        Err(ExecError::Other(format!(
+            "assertion failed: {} != {}",
+            a, b
+        )))
+    }
+}
+
+/// The number of significant figures from which `round_sig` leaves every `f64` unchanged.
+const MAX_SIGNIFICANT_FIGURES: f64 = 17.0;
+
+/// Rounds `x` to `n` significant figures, e.g. `round_sig(12345, 2)` is `12000`.
+///
+/// `n` must be a positive integer. Zero (and any non-finite `x`) is returned unchanged, as
+/// is any `x` when `n` is at least 17, since an `f64` has no more significant figures to
+/// round away, or when the scale factor would be outside the range of an `f64`, as for
+/// subnormal numbers such as `1e-310`.
+fn round_sig(args: &[f64]) -> Result<f64, ExecError> {
+    let (x, n) = (args[0], args[1]);
+    if n.fract() != 0.0 || n < 1.0 {
+        return Err(ExecError::InvalidArgument {
+            name: "round_sig".to_string(),
+            expected: "a positive integer number of significant figures".to_string(),
+            found: n,
+        });
+    }
+    if x == 0.0 || !x.is_finite() || n >= MAX_SIGNIFICANT_FIGURES {
+        return Ok(x);
+    }
+    // The power of ten of the last digit to keep.
+    let magnitude = x.abs().log10().floor() as i32;
+    let shift = n as i32 - 1 - magnitude;
+    if !(f64::MIN_10_EXP..=f64::MAX_10_EXP).contains(&shift) {
+        return Ok(x);
+    }
+    // Scale by dividing or multiplying with an exact power of ten, which rounds
+    // more accurately than multiplying by a negative power.
+    Ok(if shift >= 0 {
+        let factor = 10f64.powi(shift);
+        (x * factor).round() / factor
+    } else {
+        let factor = 10f64.powi(-shift);
+        (x / factor).round() * factor
+    })
+}
+
+/// Counts the set bits in the binary representation of `x`.
+fn popcount(name: &str, x: f64) -> Result<f64, ExecError> {
+    Ok(to_u64(name, x)?.count_ones() as f64)
+}
+
+/// Converts `x` to the `u64` magnitude of an integer, requiring it to be integral.
+///
+/// `name` is the calling function, used in the error message.
+fn to_magnitude(name: &str, x: f64) -> Result<u64, ExecError> {
+    if x.fract() == 0.0 && x.abs() < u64::MAX as f64 {
+        Ok(x.abs() as u64)
+    } else {
+        Err(ExecError::InvalidArgument {
+            name: name.to_string(),
+            expected: "an integer".to_string(),
+            found: x,
+        })
+    }
+}
+
+/// Returns the greatest common divisor of `a` and `b` by Euclid's algorithm.
+fn gcd_pair(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Greatest common divisor of all arguments, e.g. `gcd(12, 18, 24)` is `6`.
+///
+/// Signs are ignored, and zeros do not change the result: `gcd(0, 4)` is `4`.
+fn gcd(args: &[f64]) -> Result<f64, ExecError> {
+    let mut result = 0;
+    for &x in args {
+        result = gcd_pair(result, to_magnitude("gcd", x)?);
+    }
+    Ok(result as f64)
+}
+
+/// Least common multiple of all arguments, e.g. `lcm(2, 3, 4)` is `12`.
+///
+/// Signs are ignored, and any zero makes the result zero. A multiple too large
+/// for a `u64` is infinity.
+fn lcm(args: &[f64]) -> Result<f64, ExecError> {
+    let mut result: Option<u64> = Some(1);
+    for &x in args {
+        let n = to_magnitude("lcm", x)?;
+        result = match result {
+            _ if n == 0 => Some(0),
+            Some(0) => Some(0),
+            Some(m) => (m / gcd_pair(m, n)).checked_mul(n),
+            None => None,
+        };
+    }
+    Ok(result.map_or(f64::INFINITY, |n| n as f64))
+}
+
+/// Computes `base ^ exp mod modulus` by square-and-multiply, e.g. `mod_pow(2, 10, 1000)`
+/// is `24`.
+///
+/// All arguments must be integers, with `exp` non-negative and `modulus` nonzero. The
+/// result lies in `[0, |modulus|)`, also for a negative `base`.
+fn mod_pow(args: &[f64]) -> Result<f64, ExecError> {
+    let base = to_magnitude("mod_pow", args[0])?;
+    let mut exp = to_u64("mod_pow", args[1])?;
+    let modulus = to_magnitude("mod_pow", args[2])? as u128;
+    if modulus == 0 {
+        return Err(ExecError::DivisionByZero);
+    }
+    let mut base = base as u128 % modulus;
+    if args[0] < 0.0 && base != 0 {
+        base = modulus - base;
+    }
+    let mut result = 1 % modulus;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result * base % modulus;
+        }
+        base = base * base % modulus;
+        exp >>= 1;
+    }
+    Ok(result as f64)
+}
+
+/// Converts the arguments of `sum` or `product` to the integer bounds `[start, end]`,
+/// requiring both to be integers with `start` no greater than `end`.
+fn integer_range(name: &str, args: &[f64]) -> Result<(i64, i64), ExecError> {
+    for &x in args {
+        if x.fract() != 0.0 || x.abs() >= i64::MAX as f64 {
+            return Err(ExecError::InvalidArgument {
+                name: name.to_string(),
+                expected: "an integer".to_string(),
+                found: x,
+            });
+        }
+    }
+    let (start, end) = (args[0], args[1]);
+    if start > end {
+        return Err(ExecError::InvalidArgument {
+            name: name.to_string(),
+            expected: format!("a start no greater than the end {}", end),
+            found: start,
+        });
+    }
+    Ok((start as i64, end as i64))
+}
+
+/// Sum of the integers from `start` to `end` inclusive, e.g. `sum(1, 100)` is `5050`.
+///
+/// Computed with Gauss's formula rather than term by term, so long ranges are fast.
+fn range_sum(args: &[f64]) -> Result<f64, ExecError> {
+    let (start, end) = integer_range("sum", args)?;
+    let count = end as i128 - start as i128 + 1;
+    let ends = start as i128 + end as i128;
+    // `count * ends` is always even, so the division is exact.
+    Ok(match count.checked_mul(ends) {
+        Some(twice) => (twice / 2) as f64,
+        None => count as f64 * ends as f64 / 2.0,
+    })
+}
+
+/// Product of the integers from `start` to `end` inclusive, e.g. `product(1, 5)` is `120`.
+///
+/// A range containing `0` gives `0`. Otherwise the magnitudes are multiplied in a loop
+/// that stops as soon as the product overflows to infinity, so it runs at most a few
+/// hundred steps, and the sign is applied afterwards.
+fn range_product(args: &[f64]) -> Result<f64, ExecError> {
+    let (start, end) = integer_range("product", args)?;
+    if start <= 0 && 0 <= end {
+        return Ok(0.0);
+    }
+    let negative = end < 0;
+    let (low, high) = if negative {
+        (-end, -start)
+    } else {
+        (start, end)
+    };
+    let mut product = 1.0;
+    for k in low..=high {
+        product *= k as f64;
+        if product.is_infinite() {
+            break;
+        }
+    }
+    let odd_count = (high - low) % 2 == 0;
+    Ok(if negative && odd_count {
+        -product
+    } else {
+        product
+    })
+}
+
+/// Returns the sum of squared deviations of `args` from their mean.
+fn sum_squared_deviations(args: &[f64]) -> f64 {
+    let mean = args.iter().sum::<f64>() / args.len() as f64;
+    args.iter().map(|x| (x - mean) * (x - mean)).sum()
+}
+
+/// Population variance: the sum of squared deviations divided by `n`.
+fn variance(args: &[f64]) -> Result<f64, ExecError> {
+    Ok(sum_squared_deviations(args) / args.len() as f64)
+}
+
+/// Population standard deviation: the square root of `variance`.
+fn stddev(args: &[f64]) -> Result<f64, ExecError> {
+    variance(args).map(f64::sqrt)
+}
+
+/// Sample variance: the sum of squared deviations divided by `n - 1` (Bessel's correction).
+fn sample_variance(args: &[f64]) -> Result<f64, ExecError> {
+    Ok(sum_squared_deviations(args) / (args.len() - 1) as f64)
+}
+
+/// Sample standard deviation: the square root of `sample_variance`.
+fn sample_stddev(args: &[f64]) -> Result<f64, ExecError> {
+    sample_variance(args).map(f64::sqrt)
+}
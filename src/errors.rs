@@ -4,6 +4,7 @@
 //! where the error occurred, to facilitate debugging and user feedback.
 
 use crate::tokenizer::TokenType;
+use crate::value::TYPE_NAMES;
 use std::fmt;
 
 /// Represents an error that can occur during parsing.
@@ -48,6 +49,42 @@ pub enum ParserError {
         line: usize,
         col: usize,
     },
+    /// A reserved keyword was used where a variable name is expected, e.g. `let let = 5`.
+    ///
+    /// `keyword`: The keyword that was used as a name.
+    /// `line`: The 1-based line number where the keyword was found.
+    /// `col`: The 1-based column number where the keyword starts.
+    ReservedKeyword {
+        keyword: String,
+        line: usize,
+        col: usize,
+    },
+    /// A type annotation named a type that does not exist, e.g. `let x: Text = 5`.
+    ///
+    /// `name`: The unknown type name.
+    /// `line`: The 1-based line number where the type name was found.
+    /// `col`: The 1-based column number where the type name starts.
+    UnknownType {
+        name: String,
+        line: usize,
+        col: usize,
+    },
+    /// The input ended before a `(` was closed, e.g. `(1 + 2`.
+    ///
+    /// `line`: The 1-based line number of the unclosed `(`.
+    /// `col`: The 1-based column number of the unclosed `(`.
+    UnmatchedParen { line: usize, col: usize },
+    /// A `)` was found with no `(` left to close, e.g. `1 + 2)`.
+    ///
+    /// `line`: The 1-based line number of the stray `)`.
+    /// `col`: The 1-based column number of the stray `)`.
+    UnmatchedCloseParen { line: usize, col: usize },
+    /// The expression is nested more deeply than the parser's depth limit allows, e.g.
+    /// thousands of nested parentheses.
+    ///
+    /// `line`: The 1-based line number of the token where the limit was reached.
+    /// `col`: The 1-based column number of the token where the limit was reached.
+    RecursionLimitExceeded { line: usize, col: usize },
 }
 
 /// Represents an error that can occur during the tokenization (lexical analysis) phase.
@@ -66,6 +103,35 @@ pub enum TokenizerError {
         line: usize,
         col: usize,
     },
+    /// A degrees-minutes-seconds angle literal such as `30d15m0s` is malformed, e.g.
+    /// its minutes are 60 or more or its parts are out of order.
+    ///
+    /// `message`: A description of the problem.
+    /// `line`: The 1-based line number where the literal starts.
+    /// `col`: The 1-based column number where the literal starts.
+    InvalidAngle {
+        message: String,
+        line: usize,
+        col: usize,
+    },
+}
+
+/// Converts a `TokenizerError` into a `ParserError::TokenizerError` with the same location.
+impl From<TokenizerError> for ParserError {
+    fn from(error: TokenizerError) -> Self {
+        match error {
+            TokenizerError::UnexpectedCharacter { found, line, col } => {
+                ParserError::TokenizerError {
+                    message: format!("Unexpected character '{}'", found),
+                    line,
+                    col,
+                }
+            }
+            TokenizerError::InvalidAngle { message, line, col } => {
+                ParserError::TokenizerError { message, line, col }
+            }
+        }
+    }
 }
 
 /// Implements the `Display` trait for `TokenizerError`, allowing it to be
@@ -80,6 +146,9 @@ impl fmt::Display for TokenizerError {
                     found, line, col
                 )
             }
+            TokenizerError::InvalidAngle { message, line, col } => {
+                write!(f, "{} at line {}, col {}", message, line, col)
+            }
         }
     }
 }
@@ -109,6 +178,44 @@ impl fmt::Display for ParserError {
                     message, line, col
                 )
             }
+            ParserError::ReservedKeyword { keyword, line, col } => {
+                write!(
+                    f,
+                    "'{}' is a reserved keyword and cannot be used as a variable name at line {}, col {}",
+                    keyword, line, col
+                )
+            }
+            ParserError::UnknownType { name, line, col } => {
+                write!(
+                    f,
+                    "Unknown type '{}' (expected one of {}) at line {}, col {}",
+                    name,
+                    TYPE_NAMES.join(", "),
+                    line,
+                    col
+                )
+            }
+            ParserError::UnmatchedParen { line, col } => {
+                write!(
+                    f,
+                    "Unclosed '(' at line {}, col {}: expected a matching ')'",
+                    line, col
+                )
+            }
+            ParserError::UnmatchedCloseParen { line, col } => {
+                write!(
+                    f,
+                    "Unmatched ')' at line {}, col {}: there is no '(' to close",
+                    line, col
+                )
+            }
+            ParserError::RecursionLimitExceeded { line, col } => {
+                write!(
+                    f,
+                    "Expression is nested too deeply at line {}, col {} (see --max-depth)",
+                    line, col
+                )
+            }
         }
     }
 }
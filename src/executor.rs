@@ -5,19 +5,37 @@
 //! It also orchestrates the entire evaluation pipeline, from raw input string to final result,
 //! handling line continuations, comments, and comprehensive error reporting.
 
-use crate::errors::{ParserError, TokenizerError};
-use crate::parser::{Expr, Parser};
-use crate::tokenizer::{TokenType, Tokenizer};
+use crate::ast::{Expr, Statement};
+use crate::builtins::{self, AngleMode};
+use crate::errors::ParserError;
+use crate::parser::{DEFAULT_MAX_DEPTH, Parser};
+use crate::tokenizer::{DEFAULT_COMMENT_DELIMITER, Token, TokenType, Tokenizer};
+use crate::value::{Value, ValueType};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::error::Error;
 use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 /// Represents a single bytecode instruction.
 ///
 /// These instructions form a simple stack-based language used by the `SimpleExecutor`.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Instr {
-    /// Pushes a floating-point number onto the stack.
-    Push(f64),
+    /// Pushes a number onto the stack.
+    Push(Value),
+    /// Pushes the value of the named variable onto the stack.
+    Load(String),
+    /// Pushes the earlier result with the given 1-based index onto the stack.
+    LoadResult(usize),
+    /// Defines (or redefines) the named variable with the value on top of the stack.
+    /// The value is left on the stack.
+    Store(String),
+    /// Overwrites an already-defined variable with the value on top of the stack.
+    /// The value is left on the stack.
+    Assign(String),
     /// Pops two numbers, adds them, and pushes the result.
     Add,
     /// Pops two numbers, subtracts the second from the first, and pushes the result.
@@ -26,8 +44,216 @@ pub enum Instr {
     Mul,
     /// Pops two numbers, divides the first by the second, and pushes the result.
     Div,
+    /// Pops two numbers and pushes the remainder of dividing the first by the second,
+    /// which has the sign of the first, e.g. `-7 % 3` is `-1`.
+    Mod,
+    /// Pops two numbers, raises the first to the power of the second, and pushes the result.
+    Pow,
     /// Pops one number, negates it, and pushes the result.
     Neg,
+    /// Pops two integers and pushes their bitwise AND.
+    BitAnd,
+    /// Pops two integers and pushes their bitwise OR.
+    BitOr,
+    /// Pops one integer and pushes its bitwise NOT.
+    BitNot,
+    /// Pops two integers and pushes the first shifted left by the second.
+    Shl,
+    /// Pops two integers and pushes the first shifted right (arithmetically) by the second.
+    Shr,
+    /// Pops two numbers and pushes `1` if the first is less than the second, else `0`.
+    Lt,
+    /// Pops two numbers and pushes `1` if the first is greater than the second, else `0`.
+    Gt,
+    /// Pops two numbers and pushes `1` if the first is less than or equal to the second, else `0`.
+    Le,
+    /// Pops two numbers and pushes `1` if the first is greater than or equal to the second, else `0`.
+    Ge,
+    /// Pops two numbers and pushes `1` if they are equal, else `0`.
+    Eq,
+    /// Pops two numbers and pushes `1` if they are not equal, else `0`.
+    Ne,
+    /// Pops two numbers and pushes `1` if they are equal to within
+    /// `builtins::APPROX_EQ_EPSILON`, else `0`.
+    ApproxEq,
+    /// Checks that the number on top of the stack has the given type, converting an
+    /// integer to a float where a `Float` is expected. The value is left on the stack.
+    CheckType(ValueType),
+    /// Pops a number and, if it is zero, skips the given number of instructions.
+    JumpIfZero(usize),
+    /// Skips the given number of instructions.
+    Jump(usize),
+    /// Pops the given number of arguments, calls the named built-in function
+    /// with them (in call order), and pushes the result.
+    CallBuiltin(String, usize),
+    /// Like `CallBuiltin`, but calls the user-defined function of that name if
+    /// there is one, and the built-in otherwise.
+    Call(String, usize),
+}
+
+/// Implements the `Display` trait for `Instr`, producing an assembly-like listing line:
+/// an uppercase mnemonic followed by its operands, e.g. `PUSH 7`, `LOAD x` or `CALL f 2`.
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instr::Push(value) => write!(f, "PUSH {}", value),
+            Instr::Load(name) => write!(f, "LOAD {}", name),
+            Instr::LoadResult(index) => write!(f, "LOAD_RESULT ${}", index),
+            Instr::Store(name) => write!(f, "STORE {}", name),
+            Instr::Assign(name) => write!(f, "ASSIGN {}", name),
+            Instr::Add => write!(f, "ADD"),
+            Instr::Sub => write!(f, "SUB"),
+            Instr::Mul => write!(f, "MUL"),
+            Instr::Div => write!(f, "DIV"),
+            Instr::Mod => write!(f, "MOD"),
+            Instr::Pow => write!(f, "POW"),
+            Instr::Neg => write!(f, "NEG"),
+            Instr::BitAnd => write!(f, "BIT_AND"),
+            Instr::BitOr => write!(f, "BIT_OR"),
+            Instr::BitNot => write!(f, "BIT_NOT"),
+            Instr::Shl => write!(f, "SHL"),
+            Instr::Shr => write!(f, "SHR"),
+            Instr::Lt => write!(f, "LT"),
+            Instr::Gt => write!(f, "GT"),
+            Instr::Le => write!(f, "LE"),
+            Instr::Ge => write!(f, "GE"),
+            Instr::Eq => write!(f, "EQ"),
+            Instr::Ne => write!(f, "NE"),
+            Instr::ApproxEq => write!(f, "APPROX_EQ"),
+            Instr::CheckType(value_type) => write!(f, "CHECK_TYPE {}", value_type),
+            Instr::JumpIfZero(offset) => write!(f, "JUMP_IF_ZERO +{}", offset),
+            Instr::Jump(offset) => write!(f, "JUMP +{}", offset),
+            Instr::CallBuiltin(name, argc) => write!(f, "CALL_BUILTIN {} {}", name, argc),
+            Instr::Call(name, argc) => write!(f, "CALL {} {}", name, argc),
+        }
+    }
+}
+
+impl Instr {
+    /// Applies a binary arithmetic or comparison instruction to its operands,
+    /// where `a` is the first (deeper) operand and `b` the second.
+    ///
+    /// # Returns
+    /// `None` if this is not a binary instruction, otherwise the result, or
+    /// `ExecError::DivisionByZero` for a `Div` or `Mod` by zero, `ExecError::NonFinite` for
+    /// arithmetic on finite operands that overflows or is undefined, or
+    /// `ExecError::InvalidOperand` for a bitwise operation on a non-integer.
+    fn apply_binary(&self, a: Value, b: Value) -> Option<Result<Value, ExecError>> {
+        // Comparisons evaluate to the integer 1 when they hold and 0 otherwise.
+        let ordering = a.compare(b);
+        let truth = |holds: bool| Ok(Value::Int(holds as i64));
+        Some(match self {
+            Instr::Add => finite("+", a, b, a + b),
+            Instr::Sub => finite("-", a, b, a - b),
+            Instr::Mul => finite("*", a, b, a * b),
+            Instr::Div => match a.checked_div(b) {
+                Some(result) => finite("/", a, b, result),
+                None => Err(ExecError::DivisionByZero),
+            },
+            Instr::Mod => match a.checked_rem(b) {
+                Some(result) => finite("%", a, b, result),
+                None => Err(ExecError::DivisionByZero),
+            },
+            Instr::Pow => finite("^", a, b, a.pow(b)),
+            Instr::Lt => truth(ordering == Some(Ordering::Less)),
+            Instr::Gt => truth(ordering == Some(Ordering::Greater)),
+            Instr::Le => truth(matches!(ordering, Some(Ordering::Less | Ordering::Equal))),
+            Instr::Ge => truth(matches!(
+                ordering,
+                Some(Ordering::Greater | Ordering::Equal)
+            )),
+            Instr::Eq => truth(ordering == Some(Ordering::Equal)),
+            Instr::Ne => truth(ordering != Some(Ordering::Equal)),
+            Instr::ApproxEq => truth(builtins::approx_eq(a.as_f64(), b.as_f64())),
+            Instr::BitAnd => bitwise("&", a, b, |x, y| Some(x & y)),
+            Instr::BitOr => bitwise("|", a, b, |x, y| Some(x | y)),
+            Instr::Shl => bitwise("<<", a, b, |x, y| Some(x << shift_amount(y)?)),
+            Instr::Shr => bitwise(">>", a, b, |x, y| Some(x >> shift_amount(y)?)),
+            _ => return None,
+        })
+    }
+}
+
+/// Checks the `result` of applying the arithmetic operator `op` to `a` and `b`.
+///
+/// An infinite or NaN result of finite operands, such as `1e308 * 10` or `(-8) ^ 0.5`,
+/// is an `ExecError::NonFinite`; one that follows from a non-finite operand is passed on.
+fn finite(op: &str, a: Value, b: Value, result: Value) -> Result<Value, ExecError> {
+    let result_f64 = result.as_f64();
+    if !result_f64.is_finite() && a.as_f64().is_finite() && b.as_f64().is_finite() {
+        return Err(ExecError::NonFinite {
+            op: op.to_string(),
+            result: result_f64,
+        });
+    }
+    Ok(result)
+}
+
+/// Returns `true` if the quotient `result` of `a / b` is not a whole number although
+/// `a` and `b` are, e.g. `7 / 2`, so an integer division would lose the remainder.
+fn loses_remainder(a: Value, b: Value, result: Value) -> bool {
+    a.as_f64().fract() == 0.0 && b.as_f64().fract() == 0.0 && result.as_f64().fract() != 0.0
+}
+
+/// Converts the operand of the bitwise operator `op` to an `i64`, requiring it to be
+/// an integer; an integral float such as `sqrt(4)` is accepted.
+fn integer_operand(op: &str, value: Value) -> Result<i64, ExecError> {
+    match value {
+        Value::Int(n) => Ok(n),
+        Value::Float(x) if x.fract() == 0.0 && x.abs() <= i64::MAX as f64 => Ok(x as i64),
+        _ => Err(ExecError::InvalidOperand {
+            op: op.to_string(),
+            expected: "integer operands".to_string(),
+            found: value,
+        }),
+    }
+}
+
+/// Returns `shift` as a shift amount for an `i64`, or `None` if it is not in `0..64`.
+fn shift_amount(shift: i64) -> Option<u32> {
+    u32::try_from(shift).ok().filter(|s| *s < i64::BITS)
+}
+
+/// Applies the bitwise operator `op` to two integer operands. `apply` returns `None`
+/// only for a shift by an amount outside `0..64`.
+fn bitwise(
+    op: &str,
+    a: Value,
+    b: Value,
+    apply: fn(i64, i64) -> Option<i64>,
+) -> Result<Value, ExecError> {
+    let (x, y) = (integer_operand(op, a)?, integer_operand(op, b)?);
+    apply(x, y)
+        .map(Value::Int)
+        .ok_or_else(|| ExecError::InvalidOperand {
+            op: op.to_string(),
+            expected: "a shift amount from 0 to 63".to_string(),
+            found: b,
+        })
+}
+
+/// Checks that a call to the user-defined `function` with `argc` arguments, made while
+/// `depth` calls are already active, may go ahead.
+fn check_call(name: &str, function: &Function, argc: usize, depth: usize) -> Result<(), ExecError> {
+    if argc != function.params.len() {
+        return Err(ExecError::ArgumentCount {
+            name: name.to_string(),
+            expected: format!("exactly {}", function.params.len()),
+            found: argc,
+        });
+    }
+    if depth >= MAX_CALL_DEPTH {
+        return Err(ExecError::RecursionLimit {
+            name: name.to_string(),
+            depth: MAX_CALL_DEPTH,
+        });
+    }
+    Ok(())
+}
+
+/// Returns the bitwise NOT of an integer operand.
+fn bit_not(value: Value) -> Result<Value, ExecError> {
+    Ok(Value::Int(!integer_operand("~", value)?))
 }
 
 /// Errors that can happen during compilation (AST -> bytecode)
@@ -35,6 +261,8 @@ pub enum Instr {
 pub enum CompileError {
     /// An operator was encountered in the AST that is not supported by the bytecode compiler.
     UnsupportedOperator(String),
+    /// A constant subexpression divides by zero, e.g. `1 / 0`, found while folding it.
+    DivisionByZero,
 }
 
 /// Implements the `Display` trait for `CompileError`, allowing it to be
@@ -45,6 +273,7 @@ impl fmt::Display for CompileError {
             CompileError::UnsupportedOperator(s) => {
                 write!(f, "unsupported operator during compilation: {}", s)
             }
+            CompileError::DivisionByZero => write!(f, "division by zero"),
         }
     }
 }
@@ -58,8 +287,44 @@ pub enum ExecError {
     StackUnderflow { instr: String },
     /// A division by zero operation was attempted.
     DivisionByZero,
+    /// A variable was read or assigned before being defined with `let`.
+    UndefinedVariable { name: String },
+    /// In strict mode, `let` was used on a variable that is already defined.
+    AlreadyDefined { name: String },
+    /// A history reference such as `$3` named a result that does not exist:
+    /// `$0`, or an index past the number of results produced so far.
+    HistoryIndex { index: usize, available: usize },
     /// The execution finished, but no result was left on the stack.
     NoResult,
+    /// A function was called that is not in the built-in registry.
+    UnknownFunction { name: String },
+    /// A built-in function was called with an unsupported number of arguments.
+    ArgumentCount {
+        name: String,
+        expected: String,
+        found: usize,
+    },
+    /// A built-in function was called with an argument outside its domain,
+    /// e.g. a fractional number where an integer is required.
+    InvalidArgument {
+        name: String,
+        expected: String,
+        found: f64,
+    },
+    /// An operator was applied to an operand it does not support, e.g. `1.5 & 1`.
+    InvalidOperand {
+        op: String,
+        expected: String,
+        found: Value,
+    },
+    /// An operator or built-in function applied to finite operands produced infinity or
+    /// NaN, e.g. `1e308 * 10` or `sqrt(-1)`. `op` is the operator or the function's name.
+    NonFinite { op: String, result: f64 },
+    /// A user-defined function called itself, directly or indirectly, more than
+    /// `MAX_CALL_DEPTH` calls deep.
+    RecursionLimit { name: String, depth: usize },
+    /// A value did not have the type declared for it, e.g. `let n: Int = 2.5`.
+    TypeMismatch { expected: ValueType, found: Value },
     /// A generic execution error with a descriptive message.
     Other(String),
 }
@@ -73,7 +338,62 @@ impl fmt::Display for ExecError {
                 write!(f, "stack underflow while executing instruction '{}'", instr)
             }
             ExecError::DivisionByZero => write!(f, "division by zero"),
+            ExecError::UndefinedVariable { name } => write!(f, "undefined variable '{}'", name),
+            ExecError::AlreadyDefined { name } => write!(
+                f,
+                "variable '{}' is already defined; assign to it with `{} = ...` instead",
+                name, name
+            ),
+            ExecError::HistoryIndex { index, available } => write!(
+                f,
+                "no result ${} (results are numbered from $1, {} available)",
+                index, available
+            ),
             ExecError::NoResult => write!(f, "execution finished with no result on the stack"),
+            ExecError::UnknownFunction { name } => write!(f, "unknown function '{}'", name),
+            ExecError::ArgumentCount {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "function '{}' expects {} argument(s), got {}",
+                name, expected, found
+            ),
+            ExecError::InvalidArgument {
+                name,
+                expected,
+                found,
+            } => write!(f, "function '{}' expects {}, got {}", name, expected, found),
+            ExecError::InvalidOperand {
+                op,
+                expected,
+                found,
+            } => write!(f, "operator '{}' expects {}, got {}", op, expected, found),
+            ExecError::NonFinite { op, result } => {
+                let kind = if op.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                    "function"
+                } else {
+                    "operator"
+                };
+                write!(
+                    f,
+                    "{} '{}' produced {}, which is not a finite number",
+                    kind, op, result
+                )
+            }
+            ExecError::RecursionLimit { name, depth } => write!(
+                f,
+                "calling '{}' exceeded the maximum call depth of {}",
+                name, depth
+            ),
+            ExecError::TypeMismatch { expected, found } => write!(
+                f,
+                "type mismatch: expected {}, got {} {}",
+                expected,
+                found.value_type(),
+                found
+            ),
             ExecError::Other(s) => write!(f, "execution error: {}", s),
         }
     }
@@ -97,6 +417,15 @@ pub enum EvalError {
     /// An error occurred during the bytecode execution phase.
     /// Contains the `ExecError` and the input string that caused the error.
     Exec(ExecError, String),
+    /// The input ended right after a line continuation (`\`), leaving the expression incomplete.
+    /// Contains the incomplete expression and the line number of the dangling backslash.
+    UnterminatedContinuation(String, usize),
+    /// The input was expected to produce exactly one result but produced a different number.
+    /// Contains the number of results produced and the input string.
+    ResultCount(usize, String),
+    /// An expression's result fell outside the range allowed by `EvalOptions::range`.
+    /// Contains the result, the allowed range, and the input string.
+    OutOfRange(f64, ValueRange, String),
 }
 
 /// Implements the `Display` trait for `EvalError`, providing a comprehensive
@@ -111,6 +440,11 @@ impl fmt::Display for EvalError {
                     ParserError::UnexpectedEOF { line, col } => (*line, *col),
                     ParserError::InvalidNumber { line, col, .. } => (*line, *col),
                     ParserError::TokenizerError { line, col, .. } => (*line, *col),
+                    ParserError::ReservedKeyword { line, col, .. } => (*line, *col),
+                    ParserError::UnknownType { line, col, .. } => (*line, *col),
+                    ParserError::UnmatchedParen { line, col } => (*line, *col),
+                    ParserError::UnmatchedCloseParen { line, col } => (*line, *col),
+                    ParserError::RecursionLimitExceeded { line, col } => (*line, *col),
                 };
 
                 // Calculate the absolute line number in the original file
@@ -131,13 +465,68 @@ impl fmt::Display for EvalError {
                 )
             }
             EvalError::Compile(e, input) => write!(f, "compile error: {} in input: {}", e, input),
+            EvalError::UnterminatedContinuation(input, line) => write!(
+                f,
+                "Error: input ended after a line continuation '\\' on line {}, expression is incomplete: {}",
+                line,
+                input.trim()
+            ),
             EvalError::Exec(e, input) => write!(f, "runtime error: {} in input: {}", e, input),
+            EvalError::ResultCount(count, input) => write!(
+                f,
+                "Error: expected exactly one result, got {} in input: {}",
+                count,
+                input.trim()
+            ),
+            EvalError::OutOfRange(value, range, input) => write!(
+                f,
+                "Error: result {} is outside the allowed range {} in input: {}",
+                value,
+                range,
+                input.trim()
+            ),
         }
     }
 }
 
 impl Error for EvalError {}
 
+impl EvalError {
+    /// Returns the source text of the expression that failed.
+    pub fn input(&self) -> &str {
+        match self {
+            EvalError::Parse(_, input, _)
+            | EvalError::Compile(_, input)
+            | EvalError::Exec(_, input)
+            | EvalError::UnterminatedContinuation(input, _)
+            | EvalError::ResultCount(_, input)
+            | EvalError::OutOfRange(_, _, input) => input.trim(),
+        }
+    }
+
+    /// Returns a one-line description of what went wrong, without the failing input or a
+    /// source excerpt, e.g. `division by zero`.
+    pub fn reason(&self) -> String {
+        match self {
+            EvalError::Parse(e, _, _) => e.to_string(),
+            EvalError::Compile(e, _) => e.to_string(),
+            EvalError::Exec(e, _) => e.to_string(),
+            EvalError::UnterminatedContinuation(_, line) => {
+                format!(
+                    "input ended after a line continuation '\\' on line {}",
+                    line
+                )
+            }
+            EvalError::ResultCount(count, _) => {
+                format!("expected exactly one result, got {}", count)
+            }
+            EvalError::OutOfRange(value, range, _) => {
+                format!("result {} is outside the allowed range {}", value, range)
+            }
+        }
+    }
+}
+
 /// Compiler that lowers AST -> Vec<Instr>
 ///
 /// This component takes an Abstract Syntax Tree (`Expr`) and translates it
@@ -149,7 +538,9 @@ impl BytecodeCompiler {
     /// Compiles an expression into a vector of bytecode instructions.
     ///
     /// This function traverses the AST recursively, emitting appropriate
-    /// bytecode instructions for each node.
+    /// bytecode instructions for each node. Operations whose operands are all
+    /// constants are folded at compile time, so `2 * 3 + 4` compiles to a single
+    /// `Push(10.0)`, and a constant division by zero is reported as a `CompileError`.
     ///
     /// # Arguments
     /// * `expr` - A reference to the `Expr` (AST node) to compile.
@@ -163,6 +554,49 @@ impl BytecodeCompiler {
         Ok(code)
     }
 
+    /// Compiles a statement into a vector of bytecode instructions.
+    ///
+    /// Expressions compile exactly as with `compile`. Definitions and assignments
+    /// compile their value followed by a `Store` or `Assign`, which leaves the
+    /// assigned value on the stack. Function definitions compile to no instructions;
+    /// they are registered with `SimpleExecutor::define_function` instead.
+    ///
+    /// # Arguments
+    /// * `stmt` - A reference to the `Statement` to compile.
+    ///
+    /// # Returns
+    /// A `Result` which is `Ok` containing a `Vec<Instr>` on successful compilation,
+    /// or `Err` containing a `CompileError` if an unsupported AST node is encountered.
+    pub fn compile_statement(stmt: &Statement) -> Result<Vec<Instr>, CompileError> {
+        let mut code = Vec::new();
+        match stmt {
+            Statement::Expression(expr) => Self::compile_expr(expr, &mut code)?,
+            Statement::Let {
+                name,
+                type_name,
+                value,
+            } => {
+                Self::compile_expr(value, &mut code)?;
+                if let Some(value_type) = type_name {
+                    code.push(Instr::CheckType(*value_type));
+                }
+                code.push(Instr::Store(name.clone()));
+            }
+            Statement::Assignment { name, value } => {
+                Self::compile_expr(value, &mut code)?;
+                code.push(Instr::Assign(name.clone()));
+            }
+            Statement::CompoundAssignment { name, op, value } => {
+                code.push(Instr::Load(name.clone()));
+                Self::compile_expr(value, &mut code)?;
+                code.push(Self::binary_instr(op)?);
+                code.push(Instr::Assign(name.clone()));
+            }
+            Statement::FunctionDef { .. } => {}
+        }
+        Ok(code)
+    }
+
     /// Recursively compiles an `Expr` node and appends its bytecode to the given vector.
     ///
     /// # Arguments
@@ -175,127 +609,839 @@ impl BytecodeCompiler {
     fn compile_expr(expr: &Expr, code: &mut Vec<Instr>) -> Result<(), CompileError> {
         match expr {
             Expr::Number(n) => {
-                code.push(Instr::Push(*n));
+                code.push(Instr::Push(Value::from_literal(*n)));
+                Ok(())
+            }
+            Expr::Variable(name) => {
+                code.push(Instr::Load(name.clone()));
+                Ok(())
+            }
+            Expr::HistoryRef(index) => {
+                code.push(Instr::LoadResult(*index));
                 Ok(())
             }
             Expr::UnaryOp { op, expr: e } => {
+                let start = code.len();
                 Self::compile_expr(e, code)?;
                 match op {
                     TokenType::Minus => {
-                        code.push(Instr::Neg);
+                        // Fold the negation of a constant into the constant itself.
+                        if let [Instr::Push(n)] = &mut code[start..] {
+                            *n = -*n;
+                        } else {
+                            code.push(Instr::Neg);
+                        }
                         Ok(())
                     }
                     TokenType::Plus => Ok(()), // no-op
+                    TokenType::BitNot => {
+                        // Fold the NOT of an integer constant; anything else is left
+                        // for the executor, which reports a non-integer operand.
+                        if let [Instr::Push(n)] = code[start..]
+                            && let Ok(folded) = bit_not(n)
+                        {
+                            code[start] = Instr::Push(folded);
+                        } else {
+                            code.push(Instr::BitNot);
+                        }
+                        Ok(())
+                    }
                     other => Err(CompileError::UnsupportedOperator(format!("{:?}", other))),
                 }
             }
-            Expr::BinaryOp { left, op, right } => {
-                Self::compile_expr(left, code)?;
-                Self::compile_expr(right, code)?;
+            Expr::Postfix { expr: e, op } => {
+                let start = code.len();
+                Self::compile_expr(e, code)?;
                 match op {
-                    TokenType::Plus => {
-                        code.push(Instr::Add);
+                    // `n!` is sugar for the `factorial` built-in.
+                    TokenType::Bang => {
+                        code.push(Instr::CallBuiltin("factorial".to_string(), 1));
                         Ok(())
                     }
-                    TokenType::Minus => {
-                        code.push(Instr::Sub);
-                        Ok(())
-                    }
-                    TokenType::Mul => {
-                        code.push(Instr::Mul);
-                        Ok(())
-                    }
-                    TokenType::Div => {
-                        code.push(Instr::Div);
+                    // `x%` is `x / 100`, folded like any other division of constants.
+                    TokenType::Percent => {
+                        if let [Instr::Push(n)] = code[start..]
+                            && let Some(Ok(value)) = Instr::Div.apply_binary(n, PERCENT)
+                            && !loses_remainder(n, PERCENT, value)
+                        {
+                            code[start] = Instr::Push(value);
+                        } else {
+                            code.push(Instr::Push(PERCENT));
+                            code.push(Instr::Div);
+                        }
                         Ok(())
                     }
                     other => Err(CompileError::UnsupportedOperator(format!("{:?}", other))),
                 }
             }
+            Expr::BinaryOp { left, op, right } => {
+                let start = code.len();
+                Self::compile_expr(left, code)?;
+                Self::compile_expr(right, code)?;
+                let instr = Self::binary_instr(op)?;
+                // Constant folding: when both operands compiled to a single `Push`,
+                // compute the result now and push that instead. Operands are folded
+                // first, so whole constant subtrees like `2 * 3 + 4` become one `Push`.
+                // An invalid operand is left for the executor to report, and so is a
+                // division of whole numbers with a remainder, which it may warn about.
+                if let [Instr::Push(a), Instr::Push(b)] = code[start..] {
+                    match instr.apply_binary(a, b) {
+                        Some(Ok(value))
+                            if !(instr == Instr::Div && loses_remainder(a, b, value)) =>
+                        {
+                            code.truncate(start);
+                            code.push(Instr::Push(value));
+                            return Ok(());
+                        }
+                        Some(Err(ExecError::DivisionByZero)) => {
+                            return Err(CompileError::DivisionByZero);
+                        }
+                        _ => {}
+                    }
+                }
+                code.push(instr);
+                Ok(())
+            }
+            Expr::Conditional { cond, then, else_ } => {
+                let start = code.len();
+                Self::compile_expr(cond, code)?;
+                // A constant condition selects its branch now; the other one is
+                // never compiled.
+                if let [Instr::Push(c)] = code[start..] {
+                    code.truncate(start);
+                    let branch = if c.as_f64() != 0.0 { then } else { else_ };
+                    return Self::compile_expr(branch, code);
+                }
+                // cond; JumpIfZero(past then); then; Jump(past else); else
+                let jump_if_zero = code.len();
+                code.push(Instr::JumpIfZero(0));
+                Self::compile_expr(then, code)?;
+                let jump = code.len();
+                code.push(Instr::Jump(0));
+                code[jump_if_zero] = Instr::JumpIfZero(jump - jump_if_zero);
+                Self::compile_expr(else_, code)?;
+                code[jump] = Instr::Jump(code.len() - jump - 1);
+                Ok(())
+            }
+            Expr::Call { name, args } => {
+                for arg in args {
+                    Self::compile_expr(arg, code)?;
+                }
+                code.push(Instr::Call(name.clone(), args.len()));
+                Ok(())
+            }
             Expr::Empty => {
                 // Do nothing, produce no bytecode.
                 Ok(())
             }
             Expr::EmptyParen => {
-                code.push(Instr::Push(0.0));
+                code.push(Instr::Push(Value::Int(0)));
                 Ok(())
             }
         }
     }
+
+    /// Maps a binary operator token to the instruction implementing it.
+    ///
+    /// # Returns
+    /// The matching `Instr`, or `Err` containing a `CompileError` if `op` is not a binary operator.
+    fn binary_instr(op: &TokenType) -> Result<Instr, CompileError> {
+        match op {
+            TokenType::Plus => Ok(Instr::Add),
+            TokenType::Minus => Ok(Instr::Sub),
+            TokenType::Mul => Ok(Instr::Mul),
+            TokenType::Div => Ok(Instr::Div),
+            TokenType::Modulo => Ok(Instr::Mod),
+            // `50% of 200` multiplies the percentage by what follows.
+            TokenType::Of => Ok(Instr::Mul),
+            TokenType::Pow => Ok(Instr::Pow),
+            TokenType::Less => Ok(Instr::Lt),
+            TokenType::Greater => Ok(Instr::Gt),
+            TokenType::LessEqual => Ok(Instr::Le),
+            TokenType::GreaterEqual => Ok(Instr::Ge),
+            TokenType::Equal => Ok(Instr::Eq),
+            TokenType::NotEqual => Ok(Instr::Ne),
+            TokenType::ApproxEqual => Ok(Instr::ApproxEq),
+            TokenType::BitAnd => Ok(Instr::BitAnd),
+            TokenType::BitOr => Ok(Instr::BitOr),
+            TokenType::ShiftLeft => Ok(Instr::Shl),
+            TokenType::ShiftRight => Ok(Instr::Shr),
+            other => Err(CompileError::UnsupportedOperator(format!("{:?}", other))),
+        }
+    }
+}
+
+impl Expr {
+    /// Evaluates the expression by walking the tree directly, without compiling it.
+    ///
+    /// Variables, functions, and `$N` references are read from `executor`, which is not
+    /// modified. Operators and functions behave exactly as in the bytecode, but nothing
+    /// is constant-folded, so this serves as a reference to check the compiler against.
+    ///
+    /// `rand` still draws from the executor's generator, while `print` writes nothing.
+    ///
+    /// # Returns
+    /// The value of the expression, or the `ExecError` of the first operation that failed.
+    pub fn evaluate(&self, executor: &SimpleExecutor) -> Result<Value, ExecError> {
+        self.evaluate_in(executor, &HashMap::new(), 0)
+    }
+
+    /// Evaluates the expression like `evaluate`, inside a call to a user-defined function
+    /// whose parameters are bound in `frame`, with `depth` calls active.
+    fn evaluate_in(
+        &self,
+        executor: &SimpleExecutor,
+        frame: &HashMap<String, Value>,
+        depth: usize,
+    ) -> Result<Value, ExecError> {
+        let evaluate = |expr: &Expr| expr.evaluate_in(executor, frame, depth);
+        let unsupported =
+            |op: &TokenType| ExecError::Other(format!("unsupported operator {:?}", op));
+        match self {
+            Expr::Number(n) => Ok(Value::from_literal(*n)),
+            Expr::Variable(name) => frame
+                .get(name)
+                .copied()
+                .or_else(|| executor.value(name))
+                .ok_or_else(|| ExecError::UndefinedVariable { name: name.clone() }),
+            Expr::HistoryRef(index) => executor.result(*index),
+            Expr::UnaryOp { op, expr } => {
+                let value = evaluate(expr)?;
+                match op {
+                    TokenType::Minus => Ok(-value),
+                    TokenType::Plus => Ok(value),
+                    TokenType::BitNot => bit_not(value),
+                    other => Err(unsupported(other)),
+                }
+            }
+            Expr::Postfix { expr, op } => {
+                let value = evaluate(expr)?;
+                match op {
+                    TokenType::Bang => executor.call_builtin("factorial", &[value.as_f64()]),
+                    TokenType::Percent => Instr::Div
+                        .apply_binary(value, PERCENT)
+                        .expect("Div is a binary instruction"),
+                    other => Err(unsupported(other)),
+                }
+            }
+            Expr::BinaryOp { left, op, right } => {
+                let a = evaluate(left)?;
+                let b = evaluate(right)?;
+                let instr = BytecodeCompiler::binary_instr(op).map_err(|_| unsupported(op))?;
+                instr
+                    .apply_binary(a, b)
+                    .expect("binary_instr returns a binary instruction")
+            }
+            Expr::Conditional { cond, then, else_ } => {
+                if evaluate(cond)?.as_f64() != 0.0 {
+                    evaluate(then)
+                } else {
+                    evaluate(else_)
+                }
+            }
+            Expr::Call { name, args } => {
+                let args = args
+                    .iter()
+                    .map(evaluate)
+                    .collect::<Result<Vec<Value>, ExecError>>()?;
+                let Some(function) = executor.function(name) else {
+                    let args: Vec<f64> = args.into_iter().map(Value::as_f64).collect();
+                    return executor.call_builtin(name, &args);
+                };
+                check_call(name, function, args.len(), depth)?;
+                let frame = function.params.iter().cloned().zip(args).collect();
+                function.body.evaluate_in(executor, &frame, depth + 1)
+            }
+            Expr::Empty => Err(ExecError::NoResult),
+            Expr::EmptyParen => Ok(Value::Int(0)),
+        }
+    }
+}
+
+/// The name of the variable that accumulates the sum of every expression result.
+pub const TOTAL_VARIABLE: &str = "total";
+
+/// The name of the variable holding the most recent expression result.
+pub const ANS_VARIABLE: &str = "ans";
+
+/// The number of variable changes `SimpleExecutor::undo` can revert; older ones are forgotten.
+pub const UNDO_LIMIT: usize = 100;
+
+/// An inclusive range of allowed results, open-ended on a side whose bound is `None`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ValueRange {
+    /// The smallest allowed result.
+    pub min: Option<f64>,
+    /// The largest allowed result.
+    pub max: Option<f64>,
+}
+
+impl ValueRange {
+    /// Returns `true` if `value` lies within the range. NaN lies within no bounded range.
+    pub fn contains(&self, value: f64) -> bool {
+        self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+    }
+
+    /// Returns `value` moved to the nearest bound if it lies outside the range.
+    pub fn clamp(&self, value: f64) -> f64 {
+        let value = self.min.map_or(value, |min| value.max(min));
+        self.max.map_or(value, |max| value.min(max))
+    }
+}
+
+/// Implements the `Display` trait for `ValueRange`, e.g. `[0, 100]`, with a
+/// missing bound shown as `-inf` or `inf`.
+impl fmt::Display for ValueRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}, {}]",
+            self.min.unwrap_or(f64::NEG_INFINITY),
+            self.max.unwrap_or(f64::INFINITY)
+        )
+    }
+}
+
+/// Settings that change how input is evaluated.
+///
+/// Each `SimpleExecutor` carries its own options, so they apply to every evaluation
+/// run against it, e.g. for the whole of a REPL session.
+#[derive(Debug, Clone)]
+pub struct EvalOptions {
+    /// Calculator mode: a line ending in a bare `=`, such as `2 + 2 =`, evaluates the
+    /// expression before the `=` instead of being a syntax error.
+    pub calculator_mode: bool,
+    /// Multi-statement mode: `;` separates statements on one line, so `1 + 1; 2 + 2`
+    /// produces two results, instead of starting a comment.
+    pub multi_statement: bool,
+    /// Tape mode: a line starting with a binary operator, such as `+ 5` or `* 2`,
+    /// applies it to the previous result, `ans`, as on a calculator tape.
+    pub tape_mode: bool,
+    /// Strict mode: `let` on a variable that is already defined, including the predefined
+    /// constants, fails with `ExecError::AlreadyDefined` instead of replacing its value.
+    pub strict: bool,
+    /// The range every expression result must lie in; a result outside it is an error.
+    pub range: ValueRange,
+    /// Instead of failing, move a result outside `range` to the nearest bound, logging a warning.
+    pub clamp_to_range: bool,
+    /// The character(s) that start a comment running to the end of the line; `;` by default.
+    pub comment_delimiter: String,
+    /// The unit `sin`, `cos`, `tan`, and `normalize_angle` measure angles in.
+    pub angle_mode: AngleMode,
+    /// Evaluate the longest statement at the start of a line and warn about the tokens
+    /// after it, instead of rejecting the line, e.g. `1 + 2 , 3` gives `3`.
+    pub allow_trailing_garbage: bool,
+    /// Read a comma between digits of a number's integer part that is followed by exactly
+    /// three digits as a thousands separator, so `1,234,567` is `1234567`.
+    pub thousands_commas: bool,
+    /// How deeply expressions may nest, counting parentheses, operands, and prefix
+    /// operators, before parsing fails with `ParserError::RecursionLimitExceeded`.
+    pub max_depth: usize,
+    /// Log a warning whenever `/` divides two whole numbers into a result that is not
+    /// one, e.g. `7 / 2`, which integer division would truncate. The result is unchanged.
+    pub warn_truncation: bool,
+}
+
+impl Default for EvalOptions {
+    fn default() -> Self {
+        EvalOptions {
+            calculator_mode: false,
+            multi_statement: false,
+            tape_mode: false,
+            strict: false,
+            range: ValueRange::default(),
+            clamp_to_range: false,
+            comment_delimiter: DEFAULT_COMMENT_DELIMITER.to_string(),
+            angle_mode: AngleMode::default(),
+            allow_trailing_garbage: false,
+            thousands_commas: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            warn_truncation: false,
+        }
+    }
+}
+
+/// The divisor of the postfix percent operator: `50%` is `50 / 100`.
+const PERCENT: Value = Value::Int(100);
+
+/// The maximum number of user-defined function calls that may be active at once, which
+/// bounds recursion such as `let f(n) = f(n + 1)`.
+pub const MAX_CALL_DEPTH: usize = 100;
+
+/// A user-defined function, e.g. `let sq(x) = x * x`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    /// The names of the parameters, in order.
+    pub params: Vec<String>,
+    /// The body, evaluated with the parameters bound to the call's arguments.
+    pub body: Expr,
+    /// The body compiled to bytecode.
+    code: Vec<Instr>,
+}
+
+/// Statistics about the most recent call to `SimpleExecutor::execute`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExecStats {
+    /// The number of instructions passed to `execute`.
+    pub compiled: usize,
+    /// The number of instructions executed, including those of any user-defined
+    /// functions called. Instructions skipped by a jump are not counted.
+    pub executed: usize,
+    /// How long execution took, not counting tokenizing, parsing, or compiling.
+    pub elapsed: Duration,
 }
 
 /// A simple stack-based executor for `arith` bytecode.
 ///
-/// This executor is stateless and evaluates a given slice of `Instr` (bytecode)
-/// to produce a single floating-point result.
-pub struct SimpleExecutor;
+/// This executor evaluates a given slice of `Instr` (bytecode) to produce a single
+/// floating-point result. It owns the symbol table holding variables, which persists
+/// across calls to `execute` so that definitions from one line are visible in the next.
+/// User-defined functions are kept in a separate table, and each active call gets a
+/// frame binding its parameters, which shadow variables of the same name.
+///
+/// The symbol table is a `BTreeMap`, so variables are always iterated in name order and
+/// listings such as `:vars` are reproducible from run to run.
+#[derive(Clone)]
+pub struct SimpleExecutor {
+    symbols: BTreeMap<String, Value>,
+    functions: HashMap<String, Rc<Function>>,
+    frames: Vec<HashMap<String, Value>>,
+    results: Vec<Value>,
+    options: EvalOptions,
+    executed: usize,
+    last_stats: Option<ExecStats>,
+    undo_stack: VecDeque<(String, Option<Value>)>,
+    rng: builtins::Rng,
+}
+
+/// The state of a `SimpleExecutor` saved by `snapshot`, which `restore` returns it to.
+///
+/// A snapshot holds the variables, functions, results, undo history, and options, so
+/// restoring one also reverts settings such as the angle mode. The state of `rand` is
+/// not saved, so numbers drawn after a `restore` are not repeats of earlier ones.
+#[derive(Clone)]
+pub struct ExecutorSnapshot {
+    symbols: BTreeMap<String, Value>,
+    functions: HashMap<String, Rc<Function>>,
+    results: Vec<Value>,
+    options: EvalOptions,
+    undo_stack: VecDeque<(String, Option<Value>)>,
+}
+
+impl Default for SimpleExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl SimpleExecutor {
-    /// Creates a new `SimpleExecutor`.
+    /// Creates a new `SimpleExecutor` whose symbol table holds only the built-in constants
+    /// `pi`, `e`, and `tau`, and the running `total`, which starts at `0`.
+    ///
+    /// The constants are ordinary variables: `let pi = 3` shadows the built-in value
+    /// for the rest of the session.
     pub fn new() -> Self {
-        SimpleExecutor
+        let symbols = BTreeMap::from([
+            ("pi".to_string(), Value::Float(std::f64::consts::PI)),
+            ("e".to_string(), Value::Float(std::f64::consts::E)),
+            ("tau".to_string(), Value::Float(std::f64::consts::TAU)),
+            (TOTAL_VARIABLE.to_string(), Value::Int(0)),
+        ]);
+        SimpleExecutor {
+            symbols,
+            functions: HashMap::new(),
+            frames: Vec::new(),
+            results: Vec::new(),
+            options: EvalOptions::default(),
+            executed: 0,
+            last_stats: None,
+            undo_stack: VecDeque::new(),
+            rng: builtins::Rng::from_entropy(),
+        }
+    }
+
+    /// Creates a new `SimpleExecutor` like `new`, but with `rand` seeded with `seed`, so
+    /// it produces the same numbers every run.
+    pub fn with_seed(seed: u64) -> Self {
+        SimpleExecutor {
+            rng: builtins::Rng::new(seed),
+            ..Self::new()
+        }
+    }
+
+    /// Creates a new `SimpleExecutor` like `new`, but evaluating with the given options.
+    pub fn with_options(options: EvalOptions) -> Self {
+        SimpleExecutor {
+            options,
+            ..Self::new()
+        }
+    }
+
+    /// Returns the options this executor evaluates with.
+    pub fn options(&self) -> &EvalOptions {
+        &self.options
+    }
+
+    /// Returns the options this executor evaluates with, for changing them mid-session.
+    pub fn options_mut(&mut self) -> &mut EvalOptions {
+        &mut self.options
+    }
+
+    /// Returns the value of the variable `name` as an `f64`, or `None` if it is not defined.
+    pub fn variable(&self, name: &str) -> Option<f64> {
+        self.value(name).map(Value::as_f64)
+    }
+
+    /// Returns the typed value of the variable `name`, or `None` if it is not defined.
+    pub fn value(&self, name: &str) -> Option<Value> {
+        self.symbols.get(name).copied()
+    }
+
+    /// Returns an iterator over every defined variable and its value, sorted by name.
+    pub fn variables(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.symbols.iter()
+    }
+
+    /// Renames the variable `old` to `new`, keeping its value.
+    ///
+    /// # Returns
+    /// `true` if the variable was renamed, or `false` (leaving the symbol table unchanged)
+    /// if `old` is not defined or `new` already is.
+    pub fn rename_symbol(&mut self, old: &str, new: &str) -> bool {
+        if self.symbols.contains_key(new) {
+            return false;
+        }
+        match self.symbols.remove(old) {
+            Some(value) => {
+                self.symbols.insert(new.to_string(), value);
+                for (name, _) in self.undo_stack.iter_mut().filter(|(name, _)| name == old) {
+                    *name = new.to_string();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Defines (or redefines) the function `name`, compiling its body.
+    ///
+    /// # Returns
+    /// `Err` containing a `CompileError` if the body could not be compiled, e.g. because
+    /// it contains a constant division by zero.
+    pub fn define_function(
+        &mut self,
+        name: &str,
+        params: Vec<String>,
+        body: Expr,
+    ) -> Result<(), CompileError> {
+        let code = BytecodeCompiler::compile(&body)?;
+        let function = Function { params, body, code };
+        self.functions.insert(name.to_string(), Rc::new(function));
+        Ok(())
+    }
+
+    /// Returns the user-defined function `name`, or `None` if there is none.
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.get(name).map(Rc::as_ref)
+    }
+
+    /// Calls the function `name` with `args`: the user-defined function of that name if
+    /// there is one, otherwise the built-in.
+    fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, ExecError> {
+        let Some(function) = self.functions.get(name).cloned() else {
+            let args: Vec<f64> = args.into_iter().map(Value::as_f64).collect();
+            return self.run_builtin(name, &args);
+        };
+        check_call(name, &function, args.len(), self.frames.len())?;
+        self.frames
+            .push(function.params.iter().cloned().zip(args).collect());
+        let result = self.run(&function.code);
+        self.frames.pop();
+        result
+    }
+
+    /// Calls the built-in function `name`, drawing from this executor's generator for `rand`.
+    ///
+    /// Like a number literal, a whole-number result is an `Int`, so `let n: Int = 5!` and
+    /// `let n: Int = gcd(12, 18)` type-check. As with the operators, an infinite or NaN
+    /// result of finite arguments, such as `171!` or `sqrt(-1)`, is an
+    /// `ExecError::NonFinite`; one that follows from a non-finite argument is passed on.
+    fn call_builtin(&self, name: &str, args: &[f64]) -> Result<Value, ExecError> {
+        let result = if name == builtins::RAND {
+            builtins::rand(args, &self.rng)?
+        } else {
+            builtins::call(name, args, self.options.angle_mode)?
+        };
+        if !result.is_finite() && args.iter().all(|arg| arg.is_finite()) {
+            return Err(ExecError::NonFinite {
+                op: name.to_string(),
+                result,
+            });
+        }
+        Ok(Value::from_literal(result))
+    }
+
+    /// Calls the built-in function `name` like `call_builtin`, as the bytecode does, also
+    /// carrying out the side effect of `print`, which writes its argument to stderr.
+    fn run_builtin(&self, name: &str, args: &[f64]) -> Result<Value, ExecError> {
+        let result = self.call_builtin(name, args)?;
+        if name == builtins::PRINT {
+            builtins::print(result.as_f64());
+        }
+        Ok(result)
+    }
+
+    /// Returns the statistics of the most recent call to `execute`, or `None` if nothing
+    /// has been executed yet.
+    pub fn last_stats(&self) -> Option<ExecStats> {
+        self.last_stats
+    }
+
+    /// Returns every result produced so far, oldest first; `$1` refers to the first.
+    pub fn results(&self) -> &[Value] {
+        &self.results
+    }
+
+    /// Returns the result `$index`, or `ExecError::HistoryIndex` if there is no such result.
+    fn result(&self, index: usize) -> Result<Value, ExecError> {
+        index
+            .checked_sub(1)
+            .and_then(|i| self.results.get(i))
+            .copied()
+            .ok_or(ExecError::HistoryIndex {
+                index,
+                available: self.results.len(),
+            })
+    }
+
+    /// Appends `value` to the results that `$N` references resolve against.
+    pub fn push_result(&mut self, value: Value) {
+        self.results.push(value);
+    }
+
+    /// Reverts the most recent change made to a variable by a `let`, an assignment, or a
+    /// compound assignment. Undoing the `let` that first defined a variable removes it.
+    /// Only the last `UNDO_LIMIT` changes are remembered.
+    ///
+    /// # Returns
+    /// The name of the variable and the value it was restored to (`None` if it was
+    /// removed), or `None` if there is nothing to undo.
+    pub fn undo(&mut self) -> Option<(String, Option<Value>)> {
+        let (name, previous) = self.undo_stack.pop_back()?;
+        match previous {
+            Some(value) => self.symbols.insert(name.clone(), value),
+            None => self.symbols.remove(&name),
+        };
+        Some((name, previous))
+    }
+
+    /// Saves the executor's state, so evaluation can be tried and then rolled back with
+    /// `restore` if it fails partway.
+    pub fn snapshot(&self) -> ExecutorSnapshot {
+        ExecutorSnapshot {
+            symbols: self.symbols.clone(),
+            functions: self.functions.clone(),
+            results: self.results.clone(),
+            options: self.options.clone(),
+            undo_stack: self.undo_stack.clone(),
+        }
+    }
+
+    /// Returns the executor to the state saved in `snapshot`, discarding every variable,
+    /// function, result, and option change made since.
+    pub fn restore(&mut self, snapshot: ExecutorSnapshot) {
+        self.symbols = snapshot.symbols;
+        self.functions = snapshot.functions;
+        self.results = snapshot.results;
+        self.options = snapshot.options;
+        self.undo_stack = snapshot.undo_stack;
+    }
+
+    /// Records that `name` held `previous` before a `Store` or `Assign`, for `undo`.
+    fn record_undo(&mut self, name: &str, previous: Option<Value>) {
+        if self.undo_stack.len() == UNDO_LIMIT {
+            self.undo_stack.pop_front();
+        }
+        self.undo_stack.push_back((name.to_string(), previous));
+    }
+
+    /// Defines the variable `name` with the float `value`, replacing any previous value.
+    pub fn set_variable(&mut self, name: &str, value: f64) {
+        self.set_value(name, Value::Float(value));
+    }
+
+    /// Defines the variable `name` with `value`, replacing any previous value.
+    pub fn set_value(&mut self, name: &str, value: Value) {
+        self.symbols.insert(name.to_string(), value);
     }
 
     /// Executes a sequence of bytecode instructions.
     ///
     /// The executor maintains an internal stack for intermediate calculations.
+    /// `Store` and `Assign` instructions update the executor's symbol table.
+    /// Afterwards, whether or not execution succeeded, `last_stats` reports how many
+    /// instructions ran and how long they took.
     ///
     /// # Arguments
     /// * `instructions` - A slice of `Instr` to be executed.
     ///
     /// # Returns
-    /// A `Result` which is `Ok` containing the final `f64` result on successful execution,
+    /// A `Result` which is `Ok` containing the final `Value` on successful execution,
     /// or `Err` containing an `ExecError` if a runtime error occurs (e.g., stack underflow, division by zero).
-    pub fn execute(&self, instructions: &[Instr]) -> Result<f64, ExecError> {
-        let mut stack: Vec<f64> = Vec::with_capacity(16);
+    pub fn execute(&mut self, instructions: &[Instr]) -> Result<Value, ExecError> {
+        self.executed = 0;
+        let start = Instant::now();
+        let result = self.run(instructions);
+        self.last_stats = Some(ExecStats {
+            compiled: instructions.len(),
+            executed: self.executed,
+            elapsed: start.elapsed(),
+        });
+        result
+    }
 
-        for instr in instructions {
+    /// Executes `instructions` like `execute`, without recording statistics, counting
+    /// each instruction executed in `self.executed`.
+    fn run(&mut self, instructions: &[Instr]) -> Result<Value, ExecError> {
+        let mut stack: Vec<Value> = Vec::with_capacity(16);
+        let mut pc = 0; // index of the next instruction to execute
+
+        while let Some(instr) = instructions.get(pc) {
+            pc += 1;
+            self.executed += 1;
             match instr {
                 Instr::Push(n) => stack.push(*n),
-                Instr::Add => {
-                    let b = stack.pop().ok_or(ExecError::StackUnderflow {
-                        instr: "Add".to_string(),
+                Instr::Load(name) => {
+                    let value = self
+                        .frames
+                        .last()
+                        .and_then(|frame| frame.get(name))
+                        .or_else(|| self.symbols.get(name))
+                        .ok_or_else(|| ExecError::UndefinedVariable { name: name.clone() })?;
+                    stack.push(*value);
+                }
+                Instr::LoadResult(index) => stack.push(self.result(*index)?),
+                Instr::Store(name) => {
+                    let value = stack.last().ok_or(ExecError::StackUnderflow {
+                        instr: "Store".to_string(),
                     })?;
-                    let a = stack.pop().ok_or(ExecError::StackUnderflow {
-                        instr: "Add".to_string(),
+                    if self.options.strict && self.symbols.contains_key(name) {
+                        return Err(ExecError::AlreadyDefined { name: name.clone() });
+                    }
+                    let previous = self.symbols.insert(name.clone(), *value);
+                    self.record_undo(name, previous);
+                }
+                Instr::Assign(name) => {
+                    let value = stack.last().ok_or(ExecError::StackUnderflow {
+                        instr: "Assign".to_string(),
                     })?;
-                    stack.push(a + b);
+                    match self.symbols.get_mut(name) {
+                        Some(slot) => {
+                            let previous = std::mem::replace(slot, *value);
+                            self.record_undo(name, Some(previous));
+                        }
+                        None => {
+                            return Err(ExecError::UndefinedVariable { name: name.clone() });
+                        }
+                    }
                 }
-                Instr::Sub => {
-                    let b = stack.pop().ok_or(ExecError::StackUnderflow {
-                        instr: "Sub".to_string(),
+                Instr::Add
+                | Instr::Sub
+                | Instr::Mul
+                | Instr::Div
+                | Instr::Mod
+                | Instr::Pow
+                | Instr::Lt
+                | Instr::Gt
+                | Instr::Le
+                | Instr::Ge
+                | Instr::Eq
+                | Instr::Ne
+                | Instr::ApproxEq
+                | Instr::BitAnd
+                | Instr::BitOr
+                | Instr::Shl
+                | Instr::Shr => {
+                    let b = stack.pop().ok_or_else(|| ExecError::StackUnderflow {
+                        instr: format!("{:?}", instr),
                     })?;
-                    let a = stack.pop().ok_or(ExecError::StackUnderflow {
-                        instr: "Sub".to_string(),
+                    let a = stack.pop().ok_or_else(|| ExecError::StackUnderflow {
+                        instr: format!("{:?}", instr),
                     })?;
-                    stack.push(a - b);
+                    if let Some(result) = instr.apply_binary(a, b) {
+                        let result = result?;
+                        if self.options.warn_truncation
+                            && *instr == Instr::Div
+                            && loses_remainder(a, b, result)
+                        {
+                            log::warn!(
+                                "{} / {} is {}; integer division would truncate it to {}",
+                                a,
+                                b,
+                                result,
+                                result.as_f64().trunc()
+                            );
+                        }
+                        stack.push(result);
+                    }
                 }
-                Instr::Mul => {
-                    let b = stack.pop().ok_or(ExecError::StackUnderflow {
-                        instr: "Mul".to_string(),
+                Instr::Neg => {
+                    let a = stack.pop().ok_or(ExecError::StackUnderflow {
+                        instr: "Neg".to_string(),
                     })?;
+                    stack.push(-a);
+                }
+                Instr::BitNot => {
                     let a = stack.pop().ok_or(ExecError::StackUnderflow {
-                        instr: "Mul".to_string(),
+                        instr: "BitNot".to_string(),
                     })?;
-                    stack.push(a * b);
+                    stack.push(bit_not(a)?);
                 }
-                Instr::Div => {
-                    let b = stack.pop().ok_or(ExecError::StackUnderflow {
-                        instr: "Div".to_string(),
+                Instr::CheckType(expected) => {
+                    let value = stack.last_mut().ok_or(ExecError::StackUnderflow {
+                        instr: "CheckType".to_string(),
                     })?;
-                    if b == 0.0 {
-                        return Err(ExecError::DivisionByZero);
+                    match (expected, *value) {
+                        (ValueType::Float, Value::Int(n)) => *value = Value::Float(n as f64),
+                        (ValueType::Int, Value::Float(_)) => {
+                            return Err(ExecError::TypeMismatch {
+                                expected: *expected,
+                                found: *value,
+                            });
+                        }
+                        _ => {}
                     }
-                    let a = stack.pop().ok_or(ExecError::StackUnderflow {
-                        instr: "Div".to_string(),
-                    })?;
-                    stack.push(a / b);
                 }
-                Instr::Neg => {
-                    let a = stack.pop().ok_or(ExecError::StackUnderflow {
-                        instr: "Neg".to_string(),
+                Instr::JumpIfZero(offset) => {
+                    let cond = stack.pop().ok_or(ExecError::StackUnderflow {
+                        instr: "JumpIfZero".to_string(),
                     })?;
-                    stack.push(-a);
+                    if cond.as_f64() == 0.0 {
+                        pc += offset;
+                    }
+                }
+                Instr::Jump(offset) => pc += offset,
+                Instr::CallBuiltin(name, argc) => {
+                    if stack.len() < *argc {
+                        return Err(ExecError::StackUnderflow {
+                            instr: format!("CallBuiltin({})", name),
+                        });
+                    }
+                    let args: Vec<f64> = stack
+                        .split_off(stack.len() - argc)
+                        .into_iter()
+                        .map(Value::as_f64)
+                        .collect();
+                    stack.push(self.run_builtin(name, &args)?);
+                }
+                Instr::Call(name, argc) => {
+                    if stack.len() < *argc {
+                        return Err(ExecError::StackUnderflow {
+                            instr: format!("Call({})", name),
+                        });
+                    }
+                    let args = stack.split_off(stack.len() - argc);
+                    stack.push(self.call(name, args)?);
                 }
             }
         }
@@ -307,118 +1453,461 @@ impl SimpleExecutor {
     }
 }
 
+/// The outcome of evaluating one logical expression: its value and source text, or an error.
+pub type EvalResult = Result<(f64, String), EvalError>;
+
 /// Orchestrates the entire evaluation process for a multi-line input string.
 ///
-/// This function handles line continuations (lines ending with `\`),
-/// removes comments, tokenizes each logical expression, parses it into an AST,
-/// compiles the AST into bytecode, and finally executes the bytecode.
-///
-/// It processes the input line by line, accumulating lines that end with a backslash
-/// into a single logical expression. Each logical expression is then evaluated independently.
+/// This collects the results of `evaluate_stream`, so every expression is evaluated
+/// before it returns.
 ///
 /// # Arguments
 /// * `input` - The multi-line input string containing arithmetic expressions.
+/// * `executor` - The executor whose symbol table the expressions are evaluated against.
 ///
 /// # Returns
 /// A `Vec` of `Result`s, where each `Result` corresponds to the evaluation of one
 /// logical expression. An `Ok` variant contains a tuple of the `f64` result and
 /// the original expression string. An `Err` variant contains an `EvalError`
 /// providing details about the error.
-pub fn evaluate_lines(input: &str) -> Vec<Result<(f64, String), EvalError>> {
-    let mut joined_expressions: Vec<(String, usize)> = Vec::new();
-    let mut current_expression_buffer = String::new();
-    let mut current_expression_start_line = 0;
+pub fn evaluate_lines(input: &str, executor: &mut SimpleExecutor) -> Vec<EvalResult> {
+    evaluate_stream(input, executor).collect()
+}
 
-    for (idx, raw_line) in input.lines().enumerate() {
-        let line_num = idx + 1; // 1-based line number
+/// Evaluates a multi-line input string lazily, one logical expression per call to `next`.
+///
+/// Nothing is evaluated until the iterator is advanced, and each step reads only as many
+/// lines as it takes to complete the next expression that produces a result, so
+/// definitions made by earlier expressions are visible to later ones exactly as with
+/// `evaluate_lines`. Dropping the iterator early leaves the remaining lines unevaluated.
+///
+/// # Arguments
+/// * `input` - The multi-line input string containing arithmetic expressions.
+/// * `executor` - The executor whose symbol table the expressions are evaluated against.
+///
+/// # Returns
+/// An iterator over the same `EvalResult`s, in the same order, that `evaluate_lines`
+/// returns.
+pub fn evaluate_stream<'a>(
+    input: &'a str,
+    executor: &'a mut SimpleExecutor,
+) -> impl Iterator<Item = EvalResult> + 'a {
+    LineEvaluator::new(input, executor, 1..=usize::MAX).map(|(_, result)| result)
+}
 
-        // Remove comments
-        let line_without_comment = raw_line.split(';').next().unwrap_or("").to_string();
+/// Evaluates a multi-line input string, pairing each result with its source line number.
+///
+/// This collects the results reported by `evaluate_lines_streaming`.
+///
+/// # Arguments
+/// * `input` - The multi-line input string containing arithmetic expressions.
+/// * `executor` - The executor whose symbol table the expressions are evaluated against.
+///
+/// # Returns
+/// A `Vec` of `(line, result)` pairs, one per logical expression. `line` is the 1-based
+/// source line the expression starts on (the first line of a continued expression), and
+/// `result` is as described for `evaluate_lines`.
+pub fn evaluate_numbered_lines(
+    input: &str,
+    executor: &mut SimpleExecutor,
+) -> Vec<(usize, EvalResult)> {
+    let mut results = Vec::new();
+    evaluate_lines_streaming(input, executor, |line, result| results.push((line, result)));
+    results
+}
+
+/// Evaluates a multi-line input string, reporting each result as soon as it is produced.
+///
+/// This function handles line continuations (lines ending with `\`),
+/// removes comments, tokenizes each logical expression, parses it into an AST,
+/// compiles the AST into bytecode, and finally executes the bytecode.
+///
+/// It processes the input line by line, accumulating lines that end with a backslash
+/// into a single logical expression. Each logical expression is evaluated against
+/// `executor` as soon as it is complete, so variables defined by earlier lines are visible
+/// to later ones, and its result is handed to `on_result` before the next line is read.
+/// No results are buffered, so memory use does not grow with the number of expressions.
+///
+/// Definitions and assignments (`let x = 1`, `x = 2`, `x += 1`) update the executor's
+/// symbol table and produce the value they stored; function definitions (`let f(x) = x * x`)
+/// produce no result. Only the result of a bare expression is stored in the `ans` variable,
+/// so the last one wins, appended to the results that `$N` references resolve against,
+/// and added to the `total` variable, except for a line that is just `total` itself.
+///
+/// # Arguments
+/// * `input` - The multi-line input string containing arithmetic expressions.
+/// * `executor` - The executor whose symbol table the expressions are evaluated against.
+/// * `on_result` - Called in source order with the 1-based line the expression starts on
+///   and its `EvalResult`.
+pub fn evaluate_lines_streaming<F>(input: &str, executor: &mut SimpleExecutor, on_result: F)
+where
+    F: FnMut(usize, EvalResult),
+{
+    evaluate_selected_lines(input, executor, 1..=usize::MAX, on_result);
+}
+
+/// Like `evaluate_lines_streaming`, but evaluates only the expressions that start on a
+/// 1-based source line within `selection`; the others are skipped without being evaluated.
+///
+/// An expression continued with `\` is selected by the line it starts on, and still reads
+/// its continuation lines even if they lie past the end of `selection`.
+pub fn evaluate_selected_lines<F>(
+    input: &str,
+    executor: &mut SimpleExecutor,
+    selection: RangeInclusive<usize>,
+    mut on_result: F,
+) where
+    F: FnMut(usize, EvalResult),
+{
+    for (line, result) in LineEvaluator::new(input, executor, selection) {
+        on_result(line, result);
+    }
+}
 
-        let trimmed_line_content = line_without_comment.trim(); // Trim all whitespace
+/// The iterator behind `evaluate_stream` and `evaluate_selected_lines`. It yields
+/// `(line, result)` pairs, reading and evaluating input only as each pair is requested.
+struct LineEvaluator<'a> {
+    input: &'a str,
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+    executor: &'a mut SimpleExecutor,
+    selection: RangeInclusive<usize>,
+    /// Statements of the last completed expression that have not been evaluated yet, with
+    /// the line the expression starts on. Holds more than one only in multi-statement mode.
+    pending: VecDeque<(usize, String)>,
+    finished: bool,
+}
 
-        if current_expression_buffer.is_empty() {
-            current_expression_start_line = line_num;
+impl<'a> LineEvaluator<'a> {
+    fn new(
+        input: &'a str,
+        executor: &'a mut SimpleExecutor,
+        selection: RangeInclusive<usize>,
+    ) -> Self {
+        LineEvaluator {
+            input,
+            lines: input.lines().enumerate(),
+            executor,
+            selection,
+            pending: VecDeque::new(),
+            finished: false,
         }
+    }
+
+    /// Reads lines until the next logical expression is complete and queues its
+    /// statements in `pending`. At the end of the input, sets `finished` and returns the
+    /// error for an unterminated continuation, if there is one.
+    fn read_expression(&mut self) -> Option<(usize, EvalResult)> {
+        let mut current_expression_buffer = String::new();
+        let mut current_expression_start_line = 0;
+        let mut continued_by_operator = false;
+
+        for (idx, raw_line) in self.lines.by_ref() {
+            let line_num = idx + 1; // 1-based line number
+
+            // Remove the comment before looking for a trailing `\`, so `1 + \ ; note` still
+            // continues and a `\` inside a comment does not.
+            let trimmed_line_content = strip_comment(raw_line, self.executor.options()).trim();
+
+            if current_expression_buffer.is_empty() {
+                current_expression_start_line = line_num;
+            }
+
+            if trimmed_line_content.ends_with('\\') {
+                // This line continues the expression
+                current_expression_buffer
+                    .push_str(trimmed_line_content[0..trimmed_line_content.len() - 1].trim());
+                current_expression_buffer.push(' '); // Add a space for token separation
+                continued_by_operator = false;
+                continue;
+            }
+            if ends_with_operator(trimmed_line_content) {
+                // A trailing operator continues the expression too, but is part of it.
+                current_expression_buffer.push_str(trimmed_line_content);
+                current_expression_buffer.push(' ');
+                continued_by_operator = true;
+                continue;
+            }
 
-        if trimmed_line_content.ends_with('\\') {
-            // This line continues the expression
-            current_expression_buffer
-                .push_str(&trimmed_line_content[0..trimmed_line_content.len() - 1].trim());
-            current_expression_buffer.push(' '); // Add a space for token separation
-        } else {
             // This line completes an expression or is a single-line expression
-            current_expression_buffer.push_str(trimmed_line_content); // Add the content of the current line
+            current_expression_buffer.push_str(trimmed_line_content);
+            self.queue(current_expression_start_line, current_expression_buffer);
+            return None;
+        }
+
+        self.finished = true;
+
+        // If the input ends right after a trailing operator, parse the expression anyway,
+        // so it fails like any other one missing an operand.
+        if continued_by_operator {
+            self.queue(current_expression_start_line, current_expression_buffer);
+            return None;
+        }
+
+        // If the input ends with a backslash, the accumulated expression is incomplete.
+        // Report it instead of parsing a dangling fragment.
+        if !current_expression_buffer.trim().is_empty()
+            && self.selection.contains(&current_expression_start_line)
+        {
+            return Some((
+                current_expression_start_line,
+                Err(EvalError::UnterminatedContinuation(
+                    current_expression_buffer,
+                    self.input.lines().count(),
+                )),
+            ));
+        }
+        None
+    }
+
+    /// Queues the statements of the complete expression `expression`, which starts on
+    /// `line`, if that line is selected.
+    fn queue(&mut self, line: usize, expression: String) {
+        if !self.selection.contains(&line) {
+            return;
+        }
+        if self.executor.options().multi_statement {
+            self.pending.extend(
+                expression
+                    .split(';')
+                    .map(|statement| (line, statement.to_string())),
+            );
+        } else {
+            self.pending.push_back((line, expression));
+        }
+    }
+}
+
+impl Iterator for LineEvaluator<'_> {
+    type Item = (usize, EvalResult);
 
-            // Only push if the accumulated expression is not empty or just whitespace
-            if !current_expression_buffer.trim().is_empty() {
-                joined_expressions.push((
-                    current_expression_buffer.clone(),
-                    current_expression_start_line,
-                ));
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((line, statement)) = self.pending.pop_front() {
+                if let Some(result) = evaluate_expression(statement.trim(), line, self.executor) {
+                    return Some((line, result));
+                }
+                continue;
+            }
+            if self.finished {
+                return None;
+            }
+            if let Some(unterminated) = self.read_expression() {
+                return Some(unterminated);
             }
-            current_expression_buffer.clear();
-            current_expression_start_line = 0; // Reset
         }
     }
+}
 
-    // Handle any remaining accumulated expression if the file ends with a backslash
-    // and it's not just whitespace
-    if !current_expression_buffer.is_empty() && !current_expression_buffer.trim().is_empty() {
-        joined_expressions.push((
-            current_expression_buffer.clone(),
-            current_expression_start_line,
-        ));
+/// Removes the comment, if any, from a single source line.
+///
+/// In multi-statement mode `;` separates statements instead, so unless another comment
+/// delimiter is configured the line is returned whole, to be split once the expression
+/// is complete.
+pub fn strip_comment<'a>(line: &'a str, options: &EvalOptions) -> &'a str {
+    let delimiter = options.comment_delimiter.as_str();
+    if delimiter.is_empty() || (options.multi_statement && delimiter == ";") {
+        line
+    } else {
+        line.split(delimiter).next().unwrap_or("")
     }
+}
 
-    // Evaluate each joined line separately
-    let mut results = Vec::new();
-    let executor = SimpleExecutor::new();
+/// Returns `true` if the source line continues the expression onto the next line, ignoring
+/// its comment and trailing whitespace: it ends with a `\`, or with a binary operator as
+/// described for `ends_with_operator`.
+pub fn continues_line(line: &str, options: &EvalOptions) -> bool {
+    let content = strip_comment(line, options).trim_end();
+    content.ends_with('\\') || ends_with_operator(content)
+}
+
+/// The binary operators that continue an expression onto the next line when they end one.
+pub const CONTINUING_OPERATORS: &[char] = &['+', '-', '*', '/'];
+
+/// Returns `true` if `content`, a line without its comment, ends with one of
+/// `CONTINUING_OPERATORS`, like `1 +`, so the expression is missing its right operand.
+///
+/// A line holding nothing but operators, like a bare `+`, does not continue, so it is
+/// still reported as an error rather than silently joined to the next line.
+pub fn ends_with_operator(content: &str) -> bool {
+    let content = content.trim_end();
+    let operand =
+        content.trim_end_matches(|c: char| CONTINUING_OPERATORS.contains(&c) || c.is_whitespace());
+    content.ends_with(CONTINUING_OPERATORS) && !operand.is_empty()
+}
+
+/// Returns the name of the variable `stmt` stores its value in, or `None` for a bare
+/// expression or a function definition.
+fn stored_name(stmt: &Statement) -> Option<&str> {
+    match stmt {
+        Statement::Let { name, .. }
+        | Statement::Assignment { name, .. }
+        | Statement::CompoundAssignment { name, .. } => Some(name),
+        Statement::Expression(_) | Statement::FunctionDef { .. } => None,
+    }
+}
+
+/// Tokenizes, parses, compiles, and executes a single logical expression.
+///
+/// # Arguments
+/// * `line_str` - The logical expression, with comments and continuations already removed.
+/// * `original_line_offset` - The 1-based source line the expression starts on.
+/// * `executor` - The executor to run the compiled bytecode on.
+///
+/// # Returns
+/// `None` if the expression is empty or is a function definition that succeeded,
+/// otherwise `Some` containing its `EvalResult`. A variable definition or assignment
+/// that succeeded gives the value it stored.
+fn evaluate_expression(
+    line_str: &str,
+    original_line_offset: usize,
+    executor: &mut SimpleExecutor,
+) -> Option<EvalResult> {
+    let trimmed = line_str.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
 
-    for (line_str, original_line_offset) in joined_expressions {
-        let trimmed = line_str.trim();
-        if trimmed.is_empty() {
-            continue;
+    let mut tokens = match Tokenizer::new(trimmed.to_string())
+        .with_comment_delimiter(&executor.options().comment_delimiter)
+        .with_thousands_commas(executor.options().thousands_commas)
+        .tokenize()
+    {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            log::debug!("TokenizerError: {}", e);
+            return Some(Err(EvalError::Parse(
+                ParserError::from(e),
+                line_str.to_string(), // Pass the specific line string
+                original_line_offset,
+            )));
         }
+    };
 
-        let tokens = match Tokenizer::new(trimmed.to_string()).tokenize() {
-            Ok(tokens) => tokens,
-            Err(TokenizerError::UnexpectedCharacter { found, line, col }) => {
-                eprintln!("DEBUG: TokenizerError line = {}, col = {}", line, col);
-                results.push(Err(EvalError::Parse(
-                    ParserError::TokenizerError {
-                        message: format!("Unexpected character '{}'", found),
-                        line,
-                        col,
-                    },
-                    line_str.to_string(), // Pass the specific line string
-                    original_line_offset,
-                )));
-                continue;
-            }
-        };
+    if executor.options().calculator_mode {
+        strip_calculator_equals(&mut tokens);
+    }
+    if executor.options().tape_mode && executor.variable(ANS_VARIABLE).is_some() {
+        continue_previous_result(&mut tokens);
+    }
 
-        match Parser::new(tokens).parse() {
-            Ok(ast) => match BytecodeCompiler::compile(&ast) {
-                Ok(code) => {
-                    if !code.is_empty() {
-                        match executor.execute(&code) {
-                            Ok(v) => results.push(Ok((v, line_str.to_string()))),
-                            Err(e) => results.push(Err(EvalError::Exec(e, line_str.to_string()))),
+    let mut parser = Parser::new(tokens).with_max_depth(executor.options().max_depth);
+    let parsed = if executor.options().allow_trailing_garbage {
+        let parsed = parser.parse_prefix();
+        if parsed.is_ok()
+            && let Some(first) = parser.remaining().first()
+        {
+            let ignored: String = trimmed.chars().skip(first.get_start() - 1).collect();
+            log::warn!("ignoring trailing '{}' in '{}'", ignored, trimmed);
+        }
+        parsed
+    } else {
+        parser.parse()
+    };
+
+    match parsed {
+        Ok(Statement::FunctionDef { name, params, body }) => executor
+            .define_function(&name, params, body)
+            .err()
+            .map(|e| Err(EvalError::Compile(e, line_str.to_string()))),
+        Ok(stmt) => match BytecodeCompiler::compile_statement(&stmt) {
+            Ok(code) => {
+                if code.is_empty() {
+                    return None;
+                }
+                match executor.execute(&code) {
+                    Ok(v) => {
+                        // Every result, including the value a definition or assignment
+                        // stored, must lie in the range. An out-of-range value that was
+                        // stored is replaced by the clamped one, or taken back again.
+                        let range = executor.options().range;
+                        let v = if range.contains(v.as_f64()) {
+                            v
+                        } else if executor.options().clamp_to_range {
+                            let clamped = Value::from_literal(range.clamp(v.as_f64()));
+                            log::warn!(
+                                "result {} of '{}' is outside {}, clamped to {}",
+                                v,
+                                line_str.trim(),
+                                range,
+                                clamped
+                            );
+                            if let Some(name) = stored_name(&stmt) {
+                                executor.set_value(name, clamped);
+                            }
+                            clamped
+                        } else {
+                            if stored_name(&stmt).is_some() {
+                                executor.undo();
+                            }
+                            return Some(Err(EvalError::OutOfRange(
+                                v.as_f64(),
+                                range,
+                                line_str.to_string(),
+                            )));
+                        };
+                        // Definitions and assignments report the value they stored, but
+                        // only bare expressions update `ans`, `$N` and the running total.
+                        if let Statement::Expression(expr) = &stmt {
+                            executor.set_value(ANS_VARIABLE, v);
+                            executor.push_result(v);
+                            // Add the result to the running total, unless the line
+                            // only reads the total back.
+                            if *expr != Expr::Variable(TOTAL_VARIABLE.to_string()) {
+                                let total = executor.value(TOTAL_VARIABLE).unwrap_or(Value::Int(0));
+                                executor.set_value(TOTAL_VARIABLE, total + v);
+                            }
                         }
+                        Some(Ok((v.as_f64(), line_str.to_string())))
                     }
+                    Err(e) => Some(Err(EvalError::Exec(e, line_str.to_string()))),
                 }
-                Err(e) => results.push(Err(EvalError::Compile(e, line_str.to_string()))),
-            },
-            Err(e) => {
-                results.push(Err(EvalError::Parse(
-                    e,
-                    line_str.to_string(),
-                    original_line_offset,
-                ))); // Pass the specific line string and offset
             }
-        }
+            Err(e) => Some(Err(EvalError::Compile(e, line_str.to_string()))),
+        },
+        // Pass the specific line string and offset
+        Err(e) => Some(Err(EvalError::Parse(
+            e,
+            line_str.to_string(),
+            original_line_offset,
+        ))),
     }
+}
 
-    results
+/// Prepends `ans` to a tape-style line that starts with a binary operator, so `* 2`
+/// becomes `ans * 2`.
+///
+/// A leading `+` or `-` only continues the previous result when it is followed by
+/// whitespace: `- 3` subtracts 3 from `ans`, while `-3` is still the number minus three.
+fn continue_previous_result(tokens: &mut Vec<Token>) {
+    let (Some(first), Some(second)) = (tokens.first(), tokens.get(1)) else {
+        return;
+    };
+    let continues = match first.get_type() {
+        TokenType::Mul | TokenType::Div => true,
+        TokenType::Plus | TokenType::Minus => second.get_start() > first.get_end() + 1,
+        _ => false,
+    };
+    if continues {
+        let ans = Token::identifier(ANS_VARIABLE, first.get_line_no(), first.get_start());
+        tokens.insert(0, ans);
+    }
+}
+
+/// Removes the trailing `=` of a calculator-style line such as `2 + 2 =`.
+///
+/// The `=` is left alone when the line is a definition or assignment (`let x =`, `x =`),
+/// so those still report their missing right-hand side.
+fn strip_calculator_equals(tokens: &mut Vec<Token>) {
+    // The token stream always ends with EOF, so the `=` would be second to last.
+    let Some(equals_at) = tokens.len().checked_sub(2) else {
+        return;
+    };
+    let is_binding = match tokens.first().map(Token::get_type) {
+        Some(TokenType::Let) => true,
+        Some(TokenType::Identifier { .. }) => tokens[1].get_type().is_assignment(),
+        _ => false,
+    };
+    if equals_at > 0 && !is_binding && matches!(tokens[equals_at].get_type(), TokenType::Assign) {
+        tokens.remove(equals_at);
+    }
 }
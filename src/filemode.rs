@@ -3,24 +3,171 @@
 //! In file mode, the interpreter reads expressions from one or more specified files,
 //! evaluates them, and prints the results or any encountered errors to the console.
 
-use crate::executor::evaluate_lines;
-use std::fs::read_to_string;
+use crate::executor::{
+    EvalOptions, SimpleExecutor, evaluate_lines_streaming, evaluate_selected_lines, strip_comment,
+};
+use crate::format::{DEFAULT_PRECISION, FormatMode, format_value};
+use crate::repl::list_variables;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
 use std::path::Path;
+use std::str::FromStr;
+
+/// The text encoding input files are decoded with.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum InputEncoding {
+    /// UTF-8, the default. Files containing invalid UTF-8 are rejected.
+    #[default]
+    Utf8,
+    /// ISO-8859-1 (Latin-1). Every byte maps to the Unicode code point of the same value,
+    /// so any file can be decoded.
+    Latin1,
+}
+
+impl InputEncoding {
+    /// Decodes the raw contents of a file.
+    ///
+    /// # Returns
+    /// The decoded text, or an `io::Error` of kind `InvalidData` naming the offset of
+    /// the first byte that is not valid in this encoding.
+    pub fn decode(&self, bytes: Vec<u8>) -> io::Result<String> {
+        match self {
+            InputEncoding::Utf8 => String::from_utf8(bytes).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "input is not valid UTF-8 (invalid byte at offset {}); \
+                         use --input-encoding latin1 for Latin-1 files",
+                        e.utf8_error().valid_up_to()
+                    ),
+                )
+            }),
+            InputEncoding::Latin1 => Ok(bytes.into_iter().map(char::from).collect()),
+        }
+    }
+}
+
+/// Parses an encoding name as accepted by `--input-encoding`, e.g. `utf8` or `latin1`.
+impl FromStr for InputEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf8" | "utf-8" => Ok(InputEncoding::Utf8),
+            "latin1" | "latin-1" | "iso-8859-1" => Ok(InputEncoding::Latin1),
+            other => Err(format!(
+                "unknown encoding '{}' (expected utf8 or latin1)",
+                other
+            )),
+        }
+    }
+}
+
+/// Reads the file at `path` and decodes it with `encoding`.
+pub fn read_file<P: AsRef<Path>>(path: P, encoding: InputEncoding) -> io::Result<String> {
+    encoding.decode(fs::read(path)?)
+}
+
+/// Returns `source` with its comments removed, for the `strip-comments` subcommand.
+///
+/// Lines without a comment are kept exactly as written. A line with a trailing comment
+/// keeps its code, minus the whitespace before the comment, and a line holding only a
+/// comment is dropped.
+///
+/// # Arguments
+/// * `source` - The script to strip.
+/// * `options` - The options providing the comment delimiter, as for evaluation.
+pub fn strip_comments(source: &str, options: &EvalOptions) -> String {
+    let mut stripped = String::with_capacity(source.len());
+    for line in source.lines() {
+        let code = strip_comment(line, options);
+        if code.len() == line.len() {
+            stripped.push_str(line);
+        } else if code.trim().is_empty() {
+            continue;
+        } else {
+            stripped.push_str(code.trim_end());
+        }
+        stripped.push('\n');
+    }
+    stripped
+}
+
+/// Options controlling how file mode reports its results.
+#[derive(Debug, Default, Clone)]
+pub struct FileModeOptions {
+    /// Prefix each result with the source line number its expression starts on.
+    pub line_numbers: bool,
+    /// Evaluate all files against one shared executor, so variables defined in an
+    /// earlier file are visible in later ones. By default each file gets a fresh executor.
+    pub shared_state: bool,
+    /// The options every file is evaluated with.
+    pub eval_options: EvalOptions,
+    /// The encoding input files are decoded with.
+    pub input_encoding: InputEncoding,
+    /// Print every variable and its final value to `stderr` after each file, or once
+    /// after the last file when `shared_state` is set.
+    pub dump_symbols: bool,
+    /// The notation results are printed in.
+    pub format: FormatMode,
+    /// Print only the value of the last expression that evaluated successfully, without
+    /// headers, once per file or once after the last file when `shared_state` is set.
+    pub trailing_result: bool,
+    /// Print only the value of each successful expression, one per line, without
+    /// headers or the expression itself. Errors still go to `stderr`.
+    pub quiet: bool,
+    /// Evaluate only the expressions starting on these 1-based source lines of each file.
+    /// `None` evaluates every line.
+    pub lines: Option<RangeInclusive<usize>>,
+}
+
+/// Parses a `--range` argument of the form `A:B` into the 1-based lines `A..=B`.
+///
+/// # Returns
+/// The range, or a message explaining why `s` is not a valid, non-empty range of lines.
+pub fn parse_line_range(s: &str) -> Result<RangeInclusive<usize>, String> {
+    let invalid = || format!("invalid line range '{}' (expected A:B, e.g. 3:7)", s);
+    let (start, end) = s.split_once(':').ok_or_else(invalid)?;
+    let start: usize = start.trim().parse().map_err(|_| invalid())?;
+    let end: usize = end.trim().parse().map_err(|_| invalid())?;
+    if start == 0 || end < start {
+        return Err(format!(
+            "invalid line range '{}' (lines start at 1 and A must not exceed B)",
+            s
+        ));
+    }
+    Ok(start..=end)
+}
 
 /// Runs the `arith` interpreter in file mode, processing expressions from the given files.
 ///
-/// For each file, it reads the content, evaluates all expressions within it using
-/// `evaluate_lines`, and prints the results. Errors encountered during file reading
-/// or expression evaluation are reported to `stderr`.
+/// For each file, it reads the content, evaluates the expressions within it using
+/// `evaluate_selected_lines`, and prints each result as soon as it is produced. With
+/// `options.lines`, only the expressions starting on those lines are evaluated.
+/// Each file is evaluated against a fresh `SimpleExecutor` unless `options.shared_state`
+/// is set, in which case one executor is used for all files in the order given.
+/// Errors encountered during file reading or expression evaluation are reported to `stderr`.
+/// With `options.trailing_result`, every expression is still evaluated, but only the last
+/// successful result is printed. With `options.quiet`, each result is printed as a bare value.
+/// With `FormatMode::Tsv`, each expression is printed as a tab-separated `line`,
+/// `expression`, `result` row, and a failed one as `line`, `expression`, `ERROR`, `reason`,
+/// on `stdout` instead of `stderr`.
 ///
 /// # Arguments
 /// * `files` - A `Vec<String>` where each string is the path to an input file.
+/// * `options` - The `FileModeOptions` controlling the output format.
 ///
 /// # Returns
-/// A `std::io::Result<()>` which is `Ok(())` if all files were processed
-/// (even if some expressions within files resulted in errors), or `Err` if
+/// A `std::io::Result<usize>` which is `Ok` with the number of expressions that failed
+/// to evaluate (`0` if all succeeded) once all files were processed, or `Err` if
 /// there was an I/O error (e.g., file not found, permission denied).
-pub fn run_file_mode(files: Vec<String>) -> std::io::Result<()> {
+pub fn run_file_mode(files: Vec<String>, options: &FileModeOptions) -> std::io::Result<usize> {
+    let mut failures = 0;
+    let mut last_result = None;
+    let mut shared_executor = SimpleExecutor::with_options(options.eval_options.clone());
+
     for file_path_str in files {
         let path = Path::new(&file_path_str);
         let file_name = path
@@ -29,22 +176,176 @@ pub fn run_file_mode(files: Vec<String>) -> std::io::Result<()> {
             .unwrap_or(&file_path_str);
 
         log::info!("Processing file: {}", file_name);
-        let content = read_to_string(&file_path_str)?;
+        let content = read_file(&file_path_str, options.input_encoding)?;
 
-        println!("--- Results from {} ---", file_name);
+        let selection = options.lines.clone().unwrap_or(1..=usize::MAX);
+        let line_count = content.lines().count();
+        if *selection.start() > line_count {
+            eprintln!(
+                "Error in {}: line {} is out of range, the file has only {} lines",
+                file_name,
+                selection.start(),
+                line_count
+            );
+            failures += 1;
+            continue;
+        }
 
-        let results = evaluate_lines(&content);
+        let tsv = options.format == FormatMode::Tsv;
+        let headers = !options.trailing_result && !options.quiet && !tsv;
+        if headers {
+            println!("--- Results from {} ---", file_name);
+        }
 
-        for (idx, result) in results.into_iter().enumerate() {
-            // Added enumerate for expression number
+        let mut file_executor;
+        let executor = if options.shared_state {
+            &mut shared_executor
+        } else {
+            file_executor = SimpleExecutor::with_options(options.eval_options.clone());
+            &mut file_executor
+        };
+        let mut idx = 0; // expression number within the file
+        evaluate_selected_lines(&content, executor, selection, |line, result| {
+            idx += 1;
             match result {
-                Ok((val, expr_str)) => println!("{} [{}]: {}", expr_str, idx + 1, val), // New format
+                Ok((val, expr_str)) if tsv => {
+                    let value = format_value(val, options.format, DEFAULT_PRECISION);
+                    println!("{}\t{}\t{}", line, tsv_field(&expr_str), value);
+                }
+                Err(e) if tsv => {
+                    println!(
+                        "{}\t{}\tERROR\t{}",
+                        line,
+                        tsv_field(e.input()),
+                        tsv_field(&e.reason())
+                    );
+                    failures += 1;
+                }
+                Ok((val, _)) if options.trailing_result => last_result = Some(val),
+                Ok((val, expr_str)) => {
+                    let value = format_value(val, options.format, DEFAULT_PRECISION);
+                    if options.quiet {
+                        println!("{}", value);
+                    } else if options.line_numbers {
+                        println!("{}: {} [{}]: {}", line, expr_str, idx, value);
+                    } else {
+                        println!("{} [{}]: {}", expr_str, idx, value); // New format
+                    }
+                }
                 Err(e) => {
                     eprintln!("Error in {}: {}", file_name, e);
+                    failures += 1;
                 }
             }
+        });
+        if headers {
+            println!(); // Add a newline for separation between files
+        } else if options.trailing_result && !options.shared_state {
+            print_trailing_result(last_result.take(), options.format);
+        }
+
+        if options.dump_symbols && !options.shared_state {
+            dump_symbols(
+                &format!("--- Symbols after {} ---", file_name),
+                executor,
+                options.format,
+            );
+        }
+    }
+
+    if options.trailing_result && options.shared_state {
+        print_trailing_result(last_result, options.format);
+    }
+    if options.dump_symbols && options.shared_state {
+        dump_symbols("--- Symbols ---", &shared_executor, options.format);
+    }
+    Ok(failures)
+}
+
+/// Evaluates two files and prints the lines whose results differ, side by side.
+///
+/// Each file is evaluated against a fresh `SimpleExecutor`. Results are matched up by the
+/// source line an expression starts on; a line that produced a result in only one of
+/// the files is shown as `-` on the other side. Errors are compared by their message,
+/// so two lines failing the same way are not reported.
+///
+/// # Arguments
+/// * `left` - The path of the first file.
+/// * `right` - The path of the second file.
+/// * `options` - The `FileModeOptions` providing the evaluation options, input encoding
+///   and output format.
+///
+/// # Returns
+/// `Ok` with the number of differing lines, or `Err` if either file could not be read.
+pub fn compare_files(left: &str, right: &str, options: &FileModeOptions) -> io::Result<usize> {
+    let left_results = line_results(left, options)?;
+    let right_results = line_results(right, options)?;
+
+    let mut lines: Vec<usize> = left_results
+        .keys()
+        .chain(right_results.keys())
+        .copied()
+        .collect();
+    lines.sort_unstable();
+    lines.dedup();
+
+    let mut differences = 0;
+    for line in lines {
+        let a = left_results.get(&line);
+        let b = right_results.get(&line);
+        if a != b {
+            let side = |r: Option<&String>| r.map_or("-".to_string(), String::clone);
+            println!("line {}: {} | {}", line, side(a), side(b));
+            differences += 1;
         }
-        println!(); // Add a newline for separation between files
     }
-    Ok(())
+    Ok(differences)
+}
+
+/// Evaluates the file at `path` and returns each line's formatted result, or its
+/// error message prefixed with `error: `, keyed by source line.
+fn line_results(path: &str, options: &FileModeOptions) -> io::Result<BTreeMap<usize, String>> {
+    let content = read_file(path, options.input_encoding)?;
+    let mut executor = SimpleExecutor::with_options(options.eval_options.clone());
+    let mut results = BTreeMap::new();
+    evaluate_lines_streaming(&content, &mut executor, |line, result| {
+        let text = match result {
+            Ok((val, _)) => format_value(val, options.format, DEFAULT_PRECISION),
+            Err(e) => format!("error: {}", e),
+        };
+        results.insert(line, text);
+    });
+    Ok(results)
+}
+
+/// Escapes a field of a `FormatMode::Tsv` row, writing backslashes, tabs, and line breaks
+/// as `\\`, `\t`, `\n`, and `\r` so that every row stays on one line
+/// and no field spills into the next column.
+fn tsv_field(text: &str) -> String {
+    let mut field = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => field.push_str("\\\\"),
+            '\t' => field.push_str("\\t"),
+            '\n' => field.push_str("\\n"),
+            '\r' => field.push_str("\\r"),
+            _ => field.push(c),
+        }
+    }
+    field
+}
+
+/// Prints the last result of a `trailing_result` run, if any expression succeeded.
+fn print_trailing_result(result: Option<f64>, format: FormatMode) {
+    if let Some(val) = result {
+        println!("{}", format_value(val, format, DEFAULT_PRECISION));
+    }
+}
+
+/// Prints `header` followed by the executor's variables, sorted by name, to `stderr`.
+fn dump_symbols(header: &str, executor: &SimpleExecutor, format: FormatMode) {
+    eprintln!("{}", header);
+    for line in list_variables(executor, format, DEFAULT_PRECISION) {
+        eprintln!("{}", line);
+    }
 }
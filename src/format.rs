@@ -0,0 +1,229 @@
+//! This module formats numbers for output, shared by the REPL, file mode, and `-e`.
+//!
+//! `format_value` writes a result in one of the `FormatMode` notations with at most a
+//! given number of decimal places, and `format_number` is the plain default notation.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// The number of decimal places results are shown with unless changed by `:precision`.
+pub const DEFAULT_PRECISION: usize = 15;
+
+/// Formats `x` in the default notation, with trailing zeros removed.
+///
+/// # Arguments
+/// * `x` - The number to format.
+/// * `precision` - The maximum number of decimal places to show, or `None` for
+///   `DEFAULT_PRECISION`.
+pub fn format_number(x: f64, precision: Option<usize>) -> String {
+    format_value(x, FormatMode::Auto, precision.unwrap_or(DEFAULT_PRECISION))
+}
+
+/// How `format_value` writes numbers.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum FormatMode {
+    /// Plain decimal notation, switching to scientific notation for very large or very
+    /// small magnitudes.
+    #[default]
+    Auto,
+    /// Always plain decimal notation, e.g. `602200000000000000000000`.
+    Fixed,
+    /// Always scientific notation, e.g. `6.022e23`.
+    Scientific,
+    /// Scientific notation with an exponent that is a multiple of 3, e.g. `602.2e21`.
+    Engineering,
+    /// Numbers as in `Auto`, but file mode writes one tab-separated `line`, `expression`,
+    /// `result` row per expression, for importing into a spreadsheet.
+    Tsv,
+}
+
+impl FromStr for FormatMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(FormatMode::Auto),
+            "fixed" => Ok(FormatMode::Fixed),
+            "scientific" | "sci" => Ok(FormatMode::Scientific),
+            "engineering" | "eng" => Ok(FormatMode::Engineering),
+            "tsv" => Ok(FormatMode::Tsv),
+            other => Err(format!(
+                "unknown format '{}' (expected auto, fixed, scientific, engineering, or tsv)",
+                other
+            )),
+        }
+    }
+}
+
+/// Implements the `Display` trait for `FormatMode`, producing the name `FromStr` accepts.
+impl fmt::Display for FormatMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatMode::Auto => write!(f, "auto"),
+            FormatMode::Fixed => write!(f, "fixed"),
+            FormatMode::Scientific => write!(f, "scientific"),
+            FormatMode::Engineering => write!(f, "engineering"),
+            FormatMode::Tsv => write!(f, "tsv"),
+        }
+    }
+}
+
+/// In `FormatMode::Auto`, magnitudes at or above this are written in scientific notation.
+const AUTO_SCIENTIFIC_ABOVE: f64 = 1e16;
+
+/// In `FormatMode::Auto`, non-zero magnitudes below this are written in scientific notation.
+const AUTO_SCIENTIFIC_BELOW: f64 = 1e-7;
+
+/// Formats a number for display in the given mode, removing unnecessary trailing
+/// zeros and ensuring integer values are displayed without a decimal point.
+///
+/// # Arguments
+/// * `x` - The `f64` number to format.
+/// * `mode` - The notation to use.
+/// * `precision` - The maximum number of decimal places to show (of the mantissa,
+///   in scientific and engineering notation).
+///
+/// # Returns
+/// A `String` representation of the formatted number.
+pub fn format_value(x: f64, mode: FormatMode, precision: usize) -> String {
+    if !x.is_finite() {
+        return x.to_string();
+    }
+    // Show negative zero as `0`.
+    let x = if x == 0.0 { 0.0 } else { x };
+    match mode {
+        FormatMode::Auto | FormatMode::Tsv => {
+            let magnitude = x.abs();
+            if magnitude >= AUTO_SCIENTIFIC_ABOVE
+                || (magnitude != 0.0 && magnitude < AUTO_SCIENTIFIC_BELOW)
+            {
+                format_value(x, FormatMode::Scientific, precision)
+            } else {
+                format_value(x, FormatMode::Fixed, precision)
+            }
+        }
+        FormatMode::Fixed => {
+            // The shortest form that reads back as `x`, e.g. `1234.1`, is exact, so use
+            // it when it fits in `precision` places; rounding to that many places would
+            // otherwise expose binary noise such as `1234.099999999999909`.
+            let shortest = x.to_string();
+            let fixed = match shortest.split_once('.') {
+                Some((_, fraction)) if fraction.len() <= precision => shortest,
+                _ => trim_fraction(format!("{:.*}", precision, x)),
+            };
+            // A small negative number can round to zero, e.g. `-0.0001` to two places.
+            if fixed == "-0" {
+                "0".to_string()
+            } else {
+                fixed
+            }
+        }
+        FormatMode::Scientific => {
+            let (mantissa, exponent) = scientific_parts(x, precision);
+            format!("{}e{}", mantissa, exponent)
+        }
+        FormatMode::Engineering => {
+            let (mantissa, exponent) = scientific_parts(x, precision);
+            // Move the decimal point right until the exponent is a multiple of 3.
+            let shift = exponent.rem_euclid(3) as usize;
+            let (sign, digits) = match mantissa.strip_prefix('-') {
+                Some(rest) => ("-", rest),
+                None => ("", mantissa.as_str()),
+            };
+            let mut digits = digits.replace('.', "");
+            while digits.len() < shift + 1 {
+                digits.push('0');
+            }
+            let (whole, fraction) = digits.split_at(shift + 1);
+            let mantissa = trim_fraction(format!("{}.{}", whole, fraction));
+            format!("{}{}e{}", sign, mantissa, exponent - shift as i32)
+        }
+    }
+}
+
+/// Splits `x` into a trimmed scientific mantissa with at most `precision` decimal
+/// places and its base-10 exponent, e.g. `("6.022", 23)` for `6.022e23`.
+fn scientific_parts(x: f64, precision: usize) -> (String, i32) {
+    let formatted = format!("{:.*e}", precision, x);
+    let (mantissa, exponent) = formatted
+        .split_once('e')
+        .expect("`{:e}` output always contains an exponent");
+    (
+        trim_fraction(mantissa.to_string()),
+        exponent.parse().expect("`{:e}` exponents are integers"),
+    )
+}
+
+/// Trims trailing zeros after a decimal point, and the point itself if nothing is left.
+fn trim_fraction(s: String) -> String {
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_number() {
+        assert_eq!(format_number(42.0, None), "42");
+        assert_eq!(format_number(-7.0, None), "-7");
+        assert_eq!(format_number(0.25, None), "0.25");
+        assert_eq!(format_number(1.0 / 3.0, None), "0.333333333333333");
+        assert_eq!(format_number(1.0 / 3.0, Some(2)), "0.33");
+        assert_eq!(format_number(2.5, Some(2)), "2.5");
+        assert_eq!(format_number(2.999, Some(2)), "3");
+        assert_eq!(format_number(1234.1, None), "1234.1");
+        assert_eq!(format_number(100.1, None), "100.1");
+        assert_eq!(format_number(0.1 + 0.2, None), "0.3");
+    }
+
+    #[test]
+    fn test_format_modes() {
+        let avogadro = 6.022e23;
+        assert_eq!(
+            format_value(avogadro, FormatMode::Scientific, DEFAULT_PRECISION),
+            "6.022e23"
+        );
+        assert_eq!(
+            format_value(avogadro, FormatMode::Engineering, DEFAULT_PRECISION),
+            "602.2e21"
+        );
+        assert_eq!(
+            format_value(avogadro, FormatMode::Fixed, DEFAULT_PRECISION),
+            "602200000000000027262976"
+        );
+        assert_eq!(
+            format_value(avogadro, FormatMode::Auto, DEFAULT_PRECISION),
+            "6.022e23"
+        );
+
+        assert_eq!(format_value(0.00012, FormatMode::Scientific, 15), "1.2e-4");
+        assert_eq!(format_value(0.00012, FormatMode::Engineering, 15), "120e-6");
+        assert_eq!(format_value(-1500.0, FormatMode::Engineering, 15), "-1.5e3");
+        assert_eq!(format_value(1234.5678, FormatMode::Scientific, 2), "1.23e3");
+        assert_eq!(format_value(0.0, FormatMode::Scientific, 15), "0e0");
+        assert_eq!(format_value(-0.0, FormatMode::Fixed, 15), "0");
+        assert_eq!(format_value(-0.0001, FormatMode::Fixed, 2), "0");
+        assert_eq!(format_number(-0.0001, Some(2)), "0");
+        assert_eq!(format_value(-1e-16, FormatMode::Fixed, 15), "0");
+
+        // Auto only switches notation for very large or very small magnitudes.
+        assert_eq!(format_value(1234.5, FormatMode::Auto, 15), "1234.5");
+        assert_eq!(format_value(1e-9, FormatMode::Auto, 15), "1e-9");
+        assert_eq!(
+            format_value(f64::INFINITY, FormatMode::Engineering, 15),
+            "inf"
+        );
+    }
+
+    #[test]
+    fn test_parse_format_mode() {
+        assert_eq!("eng".parse(), Ok(FormatMode::Engineering));
+        assert_eq!("Scientific".parse(), Ok(FormatMode::Scientific));
+        assert!("roman".parse::<FormatMode>().is_err());
+    }
+}
@@ -9,14 +9,335 @@
 //! - **Parsing:** Constructing an Abstract Syntax Tree (AST) from the tokens (`parser` module).
 //! - **Execution:** Evaluating the AST to produce a result (`executor` module).
 //!
+//! Library users will usually want the `Interpreter` type, which owns an executor and an
+//! `InterpreterConfig` and wraps the pipeline in a few convenient methods.
+//!
 //! This crate also provides modules for error handling (`errors`), AST definition (`ast`),
-//! REPL functionality (`repl`), and file-based execution (`filemode`).
+//! built-in functions (`builtins`), style lints (`lint`), REPL functionality (`repl`), the installation check
+//! (`self_test`), and file-based execution (`filemode`).
 
 pub mod ast;
+pub mod builtins;
 pub mod errors;
 pub mod executor;
+pub mod format;
+pub mod lint;
 pub mod parser;
 pub mod repl;
+pub mod self_test;
 pub mod tokenizer;
+pub mod value;
 
 pub mod filemode; // Declare the new module
+
+use ast::Statement;
+use builtins::AngleMode;
+use executor::{
+    BytecodeCompiler, EvalError, EvalOptions, EvalResult, SimpleExecutor, ValueRange,
+    evaluate_lines,
+};
+use filemode::{FileModeOptions, InputEncoding};
+use std::io;
+use std::path::Path;
+
+/// Evaluates a single expression and returns its value.
+///
+/// The input is evaluated against a fresh `SimpleExecutor`, so it may define variables
+/// with `let` on earlier lines, but it must contain exactly one bare expression. The
+/// values reported for definitions and assignments are not counted.
+///
+/// ```
+/// assert_eq!(arith::eval_str("1 + 2 * 3").unwrap(), 7.0);
+/// ```
+///
+/// # Returns
+/// The numeric result, or the `EvalError` of the first expression that failed.
+/// Input producing zero or several results is reported as `EvalError::ResultCount`.
+pub fn eval_str(input: &str) -> Result<f64, EvalError> {
+    let results: Vec<EvalResult> = evaluate_lines(input, &mut SimpleExecutor::new())
+        .into_iter()
+        .filter(|result| !matches!(result, Ok((_, source)) if is_definition(source)))
+        .collect();
+    single_value(results, input)
+}
+
+/// Evaluates several inputs in order against one shared `SimpleExecutor`, so variables
+/// and functions defined by an entry can be used by the entries after it.
+///
+/// Unlike `evaluate_lines`, which splits one string into lines, each entry is evaluated
+/// on its own and must produce exactly one result. A definition or assignment counts,
+/// since it produces the value it stored.
+///
+/// ```
+/// let results = arith::eval_batch(&["let x = 4", "x * 2", "y"]);
+/// assert_eq!(results[0].as_ref().unwrap(), &4.0);
+/// assert_eq!(results[1].as_ref().unwrap(), &8.0);
+/// assert!(results[2].is_err());
+/// ```
+///
+/// # Returns
+/// One result per entry, in order: its value, the `EvalError` of its first expression that
+/// failed, or `EvalError::ResultCount` for an entry producing zero or several results,
+/// such as a function definition.
+pub fn eval_batch(inputs: &[&str]) -> Vec<Result<f64, EvalError>> {
+    let mut executor = SimpleExecutor::new();
+    inputs
+        .iter()
+        .map(|input| single_value(evaluate_lines(input, &mut executor), input))
+        .collect()
+}
+
+/// Returns the value of the only result in `results`, which were produced by `input`.
+///
+/// If there are zero or several results, returns the first error among them or, if none
+/// failed, `EvalError::ResultCount`.
+fn single_value(mut results: Vec<EvalResult>, input: &str) -> Result<f64, EvalError> {
+    if results.len() != 1 {
+        if let Some(pos) = results.iter().position(Result::is_err) {
+            return results.swap_remove(pos).map(|(value, _)| value);
+        }
+        return Err(EvalError::ResultCount(results.len(), input.to_string()));
+    }
+    results.remove(0).map(|(value, _)| value)
+}
+
+/// Returns `true` if `source` is a definition or assignment rather than a bare expression.
+fn is_definition(source: &str) -> bool {
+    matches!(
+        parser::parse_str(source),
+        Ok(Statement::Let { .. }
+            | Statement::Assignment { .. }
+            | Statement::CompoundAssignment { .. })
+    )
+}
+
+/// Checks that the two ways of evaluating an expression agree: compiling it to bytecode
+/// for a `SimpleExecutor`, and walking its syntax tree with `Expr::evaluate`.
+///
+/// The expression is evaluated against a fresh executor, so only the predefined
+/// constants are available. Meant for fuzzing and property tests, so input that does
+/// not parse as a bare expression has nothing to compare and is accepted.
+///
+/// ```
+/// assert_eq!(arith::differential_check("2 ^ 3 ^ 2 - (1 ? 4 : 5)"), Ok(()));
+/// ```
+///
+/// # Returns
+/// `Ok(())` if both paths produce the same value, or both fail, otherwise `Err` with a
+/// description of the mismatch. Failures are not compared further, since constant
+/// folding may report a different error first, e.g. for `x + 1 / 0`.
+pub fn differential_check(input: &str) -> Result<(), String> {
+    let expr = match parser::parse_str(input) {
+        Ok(Statement::Expression(expr)) => expr,
+        _ => return Ok(()),
+    };
+    let mut executor = SimpleExecutor::new();
+    // Walk a copy, so `rand` draws the same numbers in both evaluations.
+    let tree = expr.evaluate(&executor.clone());
+    let bytecode = BytecodeCompiler::compile(&expr)
+        .map_err(|e| e.to_string())
+        .and_then(|code| executor.execute(&code).map_err(|e| e.to_string()));
+    match (tree, bytecode) {
+        (Ok(a), Ok(b)) if a == b || (a.as_f64().is_nan() && b.as_f64().is_nan()) => Ok(()),
+        (Err(_), Err(_)) => Ok(()),
+        (tree, bytecode) => Err(format!(
+            "'{}' evaluates to {:?} by walking the tree but {:?} as bytecode",
+            input, tree, bytecode
+        )),
+    }
+}
+
+/// Configuration for an `Interpreter`.
+///
+/// Construct one with `InterpreterConfig::builder()`, or use `InterpreterConfig::default()`
+/// for the same behavior as the command-line interpreter with no flags.
+#[derive(Debug, Default, Clone)]
+pub struct InterpreterConfig {
+    /// Prefix each result printed by `Interpreter::run_files` with its source line number.
+    pub line_numbers: bool,
+    /// Share variables across all files passed to `Interpreter::run_files`.
+    pub shared_state: bool,
+    /// The options all input is evaluated with.
+    pub eval_options: EvalOptions,
+    /// The encoding files read by `eval_file` and `run_files` are decoded with.
+    pub input_encoding: InputEncoding,
+}
+
+impl InterpreterConfig {
+    /// Returns a builder starting from the default configuration.
+    pub fn builder() -> InterpreterConfigBuilder {
+        InterpreterConfigBuilder::default()
+    }
+}
+
+/// Builder for `InterpreterConfig`.
+#[derive(Debug, Default)]
+pub struct InterpreterConfigBuilder {
+    config: InterpreterConfig,
+}
+
+impl InterpreterConfigBuilder {
+    /// Sets whether file-mode results are prefixed with their source line number.
+    pub fn line_numbers(mut self, enabled: bool) -> Self {
+        self.config.line_numbers = enabled;
+        self
+    }
+
+    /// Sets whether `Interpreter::run_files` shares variables across files.
+    pub fn shared_state(mut self, enabled: bool) -> Self {
+        self.config.shared_state = enabled;
+        self
+    }
+
+    /// Sets whether a line ending in a bare `=`, like `2 + 2 =`, evaluates the expression
+    /// before it (calculator mode).
+    pub fn calculator_mode(mut self, enabled: bool) -> Self {
+        self.config.eval_options.calculator_mode = enabled;
+        self
+    }
+
+    /// Sets whether a line starting with a binary operator, like `+ 5`, applies it to the
+    /// previous result (tape mode).
+    pub fn tape_mode(mut self, enabled: bool) -> Self {
+        self.config.eval_options.tape_mode = enabled;
+        self
+    }
+
+    /// Sets whether `let` on a variable that is already defined is an error (strict mode).
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.config.eval_options.strict = enabled;
+        self
+    }
+
+    /// Sets the range every expression result must lie in, and whether a result outside
+    /// it is clamped to the nearest bound (`clamp`) instead of being an error.
+    pub fn value_range(mut self, range: ValueRange, clamp: bool) -> Self {
+        self.config.eval_options.range = range;
+        self.config.eval_options.clamp_to_range = clamp;
+        self
+    }
+
+    /// Sets the unit `sin`, `cos`, `tan`, and `normalize_angle` measure angles in.
+    pub fn angle_mode(mut self, mode: AngleMode) -> Self {
+        self.config.eval_options.angle_mode = mode;
+        self
+    }
+
+    /// Sets the character(s) that start a comment running to the end of the line.
+    pub fn comment_delimiter(mut self, delimiter: impl Into<String>) -> Self {
+        self.config.eval_options.comment_delimiter = delimiter.into();
+        self
+    }
+
+    /// Sets whether a comma between groups of three digits, like `1,234`, is read as a
+    /// thousands separator.
+    pub fn thousands_commas(mut self, enabled: bool) -> Self {
+        self.config.eval_options.thousands_commas = enabled;
+        self
+    }
+
+    /// Sets how deeply expressions may nest before parsing fails.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.config.eval_options.max_depth = depth;
+        self
+    }
+
+    /// Sets whether a warning is logged when `/` gives a result integer division would
+    /// truncate.
+    pub fn warn_truncation(mut self, enabled: bool) -> Self {
+        self.config.eval_options.warn_truncation = enabled;
+        self
+    }
+
+    /// Replaces all evaluation options at once; setters called after this one still apply.
+    pub fn eval_options(mut self, options: EvalOptions) -> Self {
+        self.config.eval_options = options;
+        self
+    }
+
+    /// Sets the encoding input files are decoded with.
+    pub fn input_encoding(mut self, encoding: InputEncoding) -> Self {
+        self.config.input_encoding = encoding;
+        self
+    }
+
+    /// Finishes building and returns the configuration.
+    pub fn build(self) -> InterpreterConfig {
+        self.config
+    }
+}
+
+/// A single entry point for embedding `arith`.
+///
+/// An `Interpreter` owns a `SimpleExecutor` and an `InterpreterConfig`. Every evaluation
+/// except `run_files` runs against the same executor, so variables defined by one call are
+/// visible to the next.
+pub struct Interpreter {
+    executor: SimpleExecutor,
+    config: InterpreterConfig,
+}
+
+impl Interpreter {
+    /// Creates a new `Interpreter` with the given configuration and a fresh symbol table,
+    /// holding only the predefined constants (`pi`, `e`, `tau`) and the running `total`.
+    pub fn new(config: InterpreterConfig) -> Self {
+        Interpreter {
+            executor: SimpleExecutor::with_options(config.eval_options.clone()),
+            config,
+        }
+    }
+
+    /// Returns the interpreter's configuration.
+    pub fn config(&self) -> &InterpreterConfig {
+        &self.config
+    }
+
+    /// Returns the interpreter's executor, e.g. to inspect its state.
+    pub fn executor(&self) -> &SimpleExecutor {
+        &self.executor
+    }
+
+    /// Evaluates the given input (which may span several lines) with `evaluate_lines`.
+    ///
+    /// # Returns
+    /// One `EvalResult` per logical expression, as for `evaluate_lines`.
+    pub fn eval_line(&mut self, input: &str) -> Vec<EvalResult> {
+        evaluate_lines(input, &mut self.executor)
+    }
+
+    /// Reads the file at `path` and evaluates its contents with `evaluate_lines`.
+    ///
+    /// # Returns
+    /// One `EvalResult` per logical expression in the file, or an I/O error if the file
+    /// could not be read.
+    pub fn eval_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<Vec<EvalResult>> {
+        let content = filemode::read_file(path, self.config.input_encoding)?;
+        Ok(evaluate_lines(&content, &mut self.executor))
+    }
+
+    /// Runs the given files in file mode, printing their results as `arith -f` does.
+    ///
+    /// Unlike the other methods, this does not use the interpreter's executor: each file is
+    /// evaluated against a fresh one (or, with `shared_state`, all files against one fresh
+    /// executor), so variables from earlier calls are not visible and definitions in the
+    /// files are not kept.
+    ///
+    /// # Returns
+    /// The number of expressions that failed to evaluate, or an I/O error if a file
+    /// could not be read.
+    pub fn run_files(&self, files: Vec<String>) -> io::Result<usize> {
+        let options = FileModeOptions {
+            line_numbers: self.config.line_numbers,
+            shared_state: self.config.shared_state,
+            eval_options: self.config.eval_options.clone(),
+            input_encoding: self.config.input_encoding,
+            ..FileModeOptions::default()
+        };
+        filemode::run_file_mode(files, &options)
+    }
+
+    /// Starts the interactive REPL against the interpreter's executor.
+    pub fn run_repl(&mut self) -> io::Result<()> {
+        repl::run_repl_with(&mut self.executor, &repl::ReplOptions::default())
+    }
+}
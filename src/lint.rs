@@ -0,0 +1,180 @@
+//! This module implements optional style lints over the parsed AST.
+//!
+//! Lints never change how an expression evaluates. They point out constructs that are
+//! valid but redundant, such as `--5`, which is just `5`.
+
+use crate::ast::{Expr, Statement};
+use crate::errors::ParserError;
+use crate::parser::{Parser, tokenize_input};
+use crate::tokenizer::TokenType;
+use std::fmt;
+
+/// A style warning produced by a lint, with the source location it refers to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintWarning {
+    /// A description of the problem and how to simplify it.
+    pub message: String,
+    /// The 1-based line the offending construct starts on.
+    pub line: usize,
+    /// The 1-based column the offending construct starts at.
+    pub col: usize,
+}
+
+/// Implements the `Display` trait for `LintWarning`, e.g.
+/// `warning: redundant double negation, write `5` instead at line 1, col 1`.
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "warning: {} at line {}, col {}",
+            self.message, self.line, self.col
+        )
+    }
+}
+
+/// Parses `input` as a single statement and runs all lints over it.
+///
+/// # Returns
+/// The warnings found, in source order (empty if the input is clean), or the
+/// `ParserError` if the input does not parse.
+pub fn lint(input: &str) -> Result<Vec<LintWarning>, ParserError> {
+    let tokens = tokenize_input(input)?;
+
+    // The AST carries no positions, so record where each prefix (unary) operator starts.
+    // A pre-order walk of the AST visits `UnaryOp` nodes in the same order.
+    let mut unary_positions = Vec::new();
+    let mut previous: Option<&TokenType> = None;
+    for token in &tokens {
+        let is_operand_end = matches!(
+            previous,
+            Some(
+                TokenType::Number { .. }
+                    | TokenType::Identifier { .. }
+                    | TokenType::HistoryRef { .. }
+                    | TokenType::ParanClose
+                    | TokenType::Bang
+                    | TokenType::Percent
+            )
+        );
+        // `~` is always a prefix operator; `+` and `-` are when no operand precedes them.
+        let is_prefix = match token.get_type() {
+            TokenType::BitNot => true,
+            TokenType::Plus | TokenType::Minus => !is_operand_end,
+            _ => false,
+        };
+        if is_prefix {
+            unary_positions.push((token.get_line_no(), token.get_start()));
+        }
+        previous = Some(token.get_type());
+    }
+
+    let statement = Parser::new(tokens).parse()?;
+    let expr = match &statement {
+        Statement::Expression(expr) => expr,
+        Statement::Let { value, .. }
+        | Statement::Assignment { value, .. }
+        | Statement::CompoundAssignment { value, .. } => value,
+        Statement::FunctionDef { body, .. } => body,
+    };
+
+    let mut walker = LintWalker {
+        unary_positions: unary_positions.into_iter(),
+        warnings: Vec::new(),
+    };
+    walker.visit(expr);
+    Ok(walker.warnings)
+}
+
+/// Walks an `Expr` tree, collecting warnings.
+struct LintWalker {
+    unary_positions: std::vec::IntoIter<(usize, usize)>,
+    warnings: Vec<LintWarning>,
+}
+
+impl LintWalker {
+    fn visit(&mut self, expr: &Expr) {
+        match expr {
+            Expr::UnaryOp { op, expr: operand } => {
+                let (line, col) = self.unary_positions.next().unwrap_or((0, 0));
+                // Double negation: `-(-x)` is just `x`.
+                if let (
+                    TokenType::Minus,
+                    Expr::UnaryOp {
+                        op: TokenType::Minus,
+                        expr: inner,
+                    },
+                ) = (op, operand.as_ref())
+                {
+                    self.warnings.push(LintWarning {
+                        message: format!("redundant double negation, write `{}` instead", inner),
+                        line,
+                        col,
+                    });
+                }
+                self.visit(operand);
+            }
+            Expr::Postfix { expr: operand, .. } => self.visit(operand),
+            Expr::BinaryOp { left, right, .. } => {
+                self.visit(left);
+                self.visit(right);
+            }
+            Expr::Conditional { cond, then, else_ } => {
+                self.visit(cond);
+                self.visit(then);
+                self.visit(else_);
+            }
+            Expr::Call { args, .. } => args.iter().for_each(|arg| self.visit(arg)),
+            Expr::Number(_)
+            | Expr::Variable(_)
+            | Expr::HistoryRef(_)
+            | Expr::Empty
+            | Expr::EmptyParen => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_double_negation_is_reported() {
+        let warnings = lint("--5").unwrap();
+        assert_eq!(
+            warnings,
+            vec![LintWarning {
+                message: "redundant double negation, write `5` instead".to_string(),
+                line: 1,
+                col: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_single_negation_is_clean() {
+        assert!(lint("-5").unwrap().is_empty());
+        assert!(lint("1 - -5").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_double_negation_location() {
+        let warnings = lint("let x = 2 * -(-(1 + 2))").unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].line, warnings[0].col), (1, 13));
+        assert_eq!(
+            warnings[0].message,
+            "redundant double negation, write `(1 + 2)` instead"
+        );
+    }
+
+    #[test]
+    fn test_double_negation_location_after_bit_not() {
+        let warnings = lint("~-1 + --2").unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].line, warnings[0].col), (1, 7));
+        assert_eq!(
+            warnings[0].message,
+            "redundant double negation, write `2` instead"
+        );
+    }
+}
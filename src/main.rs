@@ -1,16 +1,33 @@
 //! The main entry point for the `arith` command-line interpreter.
 //!
 //! This module handles command-line argument parsing using `clap`,
-//! initializes logging, and dispatches to either the interactive REPL mode
-//! or file processing mode based on the provided arguments.
+//! initializes logging, and dispatches to either the interactive REPL mode,
+//! file processing mode, or evaluation of expressions given on the command line
+//! or piped through stdin, based on the provided arguments.
 
-use arith::repl::run_repl;
+use arith::builtins::AngleMode;
+use arith::executor::{EvalOptions, SimpleExecutor, ValueRange, evaluate_lines};
+use arith::format::{DEFAULT_PRECISION, FormatMode, format_value};
+use arith::lint::lint;
+use arith::parser::{DEFAULT_MAX_DEPTH, explain_precedence, parse_str};
+use arith::repl::{DEFAULT_HISTORY_SIZE, ReplOptions, result_prefix, run_repl_with};
+use arith::self_test::run_self_test;
 use clap::{CommandFactory, Parser, Subcommand};
 use clap_complete::{generate, shells};
 use env_logger::{Builder, Env};
 use log::LevelFilter;
+use std::io::{self, IsTerminal, Read};
+use std::num::NonZeroUsize;
+use std::ops::RangeInclusive;
+use std::time::Duration;
 
-use arith::filemode;
+use arith::filemode::{self, FileModeOptions, InputEncoding};
+
+/// The exit code used when an expression fails to evaluate.
+const EXIT_EVAL_ERROR: i32 = 1;
+
+/// The exit code used when an input file cannot be read.
+const EXIT_IO_ERROR: i32 = 2;
 
 /// Command-line arguments for the `arith` interpreter.
 #[derive(Parser)]
@@ -32,6 +49,166 @@ struct Cli {
     #[arg(short, long)]
     files: Vec<String>,
 
+    /// Prefix each result in file mode with the source line number it came from.
+    #[arg(long)]
+    line_numbers: bool,
+
+    /// In file mode, print only the value of each successful expression, one per line.
+    ///
+    /// Headers and the expressions themselves are left out; errors still go to stderr.
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// In file mode, evaluate only the expression starting on source line N.
+    #[arg(long, value_name = "N", conflicts_with = "range")]
+    line: Option<NonZeroUsize>,
+
+    /// In file mode, evaluate only the expressions starting on source lines A to B.
+    #[arg(long, value_name = "A:B", value_parser = filemode::parse_line_range)]
+    range: Option<RangeInclusive<usize>>,
+
+    /// Evaluate all files with one shared set of variables instead of isolating each file.
+    #[arg(long)]
+    shared_state: bool,
+
+    /// After evaluating each file, print every variable and its final value to stderr.
+    ///
+    /// With `--shared-state`, the variables are printed once, after the last file.
+    #[arg(long)]
+    dump_symbols_on_exit: bool,
+
+    /// In file mode, print only the value of the last expression that succeeded in each file.
+    ///
+    /// With `--shared-state`, only the last value of the last file is printed.
+    #[arg(long)]
+    trailing_result: bool,
+
+    /// The text encoding of the input files: `utf8` (the default) or `latin1`.
+    #[arg(long, value_name = "ENCODING", default_value = "utf8", global = true)]
+    input_encoding: InputEncoding,
+
+    /// How results are written: `auto` (the default), `fixed`, `scientific` (`6.022e23`),
+    /// or `engineering` (`602.2e21`, with an exponent that is a multiple of 3). `tsv` writes
+    /// file-mode results as tab-separated `line`, `expression`, `result` rows.
+    #[arg(long, value_name = "MODE", default_value = "auto")]
+    format: FormatMode,
+
+    /// Exit the REPL after SECONDS without input.
+    #[arg(long, value_name = "SECONDS")]
+    repl_timeout: Option<u64>,
+
+    /// Keep at most N entries in the REPL history file, dropping the oldest when saving.
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_HISTORY_SIZE)]
+    history_size: usize,
+
+    /// The text the REPL prints before each result, `= ` by default; may be empty.
+    ///
+    /// Without this option, `$ARITH_RESULT_PREFIX` is used if it is set.
+    #[arg(long, value_name = "TEXT")]
+    repl_result_prefix: Option<String>,
+
+    /// Calculator mode: treat a line ending in a bare `=`, like `2 + 2 =`, as "evaluate".
+    #[arg(long)]
+    calculator: bool,
+
+    /// Let `;` separate statements on one line, as `:multi on` does in the REPL. Comments
+    /// then need another delimiter, set with `--comment-char`.
+    #[arg(long)]
+    multi_statement: bool,
+
+    /// How deeply an expression may nest, e.g. in parentheses, before it is rejected.
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_DEPTH)]
+    max_depth: usize,
+
+    /// Tape mode: a line starting with a binary operator, like `+ 5` or `* 2`, applies it
+    /// to the previous result.
+    #[arg(long)]
+    tape: bool,
+
+    /// Strict mode: `let` on a variable that is already defined is an error; use `x = ...`
+    /// to change its value.
+    #[arg(long)]
+    strict: bool,
+
+    /// Warn when `/` divides two whole numbers into a fraction, e.g. `7 / 2`, which
+    /// integer division would truncate. Results are unchanged.
+    #[arg(long)]
+    warn_truncation: bool,
+
+    /// Report an error for any result smaller than MIN.
+    #[arg(long, value_name = "MIN", allow_negative_numbers = true)]
+    min_value: Option<f64>,
+
+    /// Report an error for any result larger than MAX.
+    #[arg(long, value_name = "MAX", allow_negative_numbers = true)]
+    max_value: Option<f64>,
+
+    /// With `--min-value`/`--max-value`, clamp out-of-range results to the nearest bound
+    /// and print a warning instead of reporting an error.
+    #[arg(long)]
+    clamp: bool,
+
+    /// The character(s) that start a comment, such as `#` or `//`, in place of `;`.
+    #[arg(
+        long,
+        value_name = "DELIM",
+        default_value = ";",
+        global = true,
+        value_parser = clap::builder::NonEmptyStringValueParser::new()
+    )]
+    comment_char: String,
+
+    /// The unit `sin`, `cos`, `tan`, their inverses, and `normalize_angle` measure angles
+    /// in: `radians` (the default) or `degrees`.
+    #[arg(
+        long,
+        visible_alias = "angle",
+        value_name = "MODE",
+        default_value = "radians"
+    )]
+    angle_mode: AngleMode,
+
+    /// Evaluate the valid start of a line and warn about the tokens after it, such as the
+    /// `, 3` in `1 + 2 , 3`, instead of reporting an error.
+    #[arg(long)]
+    allow_trailing_garbage: bool,
+
+    /// Read commas inside numbers as thousands separators, so `1,234,567` is `1234567`.
+    ///
+    /// Only a comma between digits with exactly three digits after it counts; any other
+    /// comma, as in `gcd(4, 6)`, still separates arguments.
+    #[arg(long)]
+    treat_comma_as_thousands: bool,
+
+    /// Evaluate EXPR, print its results, and exit.
+    ///
+    /// Can be given several times; the expressions are evaluated in order against one
+    /// set of variables, so `-e 'let x = 2' -e 'x * 3'` prints `6`.
+    #[arg(short, long, value_name = "EXPR")]
+    eval: Vec<String>,
+
+    /// Read expressions from stdin and print their results instead of starting the REPL.
+    ///
+    /// This is the default when stdin is not a terminal, e.g. `echo "1 + 2" | arith`.
+    #[arg(long)]
+    stdin: bool,
+
+    /// Print EXPR fully parenthesized, showing how operators were grouped, and exit.
+    #[arg(long, value_name = "EXPR")]
+    explain_precedence: Option<String>,
+
+    /// Print the syntax tree of EXPR as JSON, and exit.
+    #[arg(long, value_name = "EXPR")]
+    ast_json: Option<String>,
+
+    /// Print style warnings for EXPR, such as a redundant double negation, and exit.
+    #[arg(long, value_name = "EXPR")]
+    lint: Option<String>,
+
+    /// Evaluate the two files A and B and print only the lines whose results differ.
+    #[arg(long, num_args = 2, value_names = ["A", "B"], conflicts_with = "files")]
+    compare_files: Option<Vec<String>>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -44,6 +221,32 @@ enum Commands {
         #[arg(value_enum)]
         shell: shells::Shell,
     },
+    /// Evaluate a built-in set of known expressions and report any wrong results
+    SelfTest,
+    /// Print a script with its comments removed, leaving everything else unchanged
+    StripComments {
+        /// The script to strip
+        file: String,
+    },
+}
+
+/// Evaluates `input` and prints each result on its own line in the given format, with
+/// errors going to stderr.
+///
+/// # Returns
+/// `true` if every expression evaluated successfully.
+fn print_results(input: &str, executor: &mut SimpleExecutor, format: FormatMode) -> bool {
+    let mut ok = true;
+    for result in evaluate_lines(input, executor) {
+        match result {
+            Ok((value, _)) => println!("{}", format_value(value, format, DEFAULT_PRECISION)),
+            Err(e) => {
+                eprintln!("{}", e);
+                ok = false;
+            }
+        }
+    }
+    ok
 }
 
 /// The main function of the `arith` interpreter.
@@ -66,12 +269,156 @@ fn main() -> std::io::Result<()> {
                 generate(shell, &mut cmd, cmd_name, &mut std::io::stdout());
                 return Ok(());
             }
+            Commands::SelfTest => {
+                let results = run_self_test();
+                for result in &results {
+                    println!("{}", result);
+                }
+                let failed = results.iter().filter(|result| !result.passed()).count();
+                println!("{} passed, {} failed", results.len() - failed, failed);
+                if failed > 0 {
+                    std::process::exit(EXIT_EVAL_ERROR);
+                }
+                return Ok(());
+            }
+            Commands::StripComments { file } => {
+                let source = match filemode::read_file(&file, args.input_encoding) {
+                    Ok(source) => source,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(EXIT_IO_ERROR);
+                    }
+                };
+                let options = EvalOptions {
+                    comment_delimiter: args.comment_char,
+                    ..EvalOptions::default()
+                };
+                print!("{}", filemode::strip_comments(&source, &options));
+                return Ok(());
+            }
+        }
+    }
+
+    if let Some(expression) = args.explain_precedence {
+        match explain_precedence(&expression) {
+            Ok(explained) => println!("{}", explained),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
         }
+        return Ok(());
+    }
+
+    if let Some(expression) = args.ast_json {
+        match parse_str(&expression) {
+            Ok(statement) => println!("{}", statement.to_json()),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(expression) = args.lint {
+        match lint(&expression) {
+            Ok(warnings) => warnings.iter().for_each(|w| println!("{}", w)),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    let eval_options = EvalOptions {
+        calculator_mode: args.calculator,
+        multi_statement: args.multi_statement,
+        max_depth: args.max_depth,
+        tape_mode: args.tape,
+        strict: args.strict,
+        warn_truncation: args.warn_truncation,
+        range: ValueRange {
+            min: args.min_value,
+            max: args.max_value,
+        },
+        clamp_to_range: args.clamp,
+        comment_delimiter: args.comment_char,
+        angle_mode: args.angle_mode,
+        allow_trailing_garbage: args.allow_trailing_garbage,
+        thousands_commas: args.treat_comma_as_thousands,
+    };
+
+    if let Some(paths) = &args.compare_files {
+        let options = FileModeOptions {
+            eval_options,
+            input_encoding: args.input_encoding,
+            format: args.format,
+            ..FileModeOptions::default()
+        };
+        match filemode::compare_files(&paths[0], &paths[1], &options) {
+            Ok(0) => return Ok(()),
+            Ok(_) => std::process::exit(EXIT_EVAL_ERROR),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(EXIT_IO_ERROR);
+            }
+        }
+    }
+
+    if !args.eval.is_empty() {
+        let mut executor = SimpleExecutor::with_options(eval_options);
+        let mut failed = false;
+        for expression in &args.eval {
+            failed |= !print_results(expression, &mut executor, args.format);
+        }
+        if failed {
+            std::process::exit(EXIT_EVAL_ERROR);
+        }
+        return Ok(());
+    }
+
+    if args.files.is_empty() && (args.stdin || !io::stdin().is_terminal()) {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input)?;
+        if !print_results(
+            &input,
+            &mut SimpleExecutor::with_options(eval_options),
+            args.format,
+        ) {
+            std::process::exit(EXIT_EVAL_ERROR);
+        }
+        return Ok(());
     }
 
     if args.files.is_empty() {
-        run_repl()
+        let options = ReplOptions {
+            format: args.format,
+            idle_timeout: args.repl_timeout.map(Duration::from_secs),
+            history_size: args.history_size,
+            result_prefix: result_prefix(args.repl_result_prefix),
+        };
+        run_repl_with(&mut SimpleExecutor::with_options(eval_options), &options)
     } else {
-        filemode::run_file_mode(args.files) // Call the new orchestrator
+        let options = FileModeOptions {
+            line_numbers: args.line_numbers,
+            shared_state: args.shared_state,
+            eval_options,
+            input_encoding: args.input_encoding,
+            dump_symbols: args.dump_symbols_on_exit,
+            format: args.format,
+            trailing_result: args.trailing_result,
+            quiet: args.quiet,
+            lines: args.line.map(|n| n.get()..=n.get()).or(args.range),
+        };
+        match filemode::run_file_mode(args.files, &options) {
+            Ok(0) => Ok(()),
+            Ok(_) => std::process::exit(EXIT_EVAL_ERROR),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(EXIT_IO_ERROR);
+            }
+        }
     }
 }
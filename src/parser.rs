@@ -1,55 +1,160 @@
+use crate::ast::{Expr, Statement};
 use crate::errors::ParserError;
-use crate::tokenizer::{Token, TokenType};
+use crate::tokenizer::{Token, TokenType, Tokenizer};
+use crate::value::ValueType;
 
-/// Represents a node in the Abstract Syntax Tree (AST).
-///
-/// The AST is a tree representation of the grammatical structure of the
-/// source code. Each variant of this enum corresponds to a different type of
-/// expression in the `arith` language.
-#[derive(Debug, PartialEq)]
-pub enum Expr {
-    /// A literal floating-point number, e.g., `42.0`, `3.14`.
-    Number(f64),
-
-    /// A unary operation, e.g., `-5`, `+x`.
-    ///
-    /// It consists of an operator (`op`) and an expression (`expr`) that it
-    /// applies to.
-    UnaryOp { op: TokenType, expr: Box<Expr> },
+/// How a binary operator groups with neighbours of the same precedence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Associativity {
+    /// `a - b - c` is `(a - b) - c`.
+    Left,
+    /// `a ^ b ^ c` is `a ^ (b ^ c)`.
+    Right,
+}
 
-    /// A binary operation, e.g., `a + b`, `c * d`.
-    ///
-    /// It consists of a left-hand side expression (`left`), an operator (`op`),
-    /// and a right-hand side expression (`right`).
-    BinaryOp {
-        left: Box<Expr>,
-        op: TokenType,
-        right: Box<Expr>,
+/// Describes how a binary operator is parsed.
+struct BinaryOperator {
+    /// The operator's token.
+    token: TokenType,
+    /// How tightly the operator binds; higher binds tighter.
+    precedence: u8,
+    /// How the operator groups with others of the same precedence.
+    associativity: Associativity,
+}
+
+/// The binary operators, from the loosest to the tightest binding.
+const BINARY_OPERATORS: &[BinaryOperator] = &[
+    BinaryOperator {
+        token: TokenType::BitOr,
+        precedence: 1,
+        associativity: Associativity::Left,
+    },
+    BinaryOperator {
+        token: TokenType::BitAnd,
+        precedence: 2,
+        associativity: Associativity::Left,
+    },
+    BinaryOperator {
+        token: TokenType::Less,
+        precedence: 3,
+        associativity: Associativity::Left,
+    },
+    BinaryOperator {
+        token: TokenType::Greater,
+        precedence: 3,
+        associativity: Associativity::Left,
+    },
+    BinaryOperator {
+        token: TokenType::LessEqual,
+        precedence: 3,
+        associativity: Associativity::Left,
+    },
+    BinaryOperator {
+        token: TokenType::GreaterEqual,
+        precedence: 3,
+        associativity: Associativity::Left,
+    },
+    BinaryOperator {
+        token: TokenType::Equal,
+        precedence: 3,
+        associativity: Associativity::Left,
+    },
+    BinaryOperator {
+        token: TokenType::NotEqual,
+        precedence: 3,
+        associativity: Associativity::Left,
+    },
+    BinaryOperator {
+        token: TokenType::ApproxEqual,
+        precedence: 3,
+        associativity: Associativity::Left,
+    },
+    BinaryOperator {
+        token: TokenType::ShiftLeft,
+        precedence: 4,
+        associativity: Associativity::Left,
+    },
+    BinaryOperator {
+        token: TokenType::ShiftRight,
+        precedence: 4,
+        associativity: Associativity::Left,
+    },
+    BinaryOperator {
+        token: TokenType::Plus,
+        precedence: 5,
+        associativity: Associativity::Left,
+    },
+    BinaryOperator {
+        token: TokenType::Minus,
+        precedence: 5,
+        associativity: Associativity::Left,
+    },
+    BinaryOperator {
+        token: TokenType::Mul,
+        precedence: 6,
+        associativity: Associativity::Left,
+    },
+    BinaryOperator {
+        token: TokenType::Div,
+        precedence: 6,
+        associativity: Associativity::Left,
+    },
+    BinaryOperator {
+        token: TokenType::Modulo,
+        precedence: 6,
+        associativity: Associativity::Left,
     },
+    BinaryOperator {
+        token: TokenType::Of,
+        precedence: 6,
+        associativity: Associativity::Left,
+    },
+    BinaryOperator {
+        token: TokenType::Pow,
+        precedence: 7,
+        associativity: Associativity::Right,
+    },
+];
+
+/// How deeply a `Parser` lets expressions nest unless `Parser::with_max_depth` says otherwise.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// The precedence of the loosest-binding operator, `|`.
+const LOWEST_PRECEDENCE: u8 = 1;
+
+/// The precedence of implicit multiplication (`2x`), the same as `*`.
+const IMPLICIT_MULTIPLICATION_PRECEDENCE: u8 = 6;
 
-    /// Represents an empty expression, typically from an empty input string.
-    Empty,
+/// The minimum precedence of the operand of a prefix `+`, `-`, or `~`, that of `^`.
+const PREFIX_OPERAND_PRECEDENCE: u8 = 7;
 
-    /// Represents empty parentheses, e.g., `()`. In `arith`, this evaluates to `0`.
-    EmptyParen,
+/// Returns the descriptor of the binary operator `token`, or `None` if it is not one.
+fn binary_operator(token: &TokenType) -> Option<&'static BinaryOperator> {
+    BINARY_OPERATORS
+        .iter()
+        .find(|operator| operator.token == *token)
 }
 
 /// The `Parser` is responsible for syntactic analysis. It consumes a stream of
 /// `Token`s from the `Tokenizer` and produces an Abstract Syntax Tree (AST)
-/// that represents the grammatical structure of the input expression.
+/// that represents the grammatical structure of the input statement.
 ///
 /// The parser implements a top-down recursive descent strategy, specifically
 /// a Pratt parser, to handle operator precedence and associativity correctly.
 ///
 /// The grammar rules are applied in the parsing methods:
-/// - `parse_expr`: Handles the lowest precedence operators (`+`, `-`).
-/// - `parse_term`: Handles higher precedence operators (`*`, `/`) and implicit
-///   multiplication.
-/// - `parse_factor`: Handles the highest precedence elements, including numbers,
-///   parenthesized expressions, and unary operators.
+/// - `parse`: Decides between a `let` definition, an assignment, and a bare expression.
+/// - `parse_expr`: Handles conditionals (`c ? a : b`), the loosest-binding expressions.
+/// - `parse_binary`: Handles every binary operator, including implicit multiplication,
+///   using the precedence and associativity in `BINARY_OPERATORS`.
+/// - `parse_factor`: Handles prefix (`+`, `-`) and postfix (`!`, `%`) unary operators.
+/// - `parse_primary`: Handles the highest precedence elements: numbers, variables,
+///   function calls, and parenthesized expressions.
 pub(crate) struct Parser {
     tokens: Vec<Token>,
     pos: usize,
+    depth: usize,
+    max_depth: usize,
 }
 
 impl Parser {
@@ -69,9 +174,20 @@ impl Parser {
         Parser {
             tokens: filtered_tokens,
             pos: 0,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
         }
     }
 
+    /// Sets how deeply expressions may nest (`DEFAULT_MAX_DEPTH` by default). Each
+    /// parenthesized group, operand of a right-associative or prefix operator, and
+    /// function argument counts as a level; going deeper fails with
+    /// `ParserError::RecursionLimitExceeded` instead of overflowing the stack.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
     /// Returns a reference to the current token without advancing the parser's position.
     ///
     /// This is useful for "peeking" at the next token to decide parsing strategy.
@@ -79,6 +195,13 @@ impl Parser {
         &self.tokens[self.pos]
     }
 
+    /// Returns a reference to the token after the current one without advancing.
+    ///
+    /// If the current token is the last one (`EOF`), the current token is returned.
+    fn peek(&self) -> &Token {
+        &self.tokens[(self.pos + 1).min(self.tokens.len() - 1)]
+    }
+
     /// Advances the parser's position to the next token in the stream.
     ///
     /// This method should be called after a token has been successfully consumed.
@@ -88,39 +211,294 @@ impl Parser {
         }
     }
 
-    /// Parses the entire token stream and returns the root of the Abstract Syntax Tree (AST).
+    /// Parses the entire token stream and returns the resulting `Statement`.
     ///
-    /// This is the main entry point for the parsing process.
+    /// This is the main entry point for the parsing process. It is strict: every token
+    /// must belong to the statement.
     ///
     /// # Returns
-    /// A `Result` which is `Ok` containing an `Expr` representing the parsed AST,
+    /// A `Result` which is `Ok` containing the parsed `Statement`,
     /// or `Err` containing a `ParserError` if a syntax error is encountered.
     /// If the input token stream consists only of an `EOF` token (e.g., from an empty input string),
-    /// it returns `Expr::Empty`.
-    pub fn parse(&mut self) -> Result<Expr, ParserError> {
-        if matches!(self.current().get_type(), TokenType::EOF) {
-            return Ok(Expr::Empty);
+    /// it returns `Statement::Expression(Expr::Empty)`. A token left over after the statement
+    /// is reported as a `ParserError::UnmatchedCloseParen` if it is a `)`, and as a
+    /// `ParserError::UnexpectedToken` otherwise.
+    pub fn parse(&mut self) -> Result<Statement, ParserError> {
+        let statement = self.parse_prefix()?;
+        if self.finished() {
+            return Ok(statement);
         }
-        self.parse_expr()
+        Err(match self.current().get_type() {
+            TokenType::ParanClose => ParserError::UnmatchedCloseParen {
+                line: self.current().get_line_no(),
+                col: self.current().get_start(),
+            },
+            _ => self.unexpected_token(),
+        })
+    }
+
+    /// Parses the longest statement at the start of the token stream, leaving any tokens
+    /// after it unconsumed; `finished` and `remaining` tell whether there are any.
+    pub fn parse_prefix(&mut self) -> Result<Statement, ParserError> {
+        self.parse_statement()
     }
 
-    /// Parses expressions with the lowest precedence (addition and subtraction).
+    /// Returns `true` if every token up to the final `EOF` has been consumed.
+    pub fn finished(&self) -> bool {
+        matches!(self.current().get_type(), TokenType::EOF)
+    }
+
+    /// Returns the tokens not consumed yet, excluding the final `EOF`.
+    pub fn remaining(&self) -> &[Token] {
+        &self.tokens[self.pos..self.tokens.len() - 1]
+    }
+
+    /// Parses a single statement, choosing the kind from its leading tokens.
+    fn parse_statement(&mut self) -> Result<Statement, ParserError> {
+        match self.current().get_type() {
+            TokenType::EOF => Ok(Statement::Expression(Expr::Empty)),
+            // Assigning to a keyword, e.g. `let = 5`.
+            keyword if keyword.keyword().is_some() && self.peek().get_type().is_assignment() => {
+                Err(self.reserved_keyword_error())
+            }
+            TokenType::Let => self.parse_let_statement(),
+            TokenType::Identifier { .. } if self.peek().get_type().is_assignment() => {
+                self.parse_assignment_statement()
+            }
+            _ => Ok(Statement::Expression(self.parse_expr()?)),
+        }
+    }
+
+    /// Consumes an identifier token and returns its name.
     ///
-    /// This method forms the entry point for parsing expressions and handles
-    /// left-associative binary operators `+` and `-`. It recursively calls
-    /// `parse_term` to handle higher precedence operations.
+    /// # Returns
+    /// The identifier's name, a `ParserError::ReservedKeyword` if the current token is a
+    /// keyword, or the error from `unexpected_token` if it is anything else.
+    fn expect_identifier(&mut self) -> Result<String, ParserError> {
+        match self.current().get_type() {
+            TokenType::Identifier { name } => {
+                let name = name.clone();
+                self.advance();
+                Ok(name)
+            }
+            other if other.keyword().is_some() => Err(self.reserved_keyword_error()),
+            _ => Err(self.unexpected_token()),
+        }
+    }
+
+    /// Builds the error for a current token that is not valid where it appears: a
+    /// `ParserError::UnexpectedEOF` if the input has run out, otherwise a
+    /// `ParserError::UnexpectedToken`.
+    fn unexpected_token(&self) -> ParserError {
+        let token = self.current();
+        match token.get_type() {
+            TokenType::EOF => ParserError::UnexpectedEOF {
+                line: token.get_line_no(),
+                col: token.get_start(),
+            },
+            found => ParserError::UnexpectedToken {
+                found: found.clone(),
+                line: token.get_line_no(),
+                col: token.get_start(),
+            },
+        }
+    }
+
+    /// Builds a `ParserError::ReservedKeyword` for the current token, which must be a keyword.
+    fn reserved_keyword_error(&self) -> ParserError {
+        ParserError::ReservedKeyword {
+            keyword: self
+                .current()
+                .get_type()
+                .keyword()
+                .unwrap_or_default()
+                .to_string(),
+            line: self.current().get_line_no(),
+            col: self.current().get_start(),
+        }
+    }
+
+    /// Parses a variable definition, with an optional type annotation, or a function
+    /// definition if the name is followed by a parameter list.
     ///
-    /// Grammar rule: `expression = term, { (PLUS | MINUS), term } `;
-    fn parse_expr(&mut self) -> Result<Expr, ParserError> {
-        let mut node = self.parse_term()?;
+    /// Grammar rule: `let_statement = LET, IDENTIFIER, [COLON, IDENTIFIER], ASSIGN, expression | function_def `;
+    fn parse_let_statement(&mut self) -> Result<Statement, ParserError> {
+        self.advance(); // consume 'let'
+        let name = self.expect_identifier()?;
+        if matches!(self.current().get_type(), TokenType::ParanOpen) {
+            return self.parse_function_definition(name);
+        }
+        let type_name = if matches!(self.current().get_type(), TokenType::Colon) {
+            self.advance(); // consume ':'
+            let (line, col) = (self.current().get_line_no(), self.current().get_start());
+            let type_name = self.expect_identifier()?;
+            Some(
+                ValueType::from_name(&type_name).ok_or(ParserError::UnknownType {
+                    name: type_name,
+                    line,
+                    col,
+                })?,
+            )
+        } else {
+            None
+        };
+        if !matches!(self.current().get_type(), TokenType::Assign) {
+            return Err(self.unexpected_token());
+        }
+        self.advance(); // consume '='
+        let value = self.parse_expr()?;
+        Ok(Statement::Let {
+            name,
+            type_name,
+            value,
+        })
+    }
 
-        while matches!(
-            self.current().get_type(),
-            TokenType::Plus | TokenType::Minus
-        ) {
-            let op = self.current().get_type().clone();
+    /// Parses the rest of a function definition after `let` and the function's name.
+    ///
+    /// Grammar rule:
+    /// `function_def = LET, IDENTIFIER, LPAREN, [IDENTIFIER, { COMMA, IDENTIFIER }], RPAREN, ASSIGN, expression `;
+    fn parse_function_definition(&mut self, name: String) -> Result<Statement, ParserError> {
+        self.advance(); // consume '('
+        let mut params = Vec::new();
+        if !matches!(self.current().get_type(), TokenType::ParanClose) {
+            loop {
+                params.push(self.expect_identifier()?);
+                if !matches!(self.current().get_type(), TokenType::Comma) {
+                    break;
+                }
+                self.advance(); // consume ','
+            }
+        }
+        for expected in [TokenType::ParanClose, TokenType::Assign] {
+            if *self.current().get_type() != expected {
+                return Err(self.unexpected_token());
+            }
             self.advance();
-            let right = self.parse_term()?;
+        }
+        let body = self.parse_expr()?;
+        Ok(Statement::FunctionDef { name, params, body })
+    }
+
+    /// Parses a plain (`x = ...`) or compound (`x += ...`) assignment.
+    ///
+    /// Grammar rule:
+    /// `assignment_statement = IDENTIFIER, (ASSIGN | PLUS_ASSIGN | MINUS_ASSIGN | MUL_ASSIGN | DIV_ASSIGN), expression `;
+    fn parse_assignment_statement(&mut self) -> Result<Statement, ParserError> {
+        let name = self.expect_identifier()?;
+        let op = match self.current().get_type() {
+            TokenType::PlusAssign => Some(TokenType::Plus),
+            TokenType::MinusAssign => Some(TokenType::Minus),
+            TokenType::MulAssign => Some(TokenType::Mul),
+            TokenType::DivAssign => Some(TokenType::Div),
+            _ => None,
+        };
+        self.advance(); // consume the assignment operator
+        let value = self.parse_expr()?;
+        Ok(match op {
+            Some(op) => Statement::CompoundAssignment { name, op, value },
+            None => Statement::Assignment { name, value },
+        })
+    }
+
+    /// Parses a full expression: a chain of binary operators of any precedence,
+    /// optionally followed by the branches of a conditional, which binds loosest of all.
+    ///
+    /// The branches are full expressions themselves, so conditionals nest to the right:
+    /// `a ? b : c ? d : e` is `a ? b : (c ? d : e)`.
+    ///
+    /// Grammar rule: `expression = binary(1), [ QUESTION, expression, COLON, expression ] `;
+    fn parse_expr(&mut self) -> Result<Expr, ParserError> {
+        let cond = self.parse_binary(LOWEST_PRECEDENCE)?;
+        if !matches!(self.current().get_type(), TokenType::Question) {
+            return Ok(cond);
+        }
+        self.advance(); // consume '?'
+        let then = self.nested(Self::parse_expr)?;
+        if !matches!(self.current().get_type(), TokenType::Colon) {
+            return Err(self.unexpected_token());
+        }
+        self.advance(); // consume ':'
+        let else_ = self.nested(Self::parse_expr)?;
+        Ok(Expr::Conditional {
+            cond: Box::new(cond),
+            then: Box::new(then),
+            else_: Box::new(else_),
+        })
+    }
+
+    /// Parses a chain of binary operators whose precedence is at least `min_precedence`,
+    /// by precedence climbing over `BINARY_OPERATORS`.
+    ///
+    /// After each operand, the next operator is consumed only if it binds at least as
+    /// tightly as `min_precedence`. Its right operand is then parsed with a minimum of
+    /// `precedence + 1` for a left-associative operator, so `8 - 2 - 1` groups as
+    /// `(8 - 2) - 1`, or `precedence` for a right-associative one, so `2 ^ 3 ^ 2` groups
+    /// as `2 ^ (3 ^ 2)`. An operand directly followed by a number, a variable, or `(`
+    /// is an implicit multiplication (`3(5)`, `2x`), with the precedence of `*`.
+    ///
+    /// Every operator in the chain counts as a nesting level, since it makes the syntax
+    /// tree one level deeper.
+    ///
+    /// Grammar rule: `binary(p) = factor, { op, binary(p') } ` for every `op` with precedence at least `p`;
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr, ParserError> {
+        let depth = self.depth;
+        let result = self.parse_operator_chain(min_precedence);
+        self.depth = depth;
+        result
+    }
+
+    /// Parses the chain of operators for `parse_binary`, which restores the depth after.
+    fn parse_operator_chain(&mut self, min_precedence: u8) -> Result<Expr, ParserError> {
+        let mut node = self.parse_factor()?;
+
+        loop {
+            let token = self.current().get_type();
+            let (op, precedence, associativity) = if let Some(operator) = binary_operator(token) {
+                (
+                    operator.token.clone(),
+                    operator.precedence,
+                    operator.associativity,
+                )
+            } else if matches!(
+                token,
+                TokenType::ParanOpen
+                    | TokenType::Number { .. }
+                    | TokenType::Identifier { .. }
+                    | TokenType::HistoryRef { .. }
+            ) {
+                (
+                    TokenType::Mul,
+                    IMPLICIT_MULTIPLICATION_PRECEDENCE,
+                    Associativity::Left,
+                )
+            } else {
+                break;
+            };
+            // `of` only multiplies a percentage, as in `50% of 200`; anywhere else it is
+            // left for the caller to reject.
+            if precedence < min_precedence
+                || (op == TokenType::Of
+                    && !matches!(
+                        node,
+                        Expr::Postfix {
+                            op: TokenType::Percent,
+                            ..
+                        }
+                    ))
+            {
+                break;
+            }
+            // Implicit multiplication has no operator token to consume.
+            if binary_operator(token).is_some() {
+                self.advance();
+            }
+
+            self.enter()?;
+            let right = self.parse_binary(match associativity {
+                Associativity::Left => precedence + 1,
+                Associativity::Right => precedence,
+            })?;
             node = Expr::BinaryOp {
                 left: Box::new(node),
                 op,
@@ -131,66 +509,86 @@ impl Parser {
         Ok(node)
     }
 
-    /// Parses expressions with higher precedence (multiplication and division).
+    /// Parses the operands of binary operators (factors).
     ///
-    /// This method handles left-associative binary operators `*` and `/`, as
-    /// well as implicit multiplication (e.g., `3(5)` or `(2)(3)`). It recursively
-    /// calls `parse_factor` to handle the highest precedence elements.
+    /// Factors are prefix unary operators applied to an operand, or a primary
+    /// followed by any number of postfix `!` operators. Postfix operators bind
+    /// tighter than prefix ones, so `-3!` is `-(3!)`. The operand of a prefix
+    /// operator extends over any `^`, so `-2 ^ 2` is `-(2 ^ 2)`, but not over
+    /// lower-precedence operators, so `-2 * 3` is `(-2) * 3`.
     ///
-    /// Grammar rule: `term = factor, { (MUL | DIV), factor | factor } `;
-    fn parse_term(&mut self) -> Result<Expr, ParserError> {
-        let mut node = self.parse_factor()?;
+    /// Grammar rule:
+    /// `factor = (PLUS | MINUS | TILDE), binary(7) | primary, { BANG | PERCENT } `;
+    fn parse_factor(&mut self) -> Result<Expr, ParserError> {
+        self.nested(Self::parse_unary_or_postfix)
+    }
 
-        while matches!(
+    /// Parses a factor without counting it towards the depth limit; see `parse_factor`.
+    fn parse_unary_or_postfix(&mut self) -> Result<Expr, ParserError> {
+        // Unary plus, minus, and bitwise NOT operators.
+        if matches!(
             self.current().get_type(),
-            TokenType::Mul | TokenType::Div | TokenType::ParanOpen | TokenType::Number { .. }
+            TokenType::Plus | TokenType::Minus | TokenType::BitNot
         ) {
-            if matches!(self.current().get_type(), TokenType::ParanOpen)
-                || matches!(self.current().get_type(), TokenType::Number { .. })
-            {
-                // Implicit multiplication has the same precedence as explicit multiplication.
-                // e.g., `3(5)` is parsed as `3 * 5`.
-                let right = self.parse_factor()?;
-                node = Expr::BinaryOp {
-                    left: Box::new(node),
-                    op: TokenType::Mul,
-                    right: Box::new(right),
-                };
-            } else {
-                // Explicit multiplication or division.
-                let op = self.current().get_type().clone();
-                self.advance();
-                let right = self.parse_factor()?;
-                node = Expr::BinaryOp {
-                    left: Box::new(node),
-                    op,
-                    right: Box::new(right),
-                };
-            }
+            let op = self.current().get_type().clone();
+            self.advance();
+            let expr = self.parse_binary(PREFIX_OPERAND_PRECEDENCE)?;
+            return Ok(Expr::UnaryOp {
+                op,
+                expr: Box::new(expr),
+            });
         }
 
+        let mut node = self.parse_primary()?;
+        while matches!(
+            self.current().get_type(),
+            TokenType::Bang | TokenType::Percent
+        ) {
+            let op = self.current().get_type().clone();
+            self.advance();
+            node = Expr::Postfix {
+                expr: Box::new(node),
+                op,
+            };
+        }
         Ok(node)
     }
 
-    /// Parses the highest precedence expressions (factors).
-    ///
-    /// Factors include literal numbers, parenthesized expressions, and unary operators.
-    /// This method is the base case for the recursive descent parsing.
+    /// Runs `parse` one nesting level deeper, failing with
+    /// `ParserError::RecursionLimitExceeded` at the current token if that would exceed
+    /// `max_depth`. Every recursive step of the parser goes through here, so deeply
+    /// nested input is rejected instead of overflowing the stack.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParserError>,
+    ) -> Result<T, ParserError> {
+        self.enter()?;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Goes one nesting level deeper, or fails with `ParserError::RecursionLimitExceeded`
+    /// at the current token if that would exceed `max_depth`.
+    fn enter(&mut self) -> Result<(), ParserError> {
+        if self.depth >= self.max_depth {
+            return Err(ParserError::RecursionLimitExceeded {
+                line: self.current().get_line_no(),
+                col: self.current().get_start(),
+            });
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Parses a primary expression: a literal number, a variable, a function call,
+    /// or a parenthesized expression. This method is the base case for the
+    /// recursive descent parsing.
     ///
     /// Grammar rule:
-    /// `factor = NUMBER | LPAREN, [expression], RPAREN | (PLUS | MINUS), factor `;
-    fn parse_factor(&mut self) -> Result<Expr, ParserError> {
+    /// `primary = NUMBER | IDENTIFIER | HISTORY_REF | call | LPAREN, [expression], RPAREN `;
+    fn parse_primary(&mut self) -> Result<Expr, ParserError> {
         match &self.current().get_type() {
-            // Unary plus and minus operators.
-            TokenType::Plus | TokenType::Minus => {
-                let op = self.current().get_type().clone();
-                self.advance();
-                let expr = self.parse_factor()?;
-                Ok(Expr::UnaryOp {
-                    op,
-                    expr: Box::new(expr),
-                })
-            }
             // Literal numbers.
             TokenType::Number { value } => {
                 let n: f64 = value.parse().map_err(|_| ParserError::InvalidNumber {
@@ -201,8 +599,27 @@ impl Parser {
                 self.advance();
                 Ok(Expr::Number(n))
             }
+            // Function calls, e.g. `stddev(1, 2, 3)`.
+            TokenType::Identifier { .. }
+                if matches!(self.peek().get_type(), TokenType::ParanOpen) =>
+            {
+                self.parse_call()
+            }
+            // Variable references.
+            TokenType::Identifier { name } => {
+                let name = name.clone();
+                self.advance();
+                Ok(Expr::Variable(name))
+            }
+            // References to earlier results, e.g. `$3`.
+            TokenType::HistoryRef { index } => {
+                let index = *index;
+                self.advance();
+                Ok(Expr::HistoryRef(index))
+            }
             // Parenthesized expressions.
             TokenType::ParanOpen => {
+                let open = self.current().clone();
                 self.advance();
                 // Handle empty parentheses `()`.
                 if matches!(self.current().get_type(), TokenType::ParanClose) {
@@ -211,26 +628,110 @@ impl Parser {
                 }
                 let expr = self.parse_expr()?;
                 // Ensure the expression is followed by a closing parenthesis.
-                if !matches!(self.current().get_type(), TokenType::ParanClose) {
-                    return Err(ParserError::UnexpectedToken {
-                        found: self.current().get_type().clone(),
-                        line: self.current().get_line_no(),
-                        col: self.current().get_start(),
-                    });
-                }
-                self.advance();
+                self.expect_close_paren(&open)?;
                 Ok(expr)
             }
             // Handle unexpected tokens.
-            _ => Err(ParserError::UnexpectedToken {
-                found: self.current().get_type().clone(),
-                line: self.current().get_line_no(),
-                col: self.current().get_start(),
+            _ => Err(self.unexpected_token()),
+        }
+    }
+
+    /// Parses a function call: an identifier followed by a parenthesized,
+    /// comma-separated argument list.
+    ///
+    /// Grammar rule:
+    /// `call = IDENTIFIER, LPAREN, [expression, { COMMA, expression }], RPAREN `;
+    fn parse_call(&mut self) -> Result<Expr, ParserError> {
+        let name = match self.current().get_type() {
+            TokenType::Identifier { name } => name.clone(),
+            _ => unreachable!("parse_call is only entered on an identifier"),
+        };
+        self.advance(); // consume the identifier
+        let open = self.current().clone();
+        self.advance(); // consume '('
+
+        let mut args = Vec::new();
+        if matches!(self.current().get_type(), TokenType::ParanClose) {
+            self.advance();
+            return Ok(Expr::Call { name, args });
+        }
+
+        loop {
+            args.push(self.parse_expr()?);
+            match self.current().get_type() {
+                TokenType::Comma => self.advance(),
+                _ => {
+                    self.expect_close_paren(&open)?;
+                    return Ok(Expr::Call { name, args });
+                }
+            }
+        }
+    }
+
+    /// Consumes the `)` closing the parenthesis `open`.
+    ///
+    /// # Returns
+    /// A `ParserError::UnmatchedParen` pointing at `open` if the input ends first, or a
+    /// `ParserError::UnexpectedToken` if any other token is found.
+    fn expect_close_paren(&mut self, open: &Token) -> Result<(), ParserError> {
+        match self.current().get_type() {
+            TokenType::ParanClose => {
+                self.advance();
+                Ok(())
+            }
+            TokenType::EOF => Err(ParserError::UnmatchedParen {
+                line: open.get_line_no(),
+                col: open.get_start(),
             }),
+            _ => Err(self.unexpected_token()),
         }
     }
 }
 
+/// Tokenizes and parses a single line into its `Statement`, without evaluating it.
+///
+/// ```
+/// use arith::ast::{Expr, Statement};
+/// use arith::parser::parse_str;
+/// use arith::tokenizer::TokenType;
+///
+/// assert_eq!(
+///     parse_str("1+2"),
+///     Ok(Statement::Expression(Expr::BinaryOp {
+///         left: Box::new(Expr::Number(1.0)),
+///         op: TokenType::Plus,
+///         right: Box::new(Expr::Number(2.0)),
+///     }))
+/// );
+/// ```
+///
+/// # Returns
+/// A `Result` which is `Ok` containing the parsed `Statement`, or `Err` containing a
+/// `ParserError`, with tokenizer failures reported as `ParserError::TokenizerError`.
+pub fn parse_str(input: &str) -> Result<Statement, ParserError> {
+    Parser::new(tokenize_input(input)?).parse()
+}
+
+/// Parses a single line and renders it fully parenthesized, showing how the parser
+/// grouped it according to operator precedence and associativity.
+///
+/// # Arguments
+/// * `input` - The source of a single statement, e.g. `1 + 2 * 3`.
+///
+/// # Returns
+/// A `Result` which is `Ok` containing the canonical rendering (e.g. `(1 + (2 * 3))`),
+/// or `Err` containing a `ParserError` if the input could not be tokenized or parsed.
+pub fn explain_precedence(input: &str) -> Result<String, ParserError> {
+    Ok(parse_str(input)?.to_string())
+}
+
+/// Tokenizes `input`, reporting a tokenizer failure as a `ParserError::TokenizerError`.
+pub(crate) fn tokenize_input(input: &str) -> Result<Vec<Token>, ParserError> {
+    Tokenizer::new(input.to_string())
+        .tokenize()
+        .map_err(ParserError::from)
+}
+
 #[cfg(test)]
 mod parser_tests {
     use super::*;
@@ -244,11 +745,13 @@ mod parser_tests {
     /// # Returns
     /// A `Result` containing the parsed `Expr` on success, or a `ParserError` on failure.
     fn parse_ok(input: &str) -> Result<Expr, ParserError> {
-        let mut tokenizer = Tokenizer::new(input.to_string());
-        // In tests, unwrapping is acceptable for convenience if the tokenizer is assumed correct.
-        let tokens = tokenizer.tokenize().unwrap();
-        let mut parser = Parser::new(tokens);
-        parser.parse()
+        match parse_str(input)? {
+            Statement::Expression(expr) => Ok(expr),
+            other => panic!(
+                "Expected an expression for input '{}', got {:?}",
+                input, other
+            ),
+        }
     }
 
     /// Helper assertion function for tests that expect successful parsing.
@@ -337,6 +840,82 @@ mod parser_tests {
         );
     }
 
+    #[test]
+    fn test_deep_nesting_is_rejected() {
+        let parens = |n| format!("{}1{}", "(".repeat(n), ")".repeat(n));
+        assert_eq!(
+            parse_str(&parens(10_000)),
+            Err(ParserError::RecursionLimitExceeded {
+                line: 1,
+                col: DEFAULT_MAX_DEPTH + 1,
+            })
+        );
+        assert!(parse_str(&parens(100)).is_ok());
+
+        // Long operator chains and nested conditionals count too.
+        for input in [
+            format!("{}1", "-".repeat(10_000)),
+            format!("{}1", "2^".repeat(10_000)),
+            format!("{}1", "1+".repeat(10_000)),
+            format!("{}1", "1?1:".repeat(10_000)),
+        ] {
+            assert!(
+                matches!(
+                    parse_str(&input),
+                    Err(ParserError::RecursionLimitExceeded { .. })
+                ),
+                "{}...",
+                &input[..10]
+            );
+        }
+
+        let tokens = |input: &str| Tokenizer::new(input.to_string()).tokenize().unwrap();
+        assert!(
+            Parser::new(tokens(&parens(300)))
+                .with_max_depth(400)
+                .parse()
+                .is_ok()
+        );
+        assert_eq!(
+            Parser::new(tokens("(1 + 2) * 3")).with_max_depth(1).parse(),
+            Err(ParserError::RecursionLimitExceeded { line: 1, col: 2 })
+        );
+    }
+
+    #[test]
+    fn test_unary_minus_binds_looser_than_power() {
+        let power = |base: Expr| Expr::BinaryOp {
+            left: Box::new(base),
+            op: TokenType::Pow,
+            right: Box::new(Expr::Number(2.0)),
+        };
+        let negate = |expr: Expr| Expr::UnaryOp {
+            op: TokenType::Minus,
+            expr: Box::new(expr),
+        };
+        // `-3 ^ 2` is `-(3 ^ 2)`, as in mathematics ...
+        assert_parse_ok("-3 ^ 2", negate(power(Expr::Number(3.0))));
+        // ... and parentheses are needed to square a negative number.
+        assert_parse_ok("(-3) ^ 2", power(negate(Expr::Number(3.0))));
+        // A minus after `^` negates the exponent.
+        assert_parse_ok(
+            "2 ^ -3",
+            Expr::BinaryOp {
+                left: Box::new(Expr::Number(2.0)),
+                op: TokenType::Pow,
+                right: Box::new(negate(Expr::Number(3.0))),
+            },
+        );
+        assert_parse_ok(
+            "2(-3)",
+            Expr::BinaryOp {
+                left: Box::new(Expr::Number(2.0)),
+                op: TokenType::Mul,
+                right: Box::new(negate(Expr::Number(3.0))),
+            },
+        );
+    }
+
     #[test]
     fn test_parentheses() {
         assert_parse_ok(
@@ -353,6 +932,76 @@ mod parser_tests {
         );
     }
 
+    #[test]
+    fn test_unmatched_parentheses() {
+        assert_eq!(
+            parse_str("(1+2"),
+            Err(ParserError::UnmatchedParen { line: 1, col: 1 })
+        );
+        // The outer paren is the one left open.
+        assert_eq!(
+            parse_str("((1)"),
+            Err(ParserError::UnmatchedParen { line: 1, col: 1 })
+        );
+        assert_eq!(
+            parse_str("2 * max(1, (3"),
+            Err(ParserError::UnmatchedParen { line: 1, col: 12 })
+        );
+        assert_eq!(
+            parse_str("1+2)"),
+            Err(ParserError::UnmatchedCloseParen { line: 1, col: 4 })
+        );
+    }
+
+    #[test]
+    fn test_unexpected_eof() {
+        assert_eq!(
+            parse_str("1 +"),
+            Err(ParserError::UnexpectedEOF { line: 1, col: 4 })
+        );
+        assert_eq!(
+            parse_str("let x ="),
+            Err(ParserError::UnexpectedEOF { line: 1, col: 8 })
+        );
+        assert_eq!(
+            parse_str("-"),
+            Err(ParserError::UnexpectedEOF { line: 1, col: 2 })
+        );
+        // A token that is present but out of place is still an `UnexpectedToken`.
+        assert!(matches!(
+            parse_str("1 + * 2"),
+            Err(ParserError::UnexpectedToken {
+                found: TokenType::Mul,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_trailing_tokens() {
+        assert!(matches!(
+            parse_str("1 + 2 , 3"),
+            Err(ParserError::UnexpectedToken {
+                found: TokenType::Comma,
+                line: 1,
+                col: 7
+            })
+        ));
+
+        let tokens = tokenize_input("1 + 2 , 3").unwrap();
+        let mut parser = Parser::new(tokens);
+        assert_eq!(
+            parser.parse_prefix(),
+            Ok(Statement::Expression(Expr::BinaryOp {
+                left: Box::new(Expr::Number(1.0)),
+                op: TokenType::Plus,
+                right: Box::new(Expr::Number(2.0)),
+            }))
+        );
+        assert!(!parser.finished());
+        assert_eq!(parser.remaining().len(), 2);
+    }
+
     #[test]
     fn test_unary_inside_parentheses() {
         assert_parse_ok(
@@ -369,6 +1018,7 @@ mod parser_tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_float_numbers() {
         assert_parse_ok(
             "3.14*2.0",
@@ -417,7 +1067,7 @@ mod parser_tests {
         let tokens = tokenizer.tokenize().unwrap();
         let mut parser = Parser::new(tokens);
         match parser.parse() {
-            Ok(expr) => assert_eq!(expr, Expr::Empty),
+            Ok(stmt) => assert_eq!(stmt, Statement::Expression(Expr::Empty)),
             Err(e) => panic!("Parsing failed: {}", e),
         }
     }
@@ -467,6 +1117,29 @@ mod parser_tests {
         );
     }
 
+    #[test]
+    fn test_function_call() {
+        assert_parse_ok(
+            "variance(1, 2+3)",
+            Expr::Call {
+                name: "variance".to_string(),
+                args: vec![
+                    Expr::Number(1.0),
+                    Expr::BinaryOp {
+                        left: Box::new(Expr::Number(2.0)),
+                        op: TokenType::Plus,
+                        right: Box::new(Expr::Number(3.0)),
+                    },
+                ],
+            },
+        );
+    }
+
+    #[test]
+    fn test_function_call_missing_comma() {
+        assert!(parse_ok("variance(1 2").is_err());
+    }
+
     #[test]
     fn test_implicit_multiplication_between_parens() {
         assert_parse_ok(
@@ -486,4 +1159,295 @@ mod parser_tests {
             },
         );
     }
+
+    #[test]
+    fn test_variable_reference() {
+        assert_parse_ok(
+            "2x",
+            Expr::BinaryOp {
+                left: Box::new(Expr::Number(2.0)),
+                op: TokenType::Mul,
+                right: Box::new(Expr::Variable("x".to_string())),
+            },
+        );
+    }
+
+    #[test]
+    fn test_let_statement() {
+        assert_eq!(
+            parse_str("let x = 1 + 2"),
+            Ok(Statement::Let {
+                name: "x".to_string(),
+                type_name: None,
+                value: Expr::BinaryOp {
+                    left: Box::new(Expr::Number(1.0)),
+                    op: TokenType::Plus,
+                    right: Box::new(Expr::Number(2.0)),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_let_with_type_annotation() {
+        assert_eq!(
+            parse_str("let n: Int = 3"),
+            Ok(Statement::Let {
+                name: "n".to_string(),
+                type_name: Some(ValueType::Int),
+                value: Expr::Number(3.0),
+            })
+        );
+        assert_eq!(
+            parse_str("let x: Number = 1.5"),
+            Ok(Statement::Let {
+                name: "x".to_string(),
+                type_name: Some(ValueType::Number),
+                value: Expr::Number(1.5),
+            })
+        );
+        assert_eq!(
+            parse_str("let x: Banana = 1"),
+            Err(ParserError::UnknownType {
+                name: "Banana".to_string(),
+                line: 1,
+                col: 8,
+            })
+        );
+        assert_eq!(
+            parse_str("let n: Text = 3"),
+            Err(ParserError::UnknownType {
+                name: "Text".to_string(),
+                line: 1,
+                col: 8,
+            })
+        );
+    }
+
+    #[test]
+    fn test_assignment_statements() {
+        assert_eq!(
+            parse_str("x = 3"),
+            Ok(Statement::Assignment {
+                name: "x".to_string(),
+                value: Expr::Number(3.0),
+            })
+        );
+        assert_eq!(
+            parse_str("x *= 3"),
+            Ok(Statement::CompoundAssignment {
+                name: "x".to_string(),
+                op: TokenType::Mul,
+                value: Expr::Number(3.0),
+            })
+        );
+    }
+
+    #[test]
+    fn test_let_without_name_error() {
+        assert!(parse_str("let = 3").is_err());
+        assert!(parse_str("let x 3").is_err());
+    }
+
+    #[test]
+    fn test_reserved_keyword_as_name() {
+        for keyword in ["let", "of", "if", "const", "mut"] {
+            let defined = format!("let {} = 5", keyword);
+            assert_eq!(
+                parse_str(&defined),
+                Err(ParserError::ReservedKeyword {
+                    keyword: keyword.to_string(),
+                    line: 1,
+                    col: 5,
+                })
+            );
+            for op in ["=", "+="] {
+                let assigned = format!("{} {} 5", keyword, op);
+                assert_eq!(
+                    parse_str(&assigned),
+                    Err(ParserError::ReservedKeyword {
+                        keyword: keyword.to_string(),
+                        line: 1,
+                        col: 1,
+                    })
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_factorial_postfix() {
+        assert_eq!(explain_precedence("-3!"), Ok("(-(3!))".to_string()));
+        assert_eq!(
+            explain_precedence("2 * 3!!"),
+            Ok("(2 * ((3!)!))".to_string())
+        );
+        assert_eq!(
+            explain_precedence("(1 + 2)! - 1"),
+            Ok("(((1 + 2)!) - 1)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_associativity() {
+        assert_eq!(
+            explain_precedence("2 ^ 3 ^ 2"),
+            Ok("(2 ^ (3 ^ 2))".to_string())
+        );
+        assert_eq!(
+            explain_precedence("8 - 2 - 1"),
+            Ok("((8 - 2) - 1)".to_string())
+        );
+        assert_eq!(
+            explain_precedence("8 / 4 / 2"),
+            Ok("((8 / 4) / 2)".to_string())
+        );
+        assert_eq!(
+            explain_precedence("2 * 3 ^ 2"),
+            Ok("(2 * (3 ^ 2))".to_string())
+        );
+        assert_eq!(explain_precedence("-2 ^ 2"), Ok("(-(2 ^ 2))".to_string()));
+        assert_eq!(explain_precedence("2 ^ -1"), Ok("(2 ^ (-1))".to_string()));
+        assert_eq!(
+            explain_precedence("2 ^ 3x"),
+            Ok("((2 ^ 3) * x)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_bitwise_precedence() {
+        assert_eq!(
+            explain_precedence("1 | 2 & 3"),
+            Ok("(1 | (2 & 3))".to_string())
+        );
+        assert_eq!(
+            explain_precedence("x & 1 == 1"),
+            Ok("(x & (1 == 1))".to_string())
+        );
+        assert_eq!(
+            explain_precedence("1 << 2 + 3 < 4"),
+            Ok("((1 << (2 + 3)) < 4)".to_string())
+        );
+        assert_eq!(explain_precedence("~x & 7"), Ok("((~x) & 7)".to_string()));
+        assert_eq!(
+            explain_precedence("8 >> 1 >> 1"),
+            Ok("((8 >> 1) >> 1)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_function_definition() {
+        assert_eq!(
+            parse_str("let sq(x) = x * x"),
+            Ok(Statement::FunctionDef {
+                name: "sq".to_string(),
+                params: vec!["x".to_string()],
+                body: Expr::BinaryOp {
+                    left: Box::new(Expr::Variable("x".to_string())),
+                    op: TokenType::Mul,
+                    right: Box::new(Expr::Variable("x".to_string())),
+                },
+            })
+        );
+        assert_eq!(
+            explain_precedence("let hyp(a, b) = sqrt(a^2 + b^2)"),
+            Ok("let hyp(a, b) = sqrt(((a ^ 2) + (b ^ 2)))".to_string())
+        );
+        assert_eq!(
+            explain_precedence("let seven() = 7"),
+            Ok("let seven() = 7".to_string())
+        );
+        assert!(matches!(
+            parse_str("let f(x, 1) = x"),
+            Err(ParserError::UnexpectedToken { .. })
+        ));
+        assert!(matches!(
+            parse_str("let f(x = x"),
+            Err(ParserError::UnexpectedToken { .. })
+        ));
+        assert!(matches!(
+            parse_str("let f(let) = 1"),
+            Err(ParserError::ReservedKeyword { .. })
+        ));
+    }
+
+    #[test]
+    fn test_conditional() {
+        assert_eq!(
+            explain_precedence("x > 0 ? x + 1 : -x"),
+            Ok("((x > 0) ? (x + 1) : (-x))".to_string())
+        );
+        assert_eq!(
+            explain_precedence("a ? b : c ? d : e"),
+            Ok("(a ? b : (c ? d : e))".to_string())
+        );
+        assert_eq!(
+            explain_precedence("a ? b ? c : d : e"),
+            Ok("(a ? (b ? c : d) : e)".to_string())
+        );
+        assert_eq!(
+            explain_precedence("(a ? b : c) + 1"),
+            Ok("((a ? b : c) + 1)".to_string())
+        );
+        assert!(matches!(
+            explain_precedence("a ? b"),
+            Err(ParserError::UnexpectedEOF { .. })
+        ));
+    }
+
+    #[test]
+    fn test_chained_subtraction_and_division() {
+        assert_eq!(
+            explain_precedence("10 - 4 - 2"),
+            Ok("((10 - 4) - 2)".to_string())
+        );
+        assert_eq!(
+            explain_precedence("16 / 4 / 2"),
+            Ok("((16 / 4) / 2)".to_string())
+        );
+        assert_eq!(
+            explain_precedence("8 / 4 * 2"),
+            Ok("((8 / 4) * 2)".to_string())
+        );
+        assert_eq!(
+            explain_precedence("8 * 4 / 2 / 2"),
+            Ok("(((8 * 4) / 2) / 2)".to_string())
+        );
+        assert_eq!(
+            explain_precedence("10 - 4 + 2 - 1"),
+            Ok("(((10 - 4) + 2) - 1)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_comparison_precedence() {
+        assert_eq!(
+            explain_precedence("1 + 2 < 3 * 4 == 1"),
+            Ok("(((1 + 2) < (3 * 4)) == 1)".to_string())
+        );
+        assert_eq!(
+            parse_ok("x != -1"),
+            Ok(Expr::BinaryOp {
+                left: Box::new(Expr::Variable("x".to_string())),
+                op: TokenType::NotEqual,
+                right: Box::new(Expr::UnaryOp {
+                    op: TokenType::Minus,
+                    expr: Box::new(Expr::Number(1.0)),
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn test_explain_precedence() {
+        assert_eq!(
+            explain_precedence("1 + 2 * 3 - 4 / -x"),
+            Ok("((1 + (2 * 3)) - (4 / (-x)))".to_string())
+        );
+        assert_eq!(
+            explain_precedence("let y = 2(1 + max(1, 2))"),
+            Ok("let y = (2 * (1 + max(1, 2)))".to_string())
+        );
+        assert!(explain_precedence("1 + @").is_err());
+    }
 }
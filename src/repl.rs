@@ -5,26 +5,122 @@
 //! print the results. It supports multi-line input, special commands, and
 //! basic error reporting.
 
-use crate::executor::evaluate_lines;
+use crate::executor::{
+    BytecodeCompiler, EvalResult, ExecStats, SimpleExecutor, TOTAL_VARIABLE, continues_line,
+    ends_with_operator, evaluate_lines,
+};
+use crate::format::{DEFAULT_PRECISION, FormatMode, format_value};
+use crate::parser::Parser;
+use crate::tokenizer::{TokenType, Tokenizer};
+use crate::value::Value;
 use log::error;
-use std::fs::File;
+use std::env;
+use std::fs::{self, File};
 use std::io::{self, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
 use std::time::Instant;
 
+/// The most entries kept in the history file unless `--history-size` says otherwise;
+/// older ones are dropped when saving.
+pub const DEFAULT_HISTORY_SIZE: usize = 1000;
+
+/// The environment variable overriding where the REPL history is stored.
+/// Setting it to an empty value disables the history file.
+pub const HISTORY_FILE_VARIABLE: &str = "ARITH_HISTORY";
+
+/// The text printed before each result unless `--repl-result-prefix` or
+/// `$ARITH_RESULT_PREFIX` says otherwise.
+pub const DEFAULT_RESULT_PREFIX: &str = "= ";
+
+/// The environment variable setting the text printed before each REPL result, when
+/// `--repl-result-prefix` is not given.
+pub const RESULT_PREFIX_VARIABLE: &str = "ARITH_RESULT_PREFIX";
+
+/// The largest precision `:precision` accepts. An `f64` carries about 17 significant
+/// digits, so more decimal places would only show representation noise.
+const MAX_PRECISION: usize = 17;
+
 /// Runs the interactive Read-Eval-Print Loop (REPL) for the `arith` interpreter.
 ///
 /// This function continuously prompts the user for input, processes commands,
 /// evaluates arithmetic expressions, and prints the results. It handles
-/// line continuations, special REPL commands (like `:q`, `:help`, `:bench`, `:save`),
-/// and displays evaluation errors.
+/// line continuations, special REPL commands (like `:q`, `:help`, `:bench`, `:save`,
+/// `:watch`, `:precision`, `:format`, `:angle`, `:history`, `:load`, `:clear`, `:vars`, `:undo`, `:multi`, `:tape`, `:strict`), and displays evaluation errors.
+///
+/// A single `SimpleExecutor` is kept for the whole session, so variables defined
+/// on one line remain available on the following ones.
 ///
 /// # Returns
 /// A `std::io::Result<()>` indicating success or an I/O error during input/output operations.
 pub fn run_repl() -> io::Result<()> {
+    run_repl_with(&mut SimpleExecutor::new(), &ReplOptions::default())
+}
+
+/// Settings for a REPL session.
+#[derive(Debug, Clone)]
+pub struct ReplOptions {
+    /// The notation results are printed in, until changed with `:format`.
+    pub format: FormatMode,
+    /// End the session after this long without input. `None` waits forever.
+    pub idle_timeout: Option<Duration>,
+    /// The most entries kept in the history file; older ones are dropped when saving.
+    pub history_size: usize,
+    /// The text printed before each result, e.g. `= ` in `= 4`.
+    pub result_prefix: String,
+}
+
+impl Default for ReplOptions {
+    fn default() -> Self {
+        ReplOptions {
+            format: FormatMode::default(),
+            idle_timeout: None,
+            history_size: DEFAULT_HISTORY_SIZE,
+            result_prefix: DEFAULT_RESULT_PREFIX.to_string(),
+        }
+    }
+}
+
+/// Chooses the text printed before each REPL result: `flag`, the value of the
+/// `--repl-result-prefix` option, if given, then `$ARITH_RESULT_PREFIX`, then `= `.
+pub fn result_prefix(flag: Option<String>) -> String {
+    resolve_result_prefix(flag, env::var(RESULT_PREFIX_VARIABLE).ok())
+}
+
+/// Picks the result prefix from the flag and environment values, in that order of priority.
+fn resolve_result_prefix(flag: Option<String>, env_value: Option<String>) -> String {
+    flag.or(env_value)
+        .unwrap_or_else(|| DEFAULT_RESULT_PREFIX.to_string())
+}
+
+/// Runs the REPL against an existing `SimpleExecutor`.
+///
+/// Variables already defined in `executor` are available at the prompt, and
+/// definitions made during the session remain in it after the REPL exits.
+///
+/// # Arguments
+/// * `executor` - The executor to evaluate input against.
+/// * `options` - The `ReplOptions` for the session.
+///
+/// # Returns
+/// A `std::io::Result<()>` indicating success or an I/O error during input/output operations.
+pub fn run_repl_with(executor: &mut SimpleExecutor, options: &ReplOptions) -> io::Result<()> {
     println!("arith REPL — enter expressions. Use \\ for line-continuation. :q to quit.");
 
     let mut acc = String::new(); // accumulates current statement (may span lines)
+    let mut watches: Vec<String> = Vec::new(); // expressions re-evaluated after every input
+    let mut precision = DEFAULT_PRECISION; // decimal places used when printing results
+    let mut format = options.format; // notation used when printing results
+    let mut last_stats = None; // statistics of the most recent evaluation, for :profile-last
+    let reader = LineReader::stdin(options.idle_timeout);
+    let history_path = history_file();
+    let mut history = history_path
+        .as_deref()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|contents| parse_history(&contents))
+        .unwrap_or_default(); // statements evaluated, including previous sessions
 
     loop {
         // Primary prompt when empty, secondary when continuing
@@ -36,12 +132,23 @@ pub fn run_repl() -> io::Result<()> {
         io::stdout().flush()?;
 
         // Read one line
-        let mut line = String::new();
-        let n = io::stdin().read_line(&mut line)?;
-        if n == 0 {
+        let line = match reader.next_line()? {
+            ReadOutcome::Line(line) => line,
+            ReadOutcome::IdleTimeout => {
+                println!();
+                println!(
+                    "No input for {}s, goodbye.",
+                    options.idle_timeout.unwrap_or_default().as_secs()
+                );
+                break;
+            }
+            ReadOutcome::Eof => String::new(),
+        };
+        if line.is_empty() {
             // EOF (Ctrl-D). If mid-statement, try to evaluate whatever we have.
             if !acc.trim().is_empty() {
-                eval_and_print(&acc);
+                eval_and_print(&acc, executor, &options.result_prefix, format, precision);
+                print_watches(&watches, executor, format, precision);
             }
             println!();
             break;
@@ -53,8 +160,229 @@ pub fn run_repl() -> io::Result<()> {
         if acc.is_empty() {
             match trimmed {
                 ":q" | ":quit" | ":exit" => break,
+                ":history" => {
+                    for (i, entry) in history.iter().enumerate() {
+                        println!("{:>4}  {}", i + 1, entry);
+                    }
+                    continue;
+                }
                 ":h" | ":help" => {
                     println!("Commands: :q to quit, :help for this, :bench for performance test.");
+                    println!(
+                        "          :watch <expr> to re-print an expression after every input, :unwatch [expr] to remove it (or all)."
+                    );
+                    println!(
+                        "          :precision <n> to show results with up to n decimal places (0-{}).",
+                        MAX_PRECISION
+                    );
+                    println!(
+                        "          :format auto|fixed|scientific|engineering to choose how results are written."
+                    );
+                    println!(
+                        "          :angle rad|deg to choose the unit of sin, cos, tan, their inverses, and normalize_angle."
+                    );
+                    println!(
+                        "          :clear (or :reset) to remove all variables, restoring the built-in constants."
+                    );
+                    println!(
+                        "          :multi on|off to let `;` separate statements on one line instead of starting a comment."
+                    );
+                    println!(
+                        "          :tape on|off to let a line like `+ 5` or `* 2` apply to the previous result."
+                    );
+                    println!(
+                        "          :strict on|off to make `let` on an already defined variable an error."
+                    );
+                    println!(
+                        "          :bytecode <expr> (or :dis <expr>) to show the instructions an expression compiles to."
+                    );
+                    println!("          :vars (or :env) to list all variables and their values.");
+                    println!("          :history to list the statements entered so far.");
+                    println!(
+                        "          :load <path> to evaluate a file in this session, keeping its definitions."
+                    );
+                    println!(
+                        "          :profile-last to show the instruction count and time of the last evaluation."
+                    );
+                    println!(
+                        "          :rename <old> <new> to rename a variable, updating the watches that use it."
+                    );
+                    println!(
+                        "          :undo to revert the last change to a variable, removing it if a `let` defined it."
+                    );
+                    println!(
+                        "          :try <expr> to evaluate input and roll back every change it made if any of it fails."
+                    );
+                    println!(
+                        "          :reset-total to set the running total of results, `total`, back to 0."
+                    );
+                    continue;
+                }
+                cmd if cmd.starts_with(":multi") => {
+                    match parse_toggle(cmd[":multi".len()..].trim()) {
+                        Some(enabled) => executor.options_mut().multi_statement = enabled,
+                        None => println!("Usage: :multi on|off"),
+                    }
+                    let state = if executor.options().multi_statement {
+                        "on"
+                    } else {
+                        "off"
+                    };
+                    println!("Multi-statement mode: {}", state);
+                    continue;
+                }
+                cmd if cmd.starts_with(":tape") => {
+                    match parse_toggle(cmd[":tape".len()..].trim()) {
+                        Some(enabled) => executor.options_mut().tape_mode = enabled,
+                        None => println!("Usage: :tape on|off"),
+                    }
+                    let state = if executor.options().tape_mode {
+                        "on"
+                    } else {
+                        "off"
+                    };
+                    println!("Tape mode: {}", state);
+                    continue;
+                }
+                cmd if cmd.starts_with(":strict") => {
+                    match parse_toggle(cmd[":strict".len()..].trim()) {
+                        Some(enabled) => executor.options_mut().strict = enabled,
+                        None => println!("Usage: :strict on|off"),
+                    }
+                    let state = if executor.options().strict {
+                        "on"
+                    } else {
+                        "off"
+                    };
+                    println!("Strict mode: {}", state);
+                    continue;
+                }
+                ":vars" | ":env" => {
+                    for line in list_variables(executor, format, precision) {
+                        println!("{}", line);
+                    }
+                    continue;
+                }
+                cmd if cmd.starts_with(":rename") => {
+                    let names: Vec<&str> = cmd[":rename".len()..].split_whitespace().collect();
+                    let [old, new] = names[..] else {
+                        println!("Usage: :rename <old> <new>");
+                        continue;
+                    };
+                    if !is_identifier(new) {
+                        println!("'{}' is not a valid variable name.", new);
+                    } else if executor.variable(new).is_some() {
+                        println!("Cannot rename: '{}' already exists.", new);
+                    } else if executor.rename_symbol(old, new) {
+                        for watch in watches.iter_mut() {
+                            *watch = rename_in_expression(watch, old, new);
+                        }
+                        println!("Renamed {} to {}.", old, new);
+                    } else {
+                        println!("Cannot rename: '{}' is not defined.", old);
+                    }
+                    continue;
+                }
+                ":profile-last" => {
+                    match &last_stats {
+                        Some(stats) => println!("{}", format_stats(stats)),
+                        None => println!("Nothing evaluated yet."),
+                    }
+                    continue;
+                }
+                ":undo" => {
+                    match executor.undo() {
+                        Some((name, Some(value))) => println!(
+                            "Restored {} = {}.",
+                            name,
+                            format_value(value.as_f64(), format, precision)
+                        ),
+                        Some((name, None)) => println!("Removed {}.", name),
+                        None => println!("Nothing to undo."),
+                    }
+                    continue;
+                }
+                cmd if cmd.starts_with(":try") => {
+                    let input = cmd[":try".len()..].trim();
+                    if input.is_empty() {
+                        println!("Usage: :try <expr>");
+                        continue;
+                    }
+                    let results = try_evaluate(input, executor);
+                    let failed = results.iter().any(Result::is_err);
+                    print_results(results, &options.result_prefix, format, precision);
+                    if failed {
+                        println!("Rolled back.");
+                    }
+                    last_stats = executor.last_stats().or(last_stats);
+                    print_watches(&watches, executor, format, precision);
+                    continue;
+                }
+                ":reset-total" => {
+                    executor.set_value(TOTAL_VARIABLE, Value::Int(0));
+                    println!("Total reset to 0.");
+                    continue;
+                }
+                ":clear" | ":reset" => {
+                    *executor = SimpleExecutor::with_options(executor.options().clone());
+                    println!("Environment cleared.");
+                    continue;
+                }
+                cmd if cmd.starts_with(":watch") => {
+                    let expression = cmd[":watch".len()..].trim();
+                    if expression.is_empty() {
+                        println!("Usage: :watch <expr>");
+                    } else {
+                        watches.push(expression.to_string());
+                        print_watches(&watches[watches.len() - 1..], executor, format, precision);
+                    }
+                    continue;
+                }
+                cmd if cmd.starts_with(":unwatch") => {
+                    let expression = cmd[":unwatch".len()..].trim();
+                    if expression.is_empty() {
+                        watches.clear();
+                    } else if let Some(idx) = watches.iter().position(|w| w == expression) {
+                        watches.remove(idx);
+                    } else {
+                        println!("Not watching: {}", expression);
+                    }
+                    continue;
+                }
+                cmd if cmd.starts_with(":format") => {
+                    let argument = cmd[":format".len()..].trim();
+                    if argument.is_empty() {
+                        println!("Format: {}", format);
+                    } else {
+                        match parse_format(argument) {
+                            Ok(mode) => format = mode,
+                            Err(e) => println!("{}", e),
+                        }
+                    }
+                    continue;
+                }
+                cmd if cmd.starts_with(":angle") => {
+                    let argument = cmd[":angle".len()..].trim();
+                    if argument.is_empty() {
+                        println!("Angle mode: {}", executor.options().angle_mode);
+                    } else {
+                        match argument.parse() {
+                            Ok(mode) => executor.options_mut().angle_mode = mode,
+                            Err(e) => println!("{}", e),
+                        }
+                    }
+                    continue;
+                }
+                cmd if cmd.starts_with(":precision") => {
+                    let argument = cmd[":precision".len()..].trim();
+                    if argument.is_empty() {
+                        println!("Precision: {}", precision);
+                    } else {
+                        match parse_precision(argument) {
+                            Ok(n) => precision = n,
+                            Err(e) => println!("{}", e),
+                        }
+                    }
                     continue;
                 }
                 ":bench" => {
@@ -63,7 +391,7 @@ pub fn run_repl() -> io::Result<()> {
 
                     let start_time = Instant::now();
                     for _ in 0..num_iterations {
-                        evaluate_lines(expression);
+                        evaluate_lines(expression, &mut SimpleExecutor::new());
                     }
                     let elapsed_time = start_time.elapsed();
 
@@ -76,6 +404,46 @@ pub fn run_repl() -> io::Result<()> {
                     );
                     continue;
                 }
+                cmd if cmd.starts_with(":bytecode") || cmd.starts_with(":dis") => {
+                    let command = if cmd.starts_with(":bytecode") {
+                        ":bytecode"
+                    } else {
+                        ":dis"
+                    };
+                    let expression = cmd[command.len()..].trim();
+                    if expression.is_empty() {
+                        println!("Usage: :bytecode <expr>");
+                    } else {
+                        match bytecode_listing(expression, executor) {
+                            Ok(lines) => lines.iter().for_each(|line| println!("{}", line)),
+                            Err(e) => error!("! {}", e),
+                        }
+                    }
+                    continue;
+                }
+                cmd if cmd.starts_with(":load") => {
+                    let path = cmd[":load".len()..].trim();
+                    if path.is_empty() {
+                        println!("Usage: :load <path>");
+                        continue;
+                    }
+                    match load_file(Path::new(path), executor) {
+                        Ok(results) => {
+                            let errors = results.iter().filter(|r| r.is_err()).count();
+                            for e in results.iter().filter_map(|r| r.as_ref().err()) {
+                                error!("! {}", e);
+                            }
+                            println!(
+                                "Loaded {}: {} result(s), {} error(s)",
+                                path,
+                                results.len() - errors,
+                                errors
+                            );
+                        }
+                        Err(e) => println!("{}", e),
+                    }
+                    continue;
+                }
                 cmd if cmd.starts_with(":save")
                     || cmd.starts_with(":w")
                     || cmd.starts_with(":wq") =>
@@ -104,19 +472,141 @@ pub fn run_repl() -> io::Result<()> {
         acc.push_str(trimmed);
         acc.push('\n');
 
-        // If the visible line (ignoring trailing spaces) ends with a backslash, keep collecting
-        let ends_with_backslash = trimmed.trim_end().ends_with('\\');
-
-        if !ends_with_backslash {
+        // If the line, without its comment and trailing spaces, ends with a backslash or a
+        // binary operator, keep collecting
+        if !continues_line(trimmed, executor.options()) {
             // We’ve got a complete statement (or multiple statements pasted at once).
-            eval_and_print(&acc);
+            record_history(&mut history, &acc);
+            eval_and_print(&acc, executor, &options.result_prefix, format, precision);
+            last_stats = executor.last_stats().or(last_stats);
+            print_watches(&watches, executor, format, precision);
             acc.clear();
         }
     }
 
+    if let Some(path) = history_path
+        && let Err(e) = save_history(&path, recent_history(&history, options.history_size))
+    {
+        error!("Error saving history to {}: {}", path.display(), e);
+    }
     Ok(())
 }
 
+/// The result of waiting for the next line of REPL input.
+#[derive(Debug, PartialEq)]
+enum ReadOutcome {
+    /// A line of input, including its trailing newline (if any).
+    Line(String),
+    /// The input was closed.
+    Eof,
+    /// No input arrived within the idle timeout.
+    IdleTimeout,
+}
+
+/// Reads REPL input lines, optionally giving up after a period of inactivity.
+struct LineReader {
+    /// Lines read by a background thread, when there is an idle timeout. `read_line`
+    /// itself cannot time out, so it blocks on that thread instead of the REPL.
+    lines: Option<Receiver<io::Result<String>>>,
+    idle_timeout: Option<Duration>,
+}
+
+impl LineReader {
+    /// Creates a reader for stdin, timing out after `idle_timeout` without input.
+    fn stdin(idle_timeout: Option<Duration>) -> Self {
+        let lines = idle_timeout.map(|_| {
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || {
+                loop {
+                    let mut line = String::new();
+                    let result = io::stdin().read_line(&mut line).map(|_| line);
+                    let done = !matches!(&result, Ok(line) if !line.is_empty());
+                    if sender.send(result).is_err() || done {
+                        break;
+                    }
+                }
+            });
+            receiver
+        });
+        LineReader {
+            lines,
+            idle_timeout,
+        }
+    }
+
+    /// Waits for the next line of input.
+    fn next_line(&self) -> io::Result<ReadOutcome> {
+        let received = match (&self.lines, self.idle_timeout) {
+            (Some(lines), Some(timeout)) => match lines.recv_timeout(timeout) {
+                Ok(result) => Some(result),
+                Err(RecvTimeoutError::Timeout) => return Ok(ReadOutcome::IdleTimeout),
+                Err(RecvTimeoutError::Disconnected) => None,
+            },
+            (Some(lines), None) => lines.recv().ok(),
+            (None, _) => {
+                let mut line = String::new();
+                io::stdin().read_line(&mut line)?;
+                Some(Ok(line))
+            }
+        };
+        match received {
+            Some(Ok(line)) if !line.is_empty() => Ok(ReadOutcome::Line(line)),
+            Some(Err(e)) => Err(e),
+            _ => Ok(ReadOutcome::Eof),
+        }
+    }
+}
+
+/// Returns where the REPL history is stored: `$ARITH_HISTORY` if set, otherwise
+/// `~/.arith_history`, or `None` if history should not be persisted.
+fn history_file() -> Option<PathBuf> {
+    match env::var_os(HISTORY_FILE_VARIABLE) {
+        Some(path) if path.is_empty() => None,
+        Some(path) => Some(PathBuf::from(path)),
+        None => env::var_os("HOME").map(|home| Path::new(&home).join(".arith_history")),
+    }
+}
+
+/// Records a completed statement in the history. Blank input and REPL commands
+/// (lines starting with `:`) are not recorded.
+fn record_history(history: &mut Vec<String>, statement: &str) {
+    let statement = statement.trim_end();
+    if statement.trim().is_empty() || statement.trim_start().starts_with(':') {
+        return;
+    }
+    history.push(statement.to_string());
+}
+
+/// Parses the contents of a history file, one statement per line, with a trailing
+/// `\` or binary operator joining a line to the next as it does at the prompt.
+fn parse_history(contents: &str) -> Vec<String> {
+    let mut history = Vec::new();
+    let mut statement = String::new();
+    for line in contents.lines() {
+        statement.push_str(line);
+        if line.trim_end().ends_with('\\') || ends_with_operator(line) {
+            statement.push('\n');
+        } else {
+            record_history(&mut history, &statement);
+            statement.clear();
+        }
+    }
+    record_history(&mut history, &statement);
+    history
+}
+
+/// Returns the last `limit` statements of `history`, the ones kept when it is saved.
+fn recent_history(history: &[String], limit: usize) -> &[String] {
+    &history[history.len().saturating_sub(limit)..]
+}
+
+/// Writes `history` to the history file at `path`, one statement per line.
+fn save_history(path: &Path, history: &[String]) -> io::Result<()> {
+    let mut contents = history.join("\n");
+    contents.push('\n');
+    fs::write(path, contents)
+}
+
 /// Saves the accumulated REPL input to a file.
 ///
 /// This function handles appending the `.arith` extension if not present
@@ -139,7 +629,7 @@ fn save_output(filename: &str, content: &str) -> io::Result<()> {
     }
 
     let path = Path::new(&file_path);
-    let mut file = File::create(&path)?;
+    let mut file = File::create(path)?;
     file.write_all(content.as_bytes())?;
     println!("Output saved to {}", file_path);
     Ok(())
@@ -150,36 +640,545 @@ fn save_output(filename: &str, content: &str) -> io::Result<()> {
 ///
 /// # Arguments
 /// * `input` - The string containing one or more logical arithmetic expressions.
-fn eval_and_print(input: &str) {
+/// * `executor` - The session's executor, holding the variables defined so far.
+/// * `prefix` - The text printed before each result.
+/// * `format` - The notation results are printed in.
+/// * `precision` - The maximum number of decimal places to print.
+fn eval_and_print(
+    input: &str,
+    executor: &mut SimpleExecutor,
+    prefix: &str,
+    format: FormatMode,
+    precision: usize,
+) {
     // orchestrator can accept multiple logical lines; we'll pass the whole chunk.
-    let results = evaluate_lines(input);
+    let results = evaluate_lines(input, executor);
+    print_results(results, prefix, format, precision);
+}
 
-    // Print each result on its own line in order
+/// Evaluates `input` like `eval_and_print` for the `:try` command, but if any of it
+/// fails, returns `executor` to the state it was in beforehand.
+///
+/// # Returns
+/// The result of every expression in `input`, including those that were rolled back.
+fn try_evaluate(input: &str, executor: &mut SimpleExecutor) -> Vec<EvalResult> {
+    let snapshot = executor.snapshot();
+    let results = evaluate_lines(input, executor);
+    if results.iter().any(Result::is_err) {
+        executor.restore(snapshot);
+    }
+    results
+}
+
+/// Prints each result on its own line in order, and each error to the log.
+fn print_results(results: Vec<EvalResult>, prefix: &str, format: FormatMode, precision: usize) {
     for res in results {
         match res {
-            Ok((v, _)) => println!("= {}", fmt_num(v)),
+            Ok((v, _)) => println!("{}", format_result(v, prefix, format, precision)),
             Err(e) => error!("! {}", e), // assumes EvalError: Display
         }
     }
 }
 
-/// Formats a floating-point number (`f64`) for display, removing unnecessary
-/// trailing zeros and ensuring integer values are displayed without a decimal point.
+/// Compiles `input` without running it, for the `:bytecode` command.
+///
+/// The input is tokenized with the executor's options, parsed as a single statement, and
+/// compiled with `BytecodeCompiler::compile_statement`.
+///
+/// # Returns
+/// One line per instruction, prefixed with its index, or the message of the error that
+/// stopped compilation.
+fn bytecode_listing(input: &str, executor: &SimpleExecutor) -> Result<Vec<String>, String> {
+    let tokens = Tokenizer::new(input.to_string())
+        .with_comment_delimiter(&executor.options().comment_delimiter)
+        .with_thousands_commas(executor.options().thousands_commas)
+        .tokenize()
+        .map_err(|e| e.to_string())?;
+    let statement = Parser::new(tokens)
+        .with_max_depth(executor.options().max_depth)
+        .parse()
+        .map_err(|e| e.to_string())?;
+    let code = BytecodeCompiler::compile_statement(&statement).map_err(|e| e.to_string())?;
+    Ok(code
+        .iter()
+        .enumerate()
+        .map(|(i, instr)| format!("{:>4}  {}", i, instr))
+        .collect())
+}
+
+/// Evaluates the file at `path` against `executor` for the `:load` command, so its
+/// definitions stay available at the prompt.
+///
+/// # Returns
+/// The result of every expression in the file, or a message explaining why the file
+/// could not be read.
+fn load_file(path: &Path, executor: &mut SimpleExecutor) -> Result<Vec<EvalResult>, String> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(evaluate_lines(&contents, executor)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            Err(format!("Cannot load '{}': no such file", path.display()))
+        }
+        Err(e) => Err(format!("Cannot load '{}': {}", path.display(), e)),
+    }
+}
+
+/// Renders a result as the REPL prints it, e.g. `= 4` with the default prefix.
+fn format_result(value: f64, prefix: &str, format: FormatMode, precision: usize) -> String {
+    format!("{}{}", prefix, format_value(value, format, precision))
+}
+
+/// Describes the statistics of an evaluation for the `:profile-last` command.
+fn format_stats(stats: &ExecStats) -> String {
+    format!(
+        "Instructions: {} compiled, {} executed\nTime: {:?}",
+        stats.compiled, stats.executed, stats.elapsed
+    )
+}
+
+/// Evaluates every watch expression against the executor's current state.
+///
+/// Watches run against a copy of the executor, so they never change the session's
+/// variables or add to its `total`.
 ///
 /// # Arguments
-/// * `x` - The `f64` number to format.
+/// * `watches` - The registered watch expressions, in registration order.
+/// * `executor` - The session's executor, holding the variables defined so far.
+/// * `format` - The notation values are shown in.
+/// * `precision` - The maximum number of decimal places to show.
+///
+/// # Returns
+/// One line per watch, either `watch: expr = value` or `watch: expr ! error`.
+fn evaluate_watches(
+    watches: &[String],
+    executor: &SimpleExecutor,
+    format: FormatMode,
+    precision: usize,
+) -> Vec<String> {
+    watches
+        .iter()
+        .map(|watch| {
+            let results = evaluate_lines(watch, &mut executor.clone());
+            match results.last() {
+                Some(Ok((v, _))) => {
+                    format!("watch: {} = {}", watch, format_value(*v, format, precision))
+                }
+                Some(Err(e)) => format!("watch: {} ! {}", watch, e),
+                None => format!("watch: {} ! no value", watch),
+            }
+        })
+        .collect()
+}
+
+/// Prints the current value of every watch expression.
+fn print_watches(
+    watches: &[String],
+    executor: &SimpleExecutor,
+    format: FormatMode,
+    precision: usize,
+) {
+    for line in evaluate_watches(watches, executor, format, precision) {
+        println!("{}", line);
+    }
+}
+
+/// Lists the executor's variables for the `:vars` command (and `--dump-symbols-on-exit`).
+///
+/// # Returns
+/// One `name = value` line per variable, sorted by name, or a single
+/// `No variables defined.` line if there are none.
+pub(crate) fn list_variables(
+    executor: &SimpleExecutor,
+    format: FormatMode,
+    precision: usize,
+) -> Vec<String> {
+    let variables: Vec<_> = executor.variables().collect();
+    if variables.is_empty() {
+        return vec!["No variables defined.".to_string()];
+    }
+    variables
+        .into_iter()
+        .map(|(name, value)| {
+            format!(
+                "{} = {}",
+                name,
+                format_value(value.as_f64(), format, precision)
+            )
+        })
+        .collect()
+}
+
+/// Returns `true` if `name` can be used as a variable name, i.e. it is a single
+/// identifier token and not a keyword.
+fn is_identifier(name: &str) -> bool {
+    matches!(
+        Tokenizer::new(name.to_string()).tokenize().as_deref(),
+        Ok([token, _eof]) if matches!(token.get_type(), TokenType::Identifier { .. })
+    )
+}
+
+/// Rewrites every reference to the variable `old` in `expression` to `new`, leaving
+/// the rest of the text, and names that merely contain `old`, untouched.
+///
+/// An expression that does not tokenize is returned unchanged.
+fn rename_in_expression(expression: &str, old: &str, new: &str) -> String {
+    let Ok(tokens) = Tokenizer::new(expression.to_string()).tokenize() else {
+        return expression.to_string();
+    };
+    // Columns are 1-based character positions.
+    let starts: Vec<usize> = tokens
+        .iter()
+        .filter(|t| matches!(t.get_type(), TokenType::Identifier { name } if name == old))
+        .map(|t| t.get_start() - 1)
+        .collect();
+
+    let mut renamed = String::new();
+    let mut chars = expression.chars().enumerate();
+    while let Some((i, c)) = chars.next() {
+        if starts.contains(&i) {
+            renamed.push_str(new);
+            // Skip the rest of the old name.
+            for _ in 1..old.chars().count() {
+                chars.next();
+            }
+        } else {
+            renamed.push(c);
+        }
+    }
+    renamed
+}
+
+/// Parses the `on`/`off` argument of a toggle command such as `:multi`.
+///
+/// # Returns
+/// `Some(true)` for `on`, `Some(false)` for `off`, and `None` for anything else.
+fn parse_toggle(argument: &str) -> Option<bool> {
+    match argument {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses the argument of the `:precision` command.
 ///
 /// # Returns
-/// A `String` representation of the formatted number.
-fn fmt_num(x: f64) -> String {
-    // Show as integer if it is exactly an integer, else as trimmed float
-    if x.fract() == 0.0 && x.is_finite() {
-        format!("{}", x as i64)
-    } else {
-        // 15 sig figs is a decent default without getting noisy
-        let s = format!("{:.15}", x);
-        // trim trailing zeros and possible trailing dot
-        let s = s.trim_end_matches('0').trim_end_matches('.');
-        s.to_string()
+/// The requested number of decimal places, or a message explaining why it was rejected.
+fn parse_precision(argument: &str) -> Result<usize, String> {
+    match argument.parse::<usize>() {
+        Ok(n) if n <= MAX_PRECISION => Ok(n),
+        _ => Err(format!(
+            "Invalid precision '{}': expected an integer from 0 to {}",
+            argument, MAX_PRECISION
+        )),
+    }
+}
+
+/// Parses the argument of the `:format` command.
+///
+/// # Returns
+/// The requested number format, or a message explaining why it was rejected. `tsv` is
+/// rejected because it only changes the layout of file-mode output.
+fn parse_format(argument: &str) -> Result<FormatMode, String> {
+    match argument.parse()? {
+        FormatMode::Tsv => Err("The tsv format only applies to file mode".to_string()),
+        mode => Ok(mode),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_tracks_variable_changes() {
+        let mut executor = SimpleExecutor::new();
+        let watches = vec!["x * 10".to_string()];
+
+        evaluate_lines("let x = 2", &mut executor);
+        assert_eq!(
+            evaluate_watches(&watches, &executor, FormatMode::Auto, DEFAULT_PRECISION),
+            vec!["watch: x * 10 = 20"]
+        );
+
+        evaluate_lines("x = 5", &mut executor);
+        assert_eq!(
+            evaluate_watches(&watches, &executor, FormatMode::Auto, DEFAULT_PRECISION),
+            vec!["watch: x * 10 = 50"]
+        );
+    }
+
+    #[test]
+    fn test_watch_reports_errors() {
+        let executor = SimpleExecutor::new();
+        let watches = vec!["y".to_string()];
+        assert_eq!(
+            evaluate_watches(&watches, &executor, FormatMode::Auto, DEFAULT_PRECISION),
+            vec!["watch: y ! runtime error: undefined variable 'y' in input: y"]
+        );
+    }
+
+    #[test]
+    fn test_watch_does_not_change_total() {
+        let mut executor = SimpleExecutor::new();
+        evaluate_lines("5", &mut executor);
+        evaluate_watches(
+            &["total + 1".to_string()],
+            &executor,
+            FormatMode::Auto,
+            DEFAULT_PRECISION,
+        );
+        assert_eq!(executor.variable(TOTAL_VARIABLE), Some(5.0));
+    }
+
+    #[test]
+    fn test_list_variables_sorted() {
+        let mut executor = SimpleExecutor::new();
+        evaluate_lines("let b = 2\nlet a = 1.5", &mut executor);
+        assert_eq!(
+            list_variables(&executor, FormatMode::Auto, 3),
+            vec![
+                "a = 1.5",
+                "b = 2",
+                "e = 2.718",
+                "pi = 3.142",
+                "tau = 6.283",
+                "total = 0"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multi_toggle_splits_statements() {
+        let mut executor = SimpleExecutor::new();
+        let line = "1 + 1; 2 + 2";
+        assert_eq!(evaluate_lines(line, &mut executor).len(), 1);
+
+        executor.options_mut().multi_statement = parse_toggle("on").unwrap();
+        let results = evaluate_lines(line, &mut executor);
+        let values: Vec<f64> = results.into_iter().map(|r| r.unwrap().0).collect();
+        assert_eq!(values, vec![2.0, 4.0]);
+
+        executor.options_mut().multi_statement = parse_toggle("off").unwrap();
+        assert_eq!(evaluate_lines(line, &mut executor).len(), 1);
+        assert_eq!(parse_toggle("maybe"), None);
+    }
+
+    #[test]
+    fn test_profile_last_stats() {
+        let mut executor = SimpleExecutor::new();
+        evaluate_lines("let x = 3\nx * 2 + 1", &mut executor);
+        let stats = executor.last_stats().unwrap();
+        // Load x, Push 2, Mul, Push 1, Add
+        assert_eq!((stats.compiled, stats.executed), (5, 5));
+        let report = format_stats(&stats);
+        assert!(
+            report.starts_with("Instructions: 5 compiled, 5 executed\nTime: "),
+            "{}",
+            report
+        );
+
+        // Watches run on a copy, so they leave the statistics alone.
+        evaluate_watches(&["1 + x".to_string()], &executor, FormatMode::Auto, 6);
+        assert_eq!(executor.last_stats().unwrap().compiled, 5);
+    }
+
+    #[test]
+    fn test_parse_precision() {
+        assert_eq!(parse_precision("3"), Ok(3));
+        assert_eq!(parse_precision("0"), Ok(0));
+        assert!(parse_precision("-1").is_err());
+        assert!(parse_precision("2.5").is_err());
+        assert!(parse_precision("18").is_err());
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(parse_format("sci"), Ok(FormatMode::Scientific));
+        assert_eq!(
+            parse_format("tsv"),
+            Err("The tsv format only applies to file mode".to_string())
+        );
+        assert!(parse_format("hex").is_err());
+    }
+
+    #[test]
+    fn test_precision_changes_rendering() {
+        let mut executor = SimpleExecutor::new();
+        let (third, _) = evaluate_lines("1 / 3", &mut executor).remove(0).unwrap();
+        assert_eq!(
+            format_value(third, FormatMode::Auto, DEFAULT_PRECISION),
+            "0.333333333333333"
+        );
+        assert_eq!(
+            format_value(third, FormatMode::Auto, parse_precision("3").unwrap()),
+            "0.333"
+        );
+        assert_eq!(format_value(2.5, FormatMode::Auto, 3), "2.5");
+        assert_eq!(format_value(9.6, FormatMode::Auto, 0), "10");
+        assert_eq!(format_value(4.0, FormatMode::Auto, 0), "4");
+    }
+
+    #[test]
+    fn test_rename_updates_watches() {
+        assert_eq!(
+            rename_in_expression("x * 2 + max_x", "x", "y"),
+            "y * 2 + max_x"
+        );
+        assert_eq!(
+            rename_in_expression("width*height", "width", "w"),
+            "w*height"
+        );
+        assert_eq!(rename_in_expression("1 + $", "x", "y"), "1 + $");
+        assert!(is_identifier("new_name"));
+        assert!(!is_identifier("let"));
+        assert!(!is_identifier("2x"));
+    }
+
+    #[test]
+    fn test_bytecode_listing() {
+        let executor = SimpleExecutor::new();
+        assert_eq!(
+            bytecode_listing("x + 2 * 3", &executor).unwrap(),
+            vec!["   0  LOAD x", "   1  PUSH 6", "   2  ADD"]
+        );
+        assert_eq!(
+            bytecode_listing("let y: Float = sqrt(x)", &executor).unwrap(),
+            vec![
+                "   0  LOAD x",
+                "   1  CALL sqrt 1",
+                "   2  CHECK_TYPE Float",
+                "   3  STORE y"
+            ]
+        );
+        assert!(bytecode_listing("1 +", &executor).is_err());
+    }
+
+    #[test]
+    fn test_history_records_statements_only() {
+        let mut history = Vec::new();
+        for input in [
+            "1 + 2\n",
+            ":help",
+            "\n",
+            "let x = 4\n",
+            "  :q",
+            "x * \\\n2\n",
+        ] {
+            record_history(&mut history, input);
+        }
+        assert_eq!(history, vec!["1 + 2", "let x = 4", "x * \\\n2"]);
+    }
+
+    #[test]
+    fn test_history_file_round_trip() {
+        let history = vec![
+            "1 + 2".to_string(),
+            "x * \\\n2".to_string(),
+            "x +\n1".to_string(),
+        ];
+        let path = std::env::temp_dir().join(format!("arith_history_{}", std::process::id()));
+        save_history(&path, &history).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(parse_history(&contents), history);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_recent_history_drops_oldest_entries() {
+        let history: Vec<String> = (1..=5).map(|n| n.to_string()).collect();
+        assert_eq!(recent_history(&history, 3), ["3", "4", "5"]);
+        assert_eq!(recent_history(&history, 5), history.as_slice());
+        assert_eq!(recent_history(&history, 10), history.as_slice());
+        assert!(recent_history(&history, 0).is_empty());
+    }
+
+    #[test]
+    fn test_load_file_keeps_definitions() {
+        let path = std::env::temp_dir().join(format!("arith_load_{}.arith", std::process::id()));
+        fs::write(
+            &path,
+            "let rate = 3\nlet f(x) = x * rate\nf(2)\nundefined_name\n",
+        )
+        .unwrap();
+        let mut executor = SimpleExecutor::new();
+        let results = load_file(&path, &mut executor).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().0, 3.0);
+        assert_eq!(results[1].as_ref().unwrap().0, 6.0);
+        assert!(results[2].is_err());
+        // The definitions remain available afterwards.
+        let after = evaluate_lines("f(rate)", &mut executor);
+        assert_eq!(after[0].as_ref().unwrap().0, 9.0);
+    }
+
+    #[test]
+    fn test_try_rolls_back_on_error() {
+        let mut executor = SimpleExecutor::new();
+        evaluate_lines("let x = 1", &mut executor);
+
+        let results = try_evaluate("x = 2\nlet y = 3\nundefined_name", &mut executor);
+        assert_eq!(results.len(), 3);
+        assert!(results[2].is_err());
+        assert_eq!(executor.variable("x"), Some(1.0));
+        assert_eq!(executor.variable("y"), None);
+
+        let results = try_evaluate("x = 2\nlet y = 3", &mut executor);
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(executor.variable("x"), Some(2.0));
+        assert_eq!(executor.variable("y"), Some(3.0));
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let message =
+            load_file(Path::new("no/such/file.arith"), &mut SimpleExecutor::new()).unwrap_err();
+        assert_eq!(message, "Cannot load 'no/such/file.arith': no such file");
+    }
+
+    #[test]
+    fn test_result_prefix() {
+        let resolve = |flag: Option<&str>, env_value: Option<&str>| {
+            resolve_result_prefix(flag.map(String::from), env_value.map(String::from))
+        };
+        assert_eq!(resolve(None, None), "= ");
+        assert_eq!(resolve(None, Some(">>> ")), ">>> ");
+        assert_eq!(resolve(Some(""), Some(">>> ")), "");
+
+        let prefix = resolve(Some(">>> "), None);
+        let format = FormatMode::Auto;
+        assert_eq!(
+            format_result(4.0, &prefix, format, DEFAULT_PRECISION),
+            ">>> 4"
+        );
+        assert_eq!(format_result(0.5, "", format, DEFAULT_PRECISION), "0.5");
+        assert_eq!(format_result(4.0, DEFAULT_RESULT_PREFIX, format, 15), "= 4");
+    }
+
+    #[test]
+    fn test_line_reader_idle_timeout() {
+        let (sender, receiver) = mpsc::channel();
+        let reader = LineReader {
+            lines: Some(receiver),
+            idle_timeout: Some(Duration::from_millis(20)),
+        };
+
+        sender.send(Ok("1 + 2\n".to_string())).unwrap();
+        assert_eq!(
+            reader.next_line().unwrap(),
+            ReadOutcome::Line("1 + 2\n".to_string())
+        );
+        // Nothing more arrives within the timeout.
+        assert_eq!(reader.next_line().unwrap(), ReadOutcome::IdleTimeout);
+        // Input that arrives later is still read.
+        sender.send(Ok("3\n".to_string())).unwrap();
+        assert_eq!(
+            reader.next_line().unwrap(),
+            ReadOutcome::Line("3\n".to_string())
+        );
+
+        drop(sender);
+        assert_eq!(reader.next_line().unwrap(), ReadOutcome::Eof);
     }
 }
@@ -0,0 +1,125 @@
+//! This module implements the `self-test` subcommand, a quick check that an installed
+//! `arith` evaluates a set of known expressions correctly.
+//!
+//! Every case runs through the normal pipeline with `eval_str`, so a failure points at a
+//! broken build or platform rather than at the test itself.
+
+use crate::builtins::approx_eq;
+use crate::eval_str;
+use std::fmt;
+
+/// The expressions checked by `run_self_test`, each with its expected value.
+///
+/// The cases cover precedence, associativity, implicit multiplication, variables,
+/// built-in functions and constants, comparisons, and conditionals.
+pub const SELF_TEST_CASES: &[(&str, f64)] = &[
+    ("1 + 2 * 3", 7.0),
+    ("(1 + 2) * 3", 9.0),
+    ("2 ^ 3 ^ 2", 512.0),
+    ("8 / 4 / 2", 1.0),
+    ("-2 ^ 2", -4.0),
+    ("10 - 4 - 3", 3.0),
+    ("7 / 2", 3.5),
+    ("2(3 + 4)", 14.0),
+    ("1_000 + 1e3", 2000.0),
+    ("5!", 120.0),
+    ("6 & 3 | 8", 10.0),
+    ("1 << 4", 16.0),
+    ("let r = 3\n2r", 6.0),
+    ("sqrt(16) + abs(-2)", 6.0),
+    ("gcd(12, 18)", 6.0),
+    ("max(1, 5, 3)", 5.0),
+    ("cos(0)", 1.0),
+    ("pi", std::f64::consts::PI),
+    ("0.1 + 0.2 ~= 0.3", 1.0),
+    ("3 > 2 ? 10 : 20", 10.0),
+];
+
+/// The outcome of one self-test case.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfTestResult {
+    /// The expression that was evaluated.
+    pub expression: &'static str,
+    /// The value the expression should produce.
+    pub expected: f64,
+    /// The value it produced, or the message of the error it failed with.
+    pub actual: Result<f64, String>,
+}
+
+impl SelfTestResult {
+    /// Returns `true` if the expression produced its expected value, compared with
+    /// `approx_eq` so rounding in the last bits does not count as a failure.
+    pub fn passed(&self) -> bool {
+        matches!(self.actual, Ok(value) if approx_eq(value, self.expected))
+    }
+}
+
+/// Implements the `Display` trait for `SelfTestResult`, e.g. `PASS 1 + 2 * 3 = 7` or
+/// `FAIL 1 + 2 * 3: expected 7, got 9`. Line breaks in the expression are shown as `; `.
+impl fmt::Display for SelfTestResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let expression = self.expression.replace('\n', "; ");
+        match &self.actual {
+            Ok(value) if self.passed() => write!(f, "PASS {} = {}", expression, value),
+            Ok(value) => write!(
+                f,
+                "FAIL {}: expected {}, got {}",
+                expression, self.expected, value
+            ),
+            Err(e) => write!(
+                f,
+                "FAIL {}: expected {}, got error: {}",
+                expression, self.expected, e
+            ),
+        }
+    }
+}
+
+/// Evaluates every case in `SELF_TEST_CASES`, each against a fresh executor.
+///
+/// # Returns
+/// One `SelfTestResult` per case, in table order.
+pub fn run_self_test() -> Vec<SelfTestResult> {
+    SELF_TEST_CASES
+        .iter()
+        .map(|&(expression, expected)| SelfTestResult {
+            expression,
+            expected,
+            actual: eval_str(expression).map_err(|e| e.to_string()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_test_passes() {
+        let failures: Vec<String> = run_self_test()
+            .iter()
+            .filter(|result| !result.passed())
+            .map(SelfTestResult::to_string)
+            .collect();
+        assert!(failures.is_empty(), "{:#?}", failures);
+    }
+
+    #[test]
+    fn test_self_test_result_display() {
+        let result = |actual| SelfTestResult {
+            expression: "let x = 2\nx + 1",
+            expected: 3.0,
+            actual,
+        };
+        assert_eq!(result(Ok(3.0)).to_string(), "PASS let x = 2; x + 1 = 3");
+        assert_eq!(
+            result(Ok(4.0)).to_string(),
+            "FAIL let x = 2; x + 1: expected 3, got 4"
+        );
+        assert_eq!(
+            result(Err("boom".to_string())).to_string(),
+            "FAIL let x = 2; x + 1: expected 3, got error: boom"
+        );
+        assert!(!result(Err("boom".to_string())).passed());
+    }
+}
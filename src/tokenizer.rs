@@ -19,13 +19,84 @@ pub enum TokenType {
     Div,
     /// The multiplication operator `*`.
     Mul,
+    /// The exponentiation operator `^`.
+    Pow,
+
+    /// The bitwise AND operator `&`.
+    BitAnd,
+    /// The bitwise OR operator `|`.
+    BitOr,
+    /// The prefix bitwise NOT operator `~`.
+    BitNot,
+    /// The left shift operator `<<`.
+    ShiftLeft,
+    /// The arithmetic right shift operator `>>`.
+    ShiftRight,
 
     /// An opening parenthesis `(`.
     ParanOpen,
     /// A closing parenthesis `)`.
     ParanClose,
+    /// A comma `,`, separating the arguments of a function call.
+    Comma,
+    /// A colon `:`, introducing the type annotation in `let x: Int = 1` and
+    /// separating the branches of a conditional `c ? a : b`.
+    Colon,
+    /// A question mark `?`, introducing the branches of a conditional `c ? a : b`.
+    Question,
+
+    /// The assignment operator `=`.
+    Assign,
+    /// The compound assignment operator `+=`.
+    PlusAssign,
+    /// The compound assignment operator `-=`.
+    MinusAssign,
+    /// The compound assignment operator `*=`.
+    MulAssign,
+    /// The compound assignment operator `/=`.
+    DivAssign,
+
+    /// The comparison operator `<`.
+    Less,
+    /// The comparison operator `>`.
+    Greater,
+    /// The comparison operator `<=`.
+    LessEqual,
+    /// The comparison operator `>=`.
+    GreaterEqual,
+    /// The comparison operator `==`.
+    Equal,
+    /// The comparison operator `!=`.
+    NotEqual,
+    /// The tolerant comparison operator `~=`, true when both sides are nearly equal.
+    ApproxEqual,
+    /// The postfix factorial operator `!`.
+    Bang,
+    /// The postfix percent operator `%`, dividing its operand by 100. A `%` is read as
+    /// this when the next character cannot start an operand, e.g. in `50%` or `50% * 2`.
+    Percent,
+    /// The remainder operator `%`. A `%` is read as this when an operand follows it,
+    /// e.g. in `7 % 3`.
+    Modulo,
+    /// The reserved word `of`, which may only follow a percent, as in `50% of 200`,
+    /// multiplying it by what follows.
+    Of,
 
-    /// A comment, starting with `;` and extending to the end of the line.
+    /// The `let` keyword, introducing a variable definition.
+    Let,
+    /// The `if` keyword, reserved for future use; it cannot name a variable.
+    If,
+    /// The `const` keyword, reserved for future use; it cannot name a variable.
+    Const,
+    /// The `mut` keyword, reserved for future use; it cannot name a variable.
+    Mut,
+    /// An identifier, such as a variable or built-in function name.
+    Identifier { name: String },
+    /// A reference to an earlier result by its 1-based index, such as `$3`.
+    HistoryRef { index: usize },
+
+    /// A comment, starting with the comment delimiter (`;` by default) and extending to
+    /// the end of the line.
     Comment { contents: String },
     /// A number literal, which can be an integer, a float, or in scientific notation.
     Number { value: String },
@@ -33,6 +104,59 @@ pub enum TokenType {
     EOF,
 }
 
+impl TokenType {
+    /// Returns the source spelling of a keyword token, e.g. `let` for `TokenType::Let`,
+    /// or `None` if this token is not a keyword.
+    pub fn keyword(&self) -> Option<&'static str> {
+        match self {
+            TokenType::Let => Some("let"),
+            TokenType::Of => Some("of"),
+            TokenType::If => Some("if"),
+            TokenType::Const => Some("const"),
+            TokenType::Mut => Some("mut"),
+            _ => None,
+        }
+    }
+
+    /// Returns the keyword token spelled `word`, or `None` if `word` is not a keyword.
+    pub fn from_keyword(word: &str) -> Option<TokenType> {
+        match word {
+            "let" => Some(TokenType::Let),
+            "of" => Some(TokenType::Of),
+            "if" => Some(TokenType::If),
+            "const" => Some(TokenType::Const),
+            "mut" => Some(TokenType::Mut),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` for the comparison operators (`<`, `>`, `<=`, `>=`, `==`, `!=`, `~=`).
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            TokenType::Less
+                | TokenType::Greater
+                | TokenType::LessEqual
+                | TokenType::GreaterEqual
+                | TokenType::Equal
+                | TokenType::NotEqual
+                | TokenType::ApproxEqual
+        )
+    }
+
+    /// Returns `true` for `=` and the compound assignment operators (`+=`, `-=`, `*=`, `/=`).
+    pub fn is_assignment(&self) -> bool {
+        matches!(
+            self,
+            TokenType::Assign
+                | TokenType::PlusAssign
+                | TokenType::MinusAssign
+                | TokenType::MulAssign
+                | TokenType::DivAssign
+        )
+    }
+}
+
 /// Represents a token, a single lexical unit of the `arith` language.
 ///
 /// A token has a `token_type`, and its location in the source code is tracked
@@ -89,6 +213,19 @@ impl Token {
     pub fn paran_close(line_no: usize, pos: usize) -> Token {
         Token::new(TokenType::ParanClose, line_no, pos, pos)
     }
+    pub fn comma(line_no: usize, pos: usize) -> Token {
+        Token::new(TokenType::Comma, line_no, pos, pos)
+    }
+    pub fn identifier(name: &str, line_no: usize, start: usize) -> Token {
+        Token::new(
+            TokenType::Identifier {
+                name: name.to_string(),
+            },
+            line_no,
+            start,
+            start + name.len(),
+        )
+    }
     pub fn comment(contents: &str, line_no: usize, start: usize) -> Token {
         Token::new(
             TokenType::Comment {
@@ -122,8 +259,39 @@ impl Display for TokenType {
             TokenType::Minus => write!(f, "Minus"),
             TokenType::Div => write!(f, "Div"),
             TokenType::Mul => write!(f, "Mul"),
+            TokenType::Pow => write!(f, "Pow"),
+            TokenType::BitAnd => write!(f, "BitAnd"),
+            TokenType::BitOr => write!(f, "BitOr"),
+            TokenType::BitNot => write!(f, "BitNot"),
+            TokenType::ShiftLeft => write!(f, "ShiftLeft"),
+            TokenType::ShiftRight => write!(f, "ShiftRight"),
             TokenType::ParanOpen => write!(f, "ParanOpen"),
             TokenType::ParanClose => write!(f, "ParanClose"),
+            TokenType::Comma => write!(f, "Comma"),
+            TokenType::Colon => write!(f, "Colon"),
+            TokenType::Question => write!(f, "Question"),
+            TokenType::Assign => write!(f, "Assign"),
+            TokenType::PlusAssign => write!(f, "PlusAssign"),
+            TokenType::MinusAssign => write!(f, "MinusAssign"),
+            TokenType::MulAssign => write!(f, "MulAssign"),
+            TokenType::DivAssign => write!(f, "DivAssign"),
+            TokenType::Less => write!(f, "Less"),
+            TokenType::Greater => write!(f, "Greater"),
+            TokenType::LessEqual => write!(f, "LessEqual"),
+            TokenType::GreaterEqual => write!(f, "GreaterEqual"),
+            TokenType::Equal => write!(f, "Equal"),
+            TokenType::NotEqual => write!(f, "NotEqual"),
+            TokenType::ApproxEqual => write!(f, "ApproxEqual"),
+            TokenType::Bang => write!(f, "Bang"),
+            TokenType::Percent => write!(f, "Percent"),
+            TokenType::Modulo => write!(f, "Modulo"),
+            TokenType::Of => write!(f, "Of"),
+            TokenType::Let => write!(f, "Let"),
+            TokenType::If => write!(f, "If"),
+            TokenType::Const => write!(f, "Const"),
+            TokenType::Mut => write!(f, "Mut"),
+            TokenType::Identifier { name } => write!(f, "Identifier({})", name),
+            TokenType::HistoryRef { index } => write!(f, "HistoryRef(${})", index),
             TokenType::Comment { contents } => write!(f, "Comment: {}", contents),
             TokenType::Number { value } => write!(f, "Number({})", value),
             TokenType::EOF => write!(f, "eof"),
@@ -148,15 +316,20 @@ impl Display for Token {
 pub struct Tokenizer {
     content: String,
     tokens: Vec<Token>,
+    comment_delimiter: String,
+    thousands_commas: bool,
 }
 
+/// The delimiter that starts a comment unless another is configured.
+pub const DEFAULT_COMMENT_DELIMITER: &str = ";";
+
 impl Debug for Tokenizer {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "Tokenizer {{ ")?;
         write!(f, "content:\n{}\n", self.content)?;
-        write!(f, "tokens:\n")?;
+        writeln!(f, "tokens:")?;
         for token in &self.tokens {
-            write!(f, "\t{}\n", token)?;
+            writeln!(f, "\t{}", token)?;
         }
         write!(f, "}}")
     }
@@ -167,9 +340,30 @@ impl Tokenizer {
         Tokenizer {
             content,
             tokens: Vec::new(),
+            comment_delimiter: DEFAULT_COMMENT_DELIMITER.to_string(),
+            thousands_commas: false,
         }
     }
 
+    /// Sets the character(s) that start a comment running to the end of the line,
+    /// e.g. `#` or `//`, in place of the default `;`.
+    pub fn with_comment_delimiter(mut self, delimiter: &str) -> Tokenizer {
+        self.comment_delimiter = delimiter.to_string();
+        self
+    }
+
+    /// Sets whether a comma inside a number separates groups of thousands, so that
+    /// `1,234,567` is the number `1234567`.
+    ///
+    /// A comma is only taken as a separator when it follows a digit of the integer part
+    /// and is followed by exactly three digits; any other comma, as in `gcd(1,2)` or
+    /// `gcd(1, 234)`, still separates arguments. `gcd(1,234)` is therefore the single
+    /// argument `1234` in this mode.
+    pub fn with_thousands_commas(mut self, enabled: bool) -> Tokenizer {
+        self.thousands_commas = enabled;
+        self
+    }
+
     /// Performs the tokenization of the input string.
     ///
     /// It iterates through the characters of the input string and constructs a
@@ -183,31 +377,121 @@ impl Tokenizer {
         let mut i = 0;
         let mut line_no = 0;
         let mut col = 0;
+        let delimiter: Vec<char> = self.comment_delimiter.chars().collect();
 
         while i < len {
             let c = chars[i];
 
             match c {
+                _ if !delimiter.is_empty() && chars[i..].starts_with(&delimiter) => {
+                    // Comments run to the end of the line.
+                    let start_col = col;
+                    i += delimiter.len(); // consume the delimiter
+
+                    let mut comment = String::new();
+                    while i < len && chars[i] != '\n' {
+                        comment.push(chars[i]);
+                        i += 1;
+                    }
+
+                    col = start_col + delimiter.len() + comment.chars().count();
+                    tokens.push(Token::comment(&comment, line_no + 1, start_col + 1));
+                }
+                '+' if i + 1 < len && chars[i + 1] == '=' => {
+                    tokens.push(Token::new(
+                        TokenType::PlusAssign,
+                        line_no + 1,
+                        col + 1,
+                        col + 2,
+                    ));
+                    i += 2;
+                    col += 2;
+                }
                 '+' => {
                     tokens.push(Token::plus(line_no + 1, col + 1));
                     i += 1;
                     col += 1;
                 }
+                '-' if i + 1 < len && chars[i + 1] == '=' => {
+                    tokens.push(Token::new(
+                        TokenType::MinusAssign,
+                        line_no + 1,
+                        col + 1,
+                        col + 2,
+                    ));
+                    i += 2;
+                    col += 2;
+                }
                 '-' => {
                     tokens.push(Token::minus(line_no + 1, col + 1));
                     i += 1;
                     col += 1;
                 }
+                '/' if i + 1 < len && chars[i + 1] == '=' => {
+                    tokens.push(Token::new(
+                        TokenType::DivAssign,
+                        line_no + 1,
+                        col + 1,
+                        col + 2,
+                    ));
+                    i += 2;
+                    col += 2;
+                }
                 '/' => {
                     tokens.push(Token::div(line_no + 1, col + 1));
                     i += 1;
                     col += 1;
                 }
+                '*' if i + 1 < len && chars[i + 1] == '=' => {
+                    tokens.push(Token::new(
+                        TokenType::MulAssign,
+                        line_no + 1,
+                        col + 1,
+                        col + 2,
+                    ));
+                    i += 2;
+                    col += 2;
+                }
                 '*' => {
                     tokens.push(Token::mul(line_no + 1, col + 1));
                     i += 1;
                     col += 1;
                 }
+                '^' => {
+                    tokens.push(Token::new(TokenType::Pow, line_no + 1, col + 1, col + 1));
+                    i += 1;
+                    col += 1;
+                }
+                '~' if i + 1 < len && chars[i + 1] == '=' => {
+                    tokens.push(Token::new(
+                        TokenType::ApproxEqual,
+                        line_no + 1,
+                        col + 1,
+                        col + 2,
+                    ));
+                    i += 2;
+                    col += 2;
+                }
+                '&' | '|' | '~' => {
+                    let token_type = match c {
+                        '&' => TokenType::BitAnd,
+                        '|' => TokenType::BitOr,
+                        _ => TokenType::BitNot,
+                    };
+                    tokens.push(Token::new(token_type, line_no + 1, col + 1, col + 1));
+                    i += 1;
+                    col += 1;
+                }
+                '<' | '>' if i + 1 < len && chars[i + 1] == c => {
+                    let token_type = if c == '<' {
+                        TokenType::ShiftLeft
+                    } else {
+                        TokenType::ShiftRight
+                    };
+                    tokens.push(Token::new(token_type, line_no + 1, col + 1, col + 2));
+                    i += 2;
+                    col += 2;
+                }
                 '(' => {
                     tokens.push(Token::paran_open(line_no + 1, col + 1));
                     i += 1;
@@ -218,19 +502,71 @@ impl Tokenizer {
                     i += 1;
                     col += 1;
                 }
-                ';' => {
-                    // Comments run to the end of the line.
-                    let start_col = col;
-                    i += 1; // consume ';'
-
-                    let mut comment = String::new();
-                    while i < len && chars[i] != '\n' {
-                        comment.push(chars[i]);
-                        i += 1;
-                    }
-
-                    col = start_col + 1 + comment.len();
-                    tokens.push(Token::comment(&comment, line_no + 1, start_col + 1));
+                '<' | '>' | '=' | '!' if i + 1 < len && chars[i + 1] == '=' => {
+                    let token_type = match c {
+                        '<' => TokenType::LessEqual,
+                        '>' => TokenType::GreaterEqual,
+                        '=' => TokenType::Equal,
+                        _ => TokenType::NotEqual,
+                    };
+                    tokens.push(Token::new(token_type, line_no + 1, col + 1, col + 2));
+                    i += 2;
+                    col += 2;
+                }
+                '<' => {
+                    tokens.push(Token::new(TokenType::Less, line_no + 1, col + 1, col + 1));
+                    i += 1;
+                    col += 1;
+                }
+                '>' => {
+                    tokens.push(Token::new(
+                        TokenType::Greater,
+                        line_no + 1,
+                        col + 1,
+                        col + 1,
+                    ));
+                    i += 1;
+                    col += 1;
+                }
+                '=' => {
+                    tokens.push(Token::new(TokenType::Assign, line_no + 1, col + 1, col + 1));
+                    i += 1;
+                    col += 1;
+                }
+                '!' => {
+                    tokens.push(Token::new(TokenType::Bang, line_no + 1, col + 1, col + 1));
+                    i += 1;
+                    col += 1;
+                }
+                '%' => {
+                    let token_type = if starts_operand(&chars[i + 1..]) {
+                        TokenType::Modulo
+                    } else {
+                        TokenType::Percent
+                    };
+                    tokens.push(Token::new(token_type, line_no + 1, col + 1, col + 1));
+                    i += 1;
+                    col += 1;
+                }
+                ',' => {
+                    tokens.push(Token::comma(line_no + 1, col + 1));
+                    i += 1;
+                    col += 1;
+                }
+                ':' => {
+                    tokens.push(Token::new(TokenType::Colon, line_no + 1, col + 1, col + 1));
+                    i += 1;
+                    col += 1;
+                }
+                '?' => {
+                    tokens.push(Token::new(
+                        TokenType::Question,
+                        line_no + 1,
+                        col + 1,
+                        col + 1,
+                    ));
+                    i += 1;
+                    col += 1;
                 }
 
                 '\n' => {
@@ -246,22 +582,55 @@ impl Tokenizer {
                 }
                 c if c.is_ascii_digit() => {
                     // Parse a number, which can be an integer, a float, or in
-                    // scientific notation.
+                    // scientific notation. Underscores may separate digits
+                    // (`1_000_000`), as may commas in thousands mode (`1,000,000`),
+                    // and are dropped from the stored value.
+                    let start = i;
                     let start_col = col;
                     let mut number = String::new();
                     let mut has_dot = false;
 
-                    while i < len && (chars[i].is_ascii_digit() || (chars[i] == '.' && !has_dot)) {
-                        if chars[i] == '.' {
-                            has_dot = true;
+                    while i < len
+                        && (chars[i].is_ascii_digit()
+                            || chars[i] == '_'
+                            || (chars[i] == '.' && !has_dot)
+                            || (self.thousands_commas && !has_dot && is_thousands_comma(&chars, i)))
+                    {
+                        if chars[i] == '_' {
+                            // A separator must sit between two digits, so leading,
+                            // trailing, and doubled underscores are rejected.
+                            let between_digits = chars[i - 1].is_ascii_digit()
+                                && i + 1 < len
+                                && chars[i + 1].is_ascii_digit();
+                            if !between_digits {
+                                return Err(TokenizerError::UnexpectedCharacter {
+                                    found: '_',
+                                    line: line_no + 1,
+                                    col: col + 1,
+                                });
+                            }
+                        } else if chars[i] != ',' {
+                            // Thousands commas, like underscores, are not stored.
+                            if chars[i] == '.' {
+                                has_dot = true;
+                            }
+                            number.push(chars[i]);
                         }
-                        number.push(chars[i]);
                         i += 1;
                         col += 1;
                     }
 
-                    // Handle scientific notation (e.g., 1e-5, 2.5E+3).
-                    if i < len && (chars[i] == 'e' || chars[i] == 'E') {
+                    // Handle scientific notation (e.g., 1e-5, 2.5E+3). The `e` only starts an
+                    // exponent when digits follow it, so `2e` and `2exp(1)` are implicit
+                    // multiplications by the identifier instead.
+                    let exponent_digit_at = |j: usize| j < len && chars[j].is_ascii_digit();
+                    let has_exponent = i < len
+                        && (chars[i] == 'e' || chars[i] == 'E')
+                        && (exponent_digit_at(i + 1)
+                            || (i + 1 < len
+                                && (chars[i + 1] == '+' || chars[i + 1] == '-')
+                                && exponent_digit_at(i + 2)));
+                    if has_exponent {
                         number.push(chars[i]);
                         i += 1;
                         col += 1;
@@ -277,9 +646,73 @@ impl Tokenizer {
                             i += 1;
                             col += 1;
                         }
+                    } else if starts_angle_literal(&chars, i) {
+                        // A degrees-minutes-seconds angle such as `30d15m0s`, stored as
+                        // decimal degrees. A bare `30d`, or `4d2` without a unit after the
+                        // digits, stays an implicit multiplication.
+                        let end = angle_literal_end(&chars, i);
+                        let literal: String = chars[start..end].iter().collect();
+                        number = decimal_degrees(&literal).map_err(|message| {
+                            TokenizerError::InvalidAngle {
+                                message,
+                                line: line_no + 1,
+                                col: start_col + 1,
+                            }
+                        })?;
+                        col += end - i;
+                        i = end;
+                    }
+
+                    tokens.push(Token::new(
+                        TokenType::Number { value: number },
+                        line_no + 1,
+                        start_col + 1,
+                        col + 1,
+                    ));
+                }
+                c if c.is_ascii_alphabetic() => {
+                    // Identifiers start with a letter and may continue with
+                    // letters, digits, or underscores.
+                    let start_col = col;
+                    let mut name = String::new();
+
+                    while i < len && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                        name.push(chars[i]);
+                        i += 1;
+                        col += 1;
                     }
 
-                    tokens.push(Token::number(&number, line_no + 1, start_col + 1));
+                    if let Some(keyword) = TokenType::from_keyword(&name) {
+                        tokens.push(Token::new(
+                            keyword,
+                            line_no + 1,
+                            start_col + 1,
+                            start_col + 1 + name.len(),
+                        ));
+                    } else {
+                        tokens.push(Token::identifier(&name, line_no + 1, start_col + 1));
+                    }
+                }
+                '$' if i + 1 < len && chars[i + 1].is_ascii_digit() => {
+                    // A history reference, `$` followed by the result's index.
+                    let start_col = col;
+                    let mut digits = String::new();
+                    i += 1;
+                    col += 1;
+                    while i < len && chars[i].is_ascii_digit() {
+                        digits.push(chars[i]);
+                        i += 1;
+                        col += 1;
+                    }
+                    // An index too large for `usize` can never be in range, so it
+                    // saturates and is reported by the executor.
+                    let index = digits.parse().unwrap_or(usize::MAX);
+                    tokens.push(Token::new(
+                        TokenType::HistoryRef { index },
+                        line_no + 1,
+                        start_col + 1,
+                        col + 1,
+                    ));
                 }
                 c if c.is_whitespace() => {
                     // Ignore whitespace characters (other than newlines).
@@ -301,6 +734,111 @@ impl Tokenizer {
         Ok(tokens)
     }
 }
+/// Returns `true` if `chars[d]` is the `d` of a degrees-minutes-seconds literal: a `d`
+/// followed by a number and then an `m` or `s` unit, as in the `d15m` of `30d15m`.
+///
+/// Without the unit, the `d` starts an identifier, so `4d2` multiplies `4` by `d2`.
+fn starts_angle_literal(chars: &[char], d: usize) -> bool {
+    let digits = chars
+        .iter()
+        .skip(d + 1)
+        .take_while(|c| c.is_ascii_digit() || **c == '.')
+        .count();
+    chars.get(d) == Some(&'d')
+        && chars.get(d + 1).is_some_and(char::is_ascii_digit)
+        && matches!(chars.get(d + 1 + digits), Some('m' | 's'))
+}
+
+/// Returns the index just past the degrees-minutes-seconds literal whose `d` is at
+/// `chars[d]`: the `d` and every following run of digits, dots, `m`s and `s`s.
+fn angle_literal_end(chars: &[char], d: usize) -> usize {
+    let mut end = d + 1;
+    while end < chars.len()
+        && (chars[end].is_ascii_digit() || matches!(chars[end], '.' | 'm' | 's'))
+    {
+        end += 1;
+    }
+    end
+}
+
+/// Converts a degrees-minutes-seconds literal such as `30d15m0s` to decimal degrees.
+///
+/// The degrees must be followed by minutes, seconds, or both, in that order, and the
+/// minutes and seconds must each be less than 60.
+///
+/// # Returns
+/// The decimal degrees as a number literal, e.g. `30.25`, or a message describing
+/// why the literal is invalid.
+fn decimal_degrees(literal: &str) -> Result<String, String> {
+    let invalid = |reason: &str| format!("Invalid angle '{}': {}", literal, reason);
+    let (degrees, mut rest) = literal
+        .split_once('d')
+        .ok_or_else(|| invalid("missing 'd'"))?;
+    let mut total: f64 = degrees.parse().map_err(|_| invalid("bad degrees"))?;
+    let mut last_unit = 'd';
+    while !rest.is_empty() {
+        let unit_at = rest
+            .find(['m', 's'])
+            .ok_or_else(|| invalid("expected 'm' or 's' after the number"))?;
+        let unit = rest[unit_at..].chars().next().unwrap_or('s');
+        let (name, divisor) = if unit == 'm' {
+            ("minutes", 60.0)
+        } else {
+            ("seconds", 3600.0)
+        };
+        if unit == last_unit || last_unit == 's' {
+            return Err(invalid(
+                "the parts must be degrees, minutes, seconds, in that order",
+            ));
+        }
+        let amount: f64 = rest[..unit_at]
+            .parse()
+            .map_err(|_| invalid(&format!("bad {}", name)))?;
+        if amount >= 60.0 {
+            return Err(invalid(&format!("{} must be less than 60", name)));
+        }
+        total += amount / divisor;
+        last_unit = unit;
+        rest = &rest[unit_at + 1..];
+    }
+    Ok(total.to_string())
+}
+
+/// Returns `true` if the text after a `%`, `rest`, starts with an operand, ignoring spaces
+/// and tabs, which makes the `%` the remainder operator rather than a postfix percent.
+///
+/// An operand is a number, a parenthesis, a `$N` reference, or an identifier other than
+/// `of`, optionally preceded by signs (`-`, `+`, `~`) written directly against it, so
+/// `10 % -3` and `10 %-3` are remainders. Anything else, such as another operator, a
+/// sign followed by a space, a comment, or the end of the line, leaves the `%` a
+/// percent, so `50% - 3` subtracts from `0.5`.
+fn starts_operand(rest: &[char]) -> bool {
+    let mut rest = rest
+        .iter()
+        .skip_while(|c| matches!(c, ' ' | '\t'))
+        .skip_while(|c| matches!(c, '-' | '+' | '~'));
+    match rest.next() {
+        Some(c) if c.is_ascii_digit() || matches!(c, '.' | '(' | '$') => true,
+        Some(c) if c.is_ascii_alphabetic() => {
+            let word: String = std::iter::once(*c)
+                .chain(
+                    rest.take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                        .copied(),
+                )
+                .collect();
+            word != "of"
+        }
+        _ => false,
+    }
+}
+
+/// Returns `true` if `chars[i]` is a comma separating groups of thousands: it follows a
+/// digit and is followed by exactly three digits.
+fn is_thousands_comma(chars: &[char], i: usize) -> bool {
+    let digit_at = |j: usize| chars.get(j).is_some_and(char::is_ascii_digit);
+    chars[i] == ',' && i > 0 && digit_at(i - 1) && (i + 1..=i + 3).all(digit_at) && !digit_at(i + 4)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -391,6 +929,130 @@ mod tests {
         );
     }
 
+    fn tokenize_thousands(input: &str) -> Vec<TokenType> {
+        Tokenizer::new(input.to_string())
+            .with_thousands_commas(true)
+            .tokenize()
+            .unwrap()
+            .into_iter()
+            .map(|t| t.get_type().clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_thousands_commas() {
+        assert_eq!(
+            tokenize_thousands("1,234,567"),
+            vec![
+                TokenType::Number {
+                    value: "1234567".to_string()
+                },
+                TokenType::EOF
+            ]
+        );
+        assert_eq!(
+            tokenize_thousands("12,345.5"),
+            vec![
+                TokenType::Number {
+                    value: "12345.5".to_string()
+                },
+                TokenType::EOF
+            ]
+        );
+        // Without the mode, the commas separate arguments.
+        assert_eq!(
+            Tokenizer::new("1,234".to_string())
+                .tokenize()
+                .unwrap()
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_commas_that_are_not_thousands_separators() {
+        let number = |value: &str| TokenType::Number {
+            value: value.to_string(),
+        };
+        // Not exactly three digits after the comma.
+        assert_eq!(
+            tokenize_thousands("1,2"),
+            vec![number("1"), TokenType::Comma, number("2"), TokenType::EOF]
+        );
+        assert_eq!(
+            tokenize_thousands("1,2345"),
+            vec![
+                number("1"),
+                TokenType::Comma,
+                number("2345"),
+                TokenType::EOF
+            ]
+        );
+        // A space after the comma keeps it an argument separator.
+        assert_eq!(
+            tokenize_thousands("1, 234"),
+            vec![number("1"), TokenType::Comma, number("234"), TokenType::EOF]
+        );
+        // Commas in the fractional part are never separators.
+        assert_eq!(
+            tokenize_thousands("1.5,234"),
+            vec![
+                number("1.5"),
+                TokenType::Comma,
+                number("234"),
+                TokenType::EOF
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hash_comment_delimiter() {
+        let tokens = Tokenizer::new("1+2 # sum".to_string())
+            .with_comment_delimiter("#")
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::number("1", 1, 1),
+                Token::plus(1, 2),
+                Token::number("2", 1, 3),
+                Token::comment(" sum", 1, 5),
+                Token::eof(1, 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_double_slash_comment_delimiter() {
+        // A single `/` is still division; only `//` starts a comment.
+        let tokens = Tokenizer::new("6/2 // half".to_string())
+            .with_comment_delimiter("//")
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::number("6", 1, 1),
+                Token::div(1, 2),
+                Token::number("2", 1, 3),
+                Token::comment(" half", 1, 5),
+                Token::eof(1, 12),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_semicolon_is_not_a_comment_with_other_delimiter() {
+        let result = Tokenizer::new("1;2".to_string())
+            .with_comment_delimiter("#")
+            .tokenize();
+        assert!(matches!(
+            result,
+            Err(TokenizerError::UnexpectedCharacter { found: ';', .. })
+        ));
+    }
+
     #[test]
     fn test_newline_and_whitespace() {
         assert_tokenize_ok(
@@ -453,6 +1115,306 @@ mod tests {
         assert_tokenize_ok("1e-5", vec![Token::number("1e-5", 1, 1), Token::eof(1, 5)]);
     }
 
+    #[test]
+    fn test_function_call_tokens() {
+        assert_tokenize_ok(
+            "stddev(1, x_2)",
+            vec![
+                Token::identifier("stddev", 1, 1),
+                Token::paran_open(1, 7),
+                Token::number("1", 1, 8),
+                Token::comma(1, 9),
+                Token::identifier("x_2", 1, 11),
+                Token::paran_close(1, 14),
+                Token::eof(1, 15),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_let_and_assignment_tokens() {
+        assert_tokenize_ok(
+            "let x = 1",
+            vec![
+                Token::new(TokenType::Let, 1, 1, 4),
+                Token::identifier("x", 1, 5),
+                Token::new(TokenType::Assign, 1, 7, 7),
+                Token::number("1", 1, 9),
+                Token::eof(1, 10),
+            ],
+        );
+        assert_tokenize_ok(
+            "x+=2",
+            vec![
+                Token::identifier("x", 1, 1),
+                Token::new(TokenType::PlusAssign, 1, 2, 3),
+                Token::number("2", 1, 4),
+                Token::eof(1, 5),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_number_followed_by_e_identifier() {
+        assert_tokenize_ok(
+            "2e",
+            vec![
+                Token::number("2", 1, 1),
+                Token::identifier("e", 1, 2),
+                Token::eof(1, 3),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_comparison_tokens() {
+        assert_tokenize_ok(
+            "1<2>=3==4!=5",
+            vec![
+                Token::number("1", 1, 1),
+                Token::new(TokenType::Less, 1, 2, 2),
+                Token::number("2", 1, 3),
+                Token::new(TokenType::GreaterEqual, 1, 4, 5),
+                Token::number("3", 1, 6),
+                Token::new(TokenType::Equal, 1, 7, 8),
+                Token::number("4", 1, 9),
+                Token::new(TokenType::NotEqual, 1, 10, 11),
+                Token::number("5", 1, 12),
+                Token::eof(1, 13),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_degrees_minutes_seconds_literal() {
+        let number = |value: &str, start, end| {
+            Token::new(
+                TokenType::Number {
+                    value: value.to_string(),
+                },
+                1,
+                start,
+                end,
+            )
+        };
+        assert_tokenize_ok("30d30m", vec![number("30.5", 1, 7), Token::eof(1, 7)]);
+        assert_tokenize_ok(
+            "1d30m36s+2",
+            vec![
+                number("1.51", 1, 9),
+                Token::new(TokenType::Plus, 1, 9, 9),
+                Token::number("2", 1, 10),
+                Token::eof(1, 11),
+            ],
+        );
+        // Without minutes or seconds, `d` starts an identifier.
+        assert_tokenize_ok(
+            "2d",
+            vec![
+                Token::number("2", 1, 1),
+                Token::identifier("d", 1, 2),
+                Token::eof(1, 3),
+            ],
+        );
+        assert_tokenize_ok(
+            "4d2",
+            vec![
+                Token::number("4", 1, 1),
+                Token::identifier("d2", 1, 2),
+                Token::eof(1, 4),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_invalid_degrees_minutes_seconds() {
+        let invalid = |message: &str, col| TokenizerError::InvalidAngle {
+            message: message.to_string(),
+            line: 1,
+            col,
+        };
+        assert_tokenize_err(
+            "1 + 30d75m",
+            invalid("Invalid angle '30d75m': minutes must be less than 60", 5),
+        );
+        assert_tokenize_err(
+            "30d10m60s",
+            invalid("Invalid angle '30d10m60s': seconds must be less than 60", 1),
+        );
+        assert_tokenize_err(
+            "30d0s15m",
+            invalid(
+                "Invalid angle '30d0s15m': the parts must be degrees, minutes, seconds, in that order",
+                1,
+            ),
+        );
+        assert_tokenize_err(
+            "30d5m3",
+            invalid(
+                "Invalid angle '30d5m3': expected 'm' or 's' after the number",
+                1,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_minus_is_an_operator_outside_exponents() {
+        assert_tokenize_ok(
+            "-3^2",
+            vec![
+                Token::minus(1, 1),
+                Token::number("3", 1, 2),
+                Token::new(TokenType::Pow, 1, 3, 3),
+                Token::number("2", 1, 4),
+                Token::eof(1, 5),
+            ],
+        );
+        assert_tokenize_ok(
+            "1e-5-2",
+            vec![
+                Token::number("1e-5", 1, 1),
+                Token::minus(1, 5),
+                Token::number("2", 1, 6),
+                Token::eof(1, 7),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_approx_equal_token() {
+        assert_tokenize_ok(
+            "1~=~2",
+            vec![
+                Token::number("1", 1, 1),
+                Token::new(TokenType::ApproxEqual, 1, 2, 3),
+                Token::new(TokenType::BitNot, 1, 4, 4),
+                Token::number("2", 1, 5),
+                Token::eof(1, 6),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_bang_token() {
+        assert_tokenize_ok(
+            "5!!=1",
+            vec![
+                Token::number("5", 1, 1),
+                Token::new(TokenType::Bang, 1, 2, 2),
+                Token::new(TokenType::NotEqual, 1, 3, 4),
+                Token::number("1", 1, 5),
+                Token::eof(1, 6),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_percent_tokens() {
+        assert_tokenize_ok(
+            "50% of 7 % 3%",
+            vec![
+                Token::number("50", 1, 1),
+                Token::new(TokenType::Percent, 1, 3, 3),
+                Token::new(TokenType::Of, 1, 5, 7),
+                Token::number("7", 1, 8),
+                Token::new(TokenType::Modulo, 1, 10, 10),
+                Token::number("3", 1, 12),
+                Token::new(TokenType::Percent, 1, 13, 13),
+                Token::eof(1, 14),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_history_ref_token() {
+        assert_tokenize_ok(
+            "$12 + 1",
+            vec![
+                Token::new(TokenType::HistoryRef { index: 12 }, 1, 1, 4),
+                Token::new(TokenType::Plus, 1, 5, 5),
+                Token::number("1", 1, 7),
+                Token::eof(1, 8),
+            ],
+        );
+        assert_tokenize_err(
+            "$x",
+            TokenizerError::UnexpectedCharacter {
+                found: '$',
+                line: 1,
+                col: 1,
+            },
+        );
+    }
+
+    #[test]
+    fn test_bitwise_tokens() {
+        assert_tokenize_ok(
+            "~1&2|3<<4>>5<6",
+            vec![
+                Token::new(TokenType::BitNot, 1, 1, 1),
+                Token::number("1", 1, 2),
+                Token::new(TokenType::BitAnd, 1, 3, 3),
+                Token::number("2", 1, 4),
+                Token::new(TokenType::BitOr, 1, 5, 5),
+                Token::number("3", 1, 6),
+                Token::new(TokenType::ShiftLeft, 1, 7, 8),
+                Token::number("4", 1, 9),
+                Token::new(TokenType::ShiftRight, 1, 10, 11),
+                Token::number("5", 1, 12),
+                Token::new(TokenType::Less, 1, 13, 13),
+                Token::number("6", 1, 14),
+                Token::eof(1, 15),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_digit_separators() {
+        assert_tokenize_ok(
+            "1_000 + 1",
+            vec![
+                Token::new(
+                    TokenType::Number {
+                        value: "1000".to_string(),
+                    },
+                    1,
+                    1,
+                    6,
+                ),
+                Token::plus(1, 7),
+                Token::number("1", 1, 9),
+                Token::eof(1, 10),
+            ],
+        );
+        assert_tokenize_ok(
+            "3.141_592",
+            vec![
+                Token::new(
+                    TokenType::Number {
+                        value: "3.141592".to_string(),
+                    },
+                    1,
+                    1,
+                    10,
+                ),
+                Token::eof(1, 10),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_invalid_digit_separators() {
+        let underscore_at = |col| TokenizerError::UnexpectedCharacter {
+            found: '_',
+            line: 1,
+            col,
+        };
+        assert_tokenize_err("1__0", underscore_at(2));
+        assert_tokenize_err("10_", underscore_at(3));
+        assert_tokenize_err("_5", underscore_at(1));
+        assert_tokenize_err("1_.5", underscore_at(2));
+    }
+
     #[test]
     fn test_whitespace_only_input() {
         assert_tokenize_ok("   ", vec![Token::eof(1, 4)]);
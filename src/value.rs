@@ -0,0 +1,199 @@
+//! This module defines `Value`, the runtime representation of numbers in `arith`.
+//!
+//! A value is either an integer or a floating-point number. Integer arithmetic stays
+//! integral as long as the result is exact and fits in an `i64`; otherwise it is
+//! promoted to a float. Floats never turn back into integers on their own.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// The largest magnitude below which every integral `f64` is exactly representable,
+/// `2^53`. Integral literals beyond it are kept as floats.
+const MAX_EXACT_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+/// A number produced during evaluation.
+///
+/// Equality is structural, so `Int(2)` and `Float(2.0)` are not equal; use
+/// `Value::compare` for numeric comparisons.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    /// An integer, e.g. the literal `42` or the result of `6 / 3`.
+    Int(i64),
+    /// A floating-point number, e.g. the literal `2.5` or the result of `10 / 4`.
+    Float(f64),
+}
+
+/// The names a type annotation such as `let y: Int = 3` may use, one per `ValueType`.
+pub const TYPE_NAMES: &[&str] = &["Int", "Float", "Number"];
+
+/// The type of a `Value`, as written in a type annotation such as `let y: Int = 3`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueType {
+    /// The type of `Value::Int`.
+    Int,
+    /// The type of `Value::Float`.
+    Float,
+    /// Either type. Documents that a variable holds a number without constraining it.
+    Number,
+}
+
+impl ValueType {
+    /// Returns the type named `name` in a type annotation, or `None` if there is no such type.
+    pub fn from_name(name: &str) -> Option<ValueType> {
+        match name {
+            "Int" => Some(ValueType::Int),
+            "Float" => Some(ValueType::Float),
+            "Number" => Some(ValueType::Number),
+            _ => None,
+        }
+    }
+}
+
+/// Implements the `Display` trait for `ValueType`, producing its annotation name, e.g. `Int`.
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueType::Int => write!(f, "Int"),
+            ValueType::Float => write!(f, "Float"),
+            ValueType::Number => write!(f, "Number"),
+        }
+    }
+}
+
+impl Value {
+    /// Returns the value of a number literal: an `Int` if it has no fractional part
+    /// (so both `2` and `2.0` are integers), otherwise a `Float`.
+    pub fn from_literal(n: f64) -> Value {
+        if n.fract() == 0.0 && n.abs() <= MAX_EXACT_INTEGER {
+            Value::Int(n as i64)
+        } else {
+            Value::Float(n)
+        }
+    }
+
+    /// Returns the value as an `f64`.
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Value::Int(n) => n as f64,
+            Value::Float(x) => x,
+        }
+    }
+
+    /// Returns the type of this value.
+    pub fn value_type(self) -> ValueType {
+        match self {
+            Value::Int(_) => ValueType::Int,
+            Value::Float(_) => ValueType::Float,
+        }
+    }
+
+    /// Divides `self` by `rhs`, or returns `None` if `rhs` is zero.
+    ///
+    /// Two integers divide to an integer when the division is exact (`6 / 3` is `2`)
+    /// and to a float otherwise (`10 / 3` is `3.333…`).
+    pub fn checked_div(self, rhs: Value) -> Option<Value> {
+        if rhs.as_f64() == 0.0 {
+            return None;
+        }
+        if let (Value::Int(a), Value::Int(b)) = (self, rhs)
+            && a.checked_rem(b) == Some(0)
+        {
+            return Some(Value::Int(a / b));
+        }
+        Some(Value::Float(self.as_f64() / rhs.as_f64()))
+    }
+
+    /// Returns the remainder of dividing `self` by `rhs`, with the sign of `self`, or
+    /// `None` if `rhs` is zero. Two integers give an integer.
+    pub fn checked_rem(self, rhs: Value) -> Option<Value> {
+        if rhs.as_f64() == 0.0 {
+            return None;
+        }
+        Some(self.arithmetic(rhs, i64::checked_rem, |a, b| a % b))
+    }
+
+    /// Raises `self` to the power `rhs`. An integer raised to a non-negative integer
+    /// power stays an integer unless it overflows.
+    pub fn pow(self, rhs: Value) -> Value {
+        if let (Value::Int(base), Value::Int(exponent)) = (self, rhs)
+            && let Ok(exponent) = u32::try_from(exponent)
+            && let Some(n) = base.checked_pow(exponent)
+        {
+            return Value::Int(n);
+        }
+        Value::Float(self.as_f64().powf(rhs.as_f64()))
+    }
+
+    /// Compares two values numerically, regardless of their types. Returns `None`
+    /// if either is NaN.
+    pub fn compare(self, rhs: Value) -> Option<Ordering> {
+        match (self, rhs) {
+            (Value::Int(a), Value::Int(b)) => Some(a.cmp(&b)),
+            _ => self.as_f64().partial_cmp(&rhs.as_f64()),
+        }
+    }
+
+    /// Applies an integer operation, falling back to the float operation if either
+    /// operand is a float or the integer result overflows.
+    fn arithmetic(
+        self,
+        rhs: Value,
+        int_op: fn(i64, i64) -> Option<i64>,
+        float_op: fn(f64, f64) -> f64,
+    ) -> Value {
+        if let (Value::Int(a), Value::Int(b)) = (self, rhs)
+            && let Some(n) = int_op(a, b)
+        {
+            return Value::Int(n);
+        }
+        Value::Float(float_op(self.as_f64(), rhs.as_f64()))
+    }
+}
+
+impl Add for Value {
+    type Output = Value;
+
+    fn add(self, rhs: Value) -> Value {
+        self.arithmetic(rhs, i64::checked_add, |a, b| a + b)
+    }
+}
+
+impl Sub for Value {
+    type Output = Value;
+
+    fn sub(self, rhs: Value) -> Value {
+        self.arithmetic(rhs, i64::checked_sub, |a, b| a - b)
+    }
+}
+
+impl Mul for Value {
+    type Output = Value;
+
+    fn mul(self, rhs: Value) -> Value {
+        self.arithmetic(rhs, i64::checked_mul, |a, b| a * b)
+    }
+}
+
+impl Neg for Value {
+    type Output = Value;
+
+    fn neg(self) -> Value {
+        match self {
+            Value::Int(n) => n
+                .checked_neg()
+                .map_or(Value::Float(-(n as f64)), Value::Int),
+            Value::Float(x) => Value::Float(-x),
+        }
+    }
+}
+
+/// Implements the `Display` trait for `Value`, printing the number itself.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Float(x) => write!(f, "{}", x),
+        }
+    }
+}
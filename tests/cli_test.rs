@@ -0,0 +1,511 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Runs the `arith` binary with the given arguments and returns its stdout.
+fn run_arith(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_arith"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to run arith binary");
+    String::from_utf8(output.stdout).expect("stdout was not valid UTF-8")
+}
+
+/// Writes `content` to a uniquely named file in the system temp directory.
+fn write_temp_file<C: AsRef<[u8]>>(name: &str, content: C) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("arith_{}_{}", std::process::id(), name));
+    fs::write(&path, content).expect("failed to write temp file");
+    path
+}
+
+#[test]
+fn test_line_numbers_follow_source_lines() {
+    let stdout = run_arith(&["-f", "test_files/comments.arith", "--line-numbers"]);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            "--- Results from comments.arith ---",
+            "2: 1 + 2 [1]: 3",
+            "4: 3 * 4 [2]: 12",
+            "",
+        ]
+    );
+}
+
+#[test]
+fn test_line_numbers_off_by_default() {
+    let stdout = run_arith(&["-f", "test_files/comments.arith"]);
+    assert!(stdout.contains("1 + 2 [1]: 3"));
+    assert!(!stdout.contains("2: 1 + 2"));
+}
+
+#[test]
+fn test_shared_state_across_files() {
+    let a = write_temp_file("shared_a.arith", "let width = 3\n");
+    let b = write_temp_file("shared_b.arith", "width * 4\n");
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+    let shared = run_arith(&["--shared-state", "-f", a, "-f", b]);
+    assert!(shared.contains("width * 4 [1]: 12"), "got: {}", shared);
+
+    let isolated = run_arith(&["-f", a, "-f", b]);
+    assert!(!isolated.contains("width * 4 [1]"), "got: {}", isolated);
+}
+
+#[test]
+fn test_explain_precedence() {
+    let stdout = run_arith(&["--explain-precedence", "1 + 2 * 3 / 4"]);
+    assert_eq!(stdout, "(1 + ((2 * 3) / 4))\n");
+}
+
+#[test]
+fn test_lint_double_negation() {
+    let stdout = run_arith(&["--lint=--5"]);
+    assert_eq!(
+        stdout,
+        "warning: redundant double negation, write `5` instead at line 1, col 1\n"
+    );
+    assert_eq!(run_arith(&["--lint", "-5"]), "");
+}
+
+#[test]
+fn test_latin1_input_encoding() {
+    // "; café" with `é` encoded as the single Latin-1 byte 0xE9.
+    let path = write_temp_file("latin1.arith", b"2 * 21 ; caf\xe9\n");
+    let path = path.to_str().unwrap();
+
+    let stdout = run_arith(&["--input-encoding", "latin1", "-f", path]);
+    assert!(stdout.contains("2 * 21 [1]: 42"), "got: {}", stdout);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_arith"))
+        .args(["-f", path])
+        .output()
+        .expect("failed to run arith binary");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("not valid UTF-8"), "got: {}", stderr);
+}
+
+#[test]
+fn test_eval_flag() {
+    assert_eq!(run_arith(&["-e", "2 + 2"]), "4\n");
+    assert_eq!(run_arith(&["-e", "1 / 4"]), "0.25\n");
+    assert_eq!(
+        run_arith(&["-e", "let x = 2", "--eval", "x * 3", "-e", "x"]),
+        "2\n6\n2\n"
+    );
+}
+
+#[test]
+fn test_eval_flag_error_exit_code() {
+    let output = Command::new(env!("CARGO_BIN_EXE_arith"))
+        .args(["-e", "1 +", "-e", "5"])
+        .output()
+        .expect("failed to run arith binary");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "5\n");
+}
+
+#[test]
+fn test_stdin_mode() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_arith"))
+        .arg("--stdin")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run arith binary");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"1 + 2\nlet x = 4 ; comment\nx * x\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n4\n16\n");
+}
+
+/// Runs the `arith` binary and returns the lines of its stderr, without log messages.
+fn run_arith_stderr(args: &[&str]) -> Vec<String> {
+    let output = Command::new(env!("CARGO_BIN_EXE_arith"))
+        .args(args)
+        .env("RUST_LOG", "off")
+        .output()
+        .expect("failed to run arith binary");
+    String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn test_dump_symbols_on_exit() {
+    let path = write_temp_file("dump.arith", "let b = 2\nlet a = 1\nb += a\n");
+    let path = path.to_str().unwrap();
+    let file_name = std::path::Path::new(path)
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap();
+
+    let stderr = run_arith_stderr(&["--dump-symbols-on-exit", "-f", path]);
+    assert_eq!(
+        stderr,
+        vec![
+            format!("--- Symbols after {} ---", file_name),
+            "a = 1".to_string(),
+            "b = 3".to_string(),
+            "e = 2.718281828459045".to_string(),
+            "pi = 3.141592653589793".to_string(),
+            "tau = 6.283185307179586".to_string(),
+            "total = 0".to_string(),
+        ]
+    );
+
+    let shared = run_arith_stderr(&["--dump-symbols-on-exit", "--shared-state", "-f", path]);
+    assert_eq!(shared[0], "--- Symbols ---");
+    assert_eq!(shared.len(), 7);
+}
+
+/// Runs the `arith` binary and returns its exit code.
+fn arith_exit_code(args: &[&str]) -> Option<i32> {
+    Command::new(env!("CARGO_BIN_EXE_arith"))
+        .args(args)
+        .output()
+        .expect("failed to run arith binary")
+        .status
+        .code()
+}
+
+#[test]
+fn test_file_mode_exit_codes() {
+    let good = write_temp_file("exit_good.arith", "1 + 2\n");
+    let bad = write_temp_file("exit_bad.arith", "1 + 2\n1 + * 2\n3\n");
+    let missing = std::env::temp_dir().join("arith_definitely_missing.arith");
+
+    assert_eq!(arith_exit_code(&["-f", good.to_str().unwrap()]), Some(0));
+    assert_eq!(arith_exit_code(&["-f", bad.to_str().unwrap()]), Some(1));
+    assert_eq!(arith_exit_code(&["-f", missing.to_str().unwrap()]), Some(2));
+
+    // The lines around the failing one are still evaluated and printed.
+    let stdout = run_arith(&["-f", bad.to_str().unwrap()]);
+    assert!(stdout.contains("1 + 2 [1]: 3"), "got: {}", stdout);
+    assert!(stdout.contains("3 [3]: 3"), "got: {}", stdout);
+}
+
+#[test]
+fn test_value_range_flags() {
+    let args = ["--min-value", "0", "--max-value", "100"];
+    let in_range = [&args[..], &["-e", "40 + 2"]].concat();
+    assert_eq!(run_arith(&in_range), "42\n");
+    let out_of_range = [&args[..], &["-e", "200"]].concat();
+    assert_eq!(arith_exit_code(&out_of_range), Some(1));
+    let clamped = [&args[..], &["--clamp", "-e", "200", "--eval=-5"]].concat();
+    assert_eq!(run_arith(&clamped), "100\n0\n");
+}
+
+#[test]
+fn test_angle_flag() {
+    assert_eq!(
+        run_arith(&["--angle", "deg", "-e", "sin(90)", "-e", "asin(1)"]),
+        "1\n90\n"
+    );
+    assert_eq!(
+        run_arith(&["--angle-mode", "radians", "-e", "sin(pi / 2)"]),
+        "1\n"
+    );
+}
+
+#[test]
+fn test_strict_flag() {
+    assert_eq!(run_arith(&["-e", "let x = 1", "-e", "let x = 2"]), "1\n2\n");
+    assert_eq!(
+        arith_exit_code(&["--strict", "-e", "let x = 1", "-e", "let x = 2"]),
+        Some(1)
+    );
+    assert_eq!(
+        run_arith(&["--strict", "-e", "let x = 1", "-e", "x = 2"]),
+        "1\n2\n"
+    );
+}
+
+#[test]
+fn test_warn_truncation_flag() {
+    let warnings = |args: &[&str]| -> Vec<String> {
+        let output = Command::new(env!("CARGO_BIN_EXE_arith"))
+            .args(args)
+            .env("RUST_LOG", "warn")
+            .output()
+            .expect("failed to run arith binary");
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "3.5\n3\n",
+            "results are unchanged"
+        );
+        String::from_utf8_lossy(&output.stderr)
+            .lines()
+            .map(str::to_string)
+            .collect()
+    };
+    let stderr = warnings(&["--warn-truncation", "-e", "7 / 2", "-e", "6 / 2"]);
+    assert_eq!(stderr.len(), 1, "{:?}", stderr);
+    assert!(
+        stderr[0].ends_with("7 / 2 is 3.5; integer division would truncate it to 3"),
+        "{:?}",
+        stderr
+    );
+    assert!(warnings(&["-e", "7 / 2", "-e", "6 / 2"]).is_empty());
+}
+
+#[test]
+fn test_format_flag() {
+    assert_eq!(run_arith(&["-e", "6.022e23"]), "6.022e23\n");
+    assert_eq!(
+        run_arith(&["--format", "engineering", "-e", "6.022e23"]),
+        "602.2e21\n"
+    );
+    assert_eq!(
+        run_arith(&["--format", "scientific", "-e", "1500"]),
+        "1.5e3\n"
+    );
+    assert_eq!(
+        run_arith(&["--format", "fixed", "-e", "1e16"]),
+        "10000000000000000\n"
+    );
+}
+
+#[test]
+fn test_trailing_result() {
+    let path = write_temp_file(
+        "trailing.arith",
+        "let width = 3\nwidth * 2\nwidth += 1\nwidth * 10\n",
+    );
+    let stdout = run_arith(&["--trailing-result", "-f", path.to_str().unwrap()]);
+    assert_eq!(stdout, "40\n");
+}
+
+#[test]
+fn test_quiet_prints_only_values() {
+    let path = write_temp_file("quiet.arith", "let x = 4\nx * 2\n1 / 0\nx + 0.5\n");
+    let path = path.to_str().unwrap();
+
+    let normal = run_arith(&["-f", path]);
+    assert!(normal.starts_with("--- Results from "));
+    assert!(normal.contains("let x = 4 [1]: 4\n"));
+    assert!(normal.contains("x * 2 [2]: 8\n"));
+
+    assert_eq!(run_arith(&["--quiet", "-f", path]), "4\n8\n4.5\n");
+    assert_eq!(
+        run_arith(&["-q", "--line-numbers", "-f", path]),
+        "4\n8\n4.5\n"
+    );
+    assert!(run_arith_stderr(&["-q", "-f", path])[0].starts_with("Error in "));
+}
+
+#[test]
+fn test_trailing_result_with_shared_state() {
+    let a = write_temp_file("trailing_a.arith", "let n = 2\nn * 3\n");
+    let b = write_temp_file("trailing_b.arith", "n + 1\nundefined_name\n");
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+    // The failing last line is skipped in favor of the last successful result.
+    let shared = run_arith(&["--trailing-result", "--shared-state", "-f", a, "-f", b]);
+    assert_eq!(shared, "3\n");
+
+    let isolated = run_arith(&["--trailing-result", "-f", a]);
+    assert_eq!(isolated, "6\n");
+}
+
+#[test]
+fn test_comment_char_flag() {
+    let file = write_temp_file("comment_char.txt", "# sums\n1 + 2 # three\n");
+    let output = run_arith(&[
+        "--comment-char",
+        "#",
+        "--trailing-result",
+        "-f",
+        file.to_str().unwrap(),
+    ]);
+    assert_eq!(output, "3\n");
+    let output = run_arith(&["--comment-char", "//", "-e", "6 / 2 // half of six"]);
+    assert_eq!(output, "3\n");
+}
+
+#[test]
+fn test_line_and_range_selection() {
+    let path = write_temp_file("selection.arith", "1 + 1\n2 + 2\n3 + \\\n  3\n4 + 4\n");
+    let path = path.to_str().unwrap();
+
+    assert_eq!(run_arith(&["-q", "--line", "2", "-f", path]), "4\n");
+    // An expression continued with `\` is selected by its first line.
+    assert_eq!(run_arith(&["-q", "--range", "2:3", "-f", path]), "4\n6\n");
+    assert_eq!(run_arith(&["-q", "--range", "4:4", "-f", path]), "");
+    assert_eq!(run_arith(&["-q", "--range", "3:99", "-f", path]), "6\n8\n");
+
+    let stderr = run_arith_stderr(&["--line", "9", "-f", path]);
+    assert!(stderr[0].ends_with("line 9 is out of range, the file has only 5 lines"));
+    assert_eq!(arith_exit_code(&["--line", "9", "-f", path]), Some(1));
+}
+
+#[test]
+fn test_treat_comma_as_thousands() {
+    assert_eq!(
+        run_arith(&["--treat-comma-as-thousands", "-e", "1,234,567 + 1"]),
+        "1234568\n"
+    );
+    assert_eq!(
+        run_arith(&["--treat-comma-as-thousands", "-e", "lcm(4, 1,000)"]),
+        "1000\n"
+    );
+}
+
+#[test]
+fn test_print_builtin() {
+    // Printed values go to stderr, so stdout holds only the results.
+    assert_eq!(run_arith(&["-e", "print(5) + 1"]), "6\n");
+    assert_eq!(run_arith_stderr(&["-e", "print(5) + 1"]), ["5"]);
+    assert_eq!(
+        run_arith_stderr(&["-e", "print(2) * print(0.5)"]),
+        ["2", "0.5"]
+    );
+
+    let path = write_temp_file("print.arith", "1 + 1\nlet x = print(3)\nx * 2\n");
+    let path = path.to_str().unwrap();
+    assert_eq!(run_arith(&["-q", "-f", path]), "2\n3\n6\n");
+    assert_eq!(run_arith_stderr(&["-q", "-f", path]), ["3"]);
+    assert_eq!(
+        run_arith(&["--format", "tsv", "-f", path]),
+        "1\t1 + 1\t2\n2\tlet x = print(3)\t3\n3\tx * 2\t6\n"
+    );
+}
+
+#[test]
+fn test_compare_files_reports_only_differing_lines() {
+    let a = write_temp_file("compare_a.arith", "let r = 2\nr * 3\n1 + 1\n10 / 4\n");
+    let b = write_temp_file("compare_b.arith", "let r = 2\nr * 3\n1 + 2\n10 / 4\n");
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+    assert_eq!(run_arith(&["--compare-files", a, b]), "line 3: 2 | 3\n");
+    assert_eq!(arith_exit_code(&["--compare-files", a, b]), Some(1));
+    assert_eq!(run_arith(&["--compare-files", a, a]), "");
+    assert_eq!(arith_exit_code(&["--compare-files", a, a]), Some(0));
+}
+
+#[test]
+fn test_strip_comments() {
+    let path = write_temp_file(
+        "strip.arith",
+        "; header comment\nlet r = 2   ; the radius\n\n  pi * r^2\n    ; indented note\nr * \\\n3 ; continued\n",
+    );
+    let path = path.to_str().unwrap();
+    assert_eq!(
+        run_arith(&["strip-comments", path]),
+        "let r = 2\n\n  pi * r^2\nr * \\\n3\n"
+    );
+
+    let path = write_temp_file("strip_hash.arith", "# note\n1 + 1 # two\n2 ; kept\n");
+    assert_eq!(
+        run_arith(&[
+            "strip-comments",
+            "--comment-char",
+            "#",
+            path.to_str().unwrap()
+        ]),
+        "1 + 1\n2 ; kept\n"
+    );
+}
+
+#[test]
+fn test_multi_statement_flag() {
+    assert_eq!(run_arith(&["-e", "1 + 1; 2 + 2"]), "2\n");
+    assert_eq!(
+        run_arith(&["--multi-statement", "-e", "1 + 1; 2 + 2"]),
+        "2\n4\n"
+    );
+
+    let path = write_temp_file("multi.arith", "let r = 2; r * 3 # six\n# note\nr; r + 1\n");
+    assert_eq!(
+        run_arith(&[
+            "--multi-statement",
+            "--comment-char",
+            "#",
+            "-q",
+            "-f",
+            path.to_str().unwrap()
+        ]),
+        "2\n6\n2\n3\n"
+    );
+}
+
+#[test]
+fn test_self_test_subcommand_passes() {
+    let stdout = run_arith(&["self-test"]);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines[0].starts_with("PASS 1 + 2 * 3 = 7"));
+    assert!(!stdout.contains("FAIL"), "{}", stdout);
+    assert!(lines.last().unwrap().ends_with(", 0 failed"));
+    assert_eq!(arith_exit_code(&["self-test"]), Some(0));
+}
+
+#[test]
+fn test_tsv_format() {
+    let path = write_temp_file(
+        "tsv.arith",
+        "let x = 2\nx * 3 ; six\n1 +\t2\n\n4 / (x - 2)\n7 / 2\n1 +\n",
+    );
+    let stdout = run_arith(&["--format", "tsv", "-f", path.to_str().unwrap()]);
+    let rows: Vec<Vec<&str>> = stdout
+        .lines()
+        .map(|row| row.split('\t').collect())
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            vec!["1", "let x = 2", "2"],
+            vec!["2", "x * 3", "6"],
+            vec!["3", "1 +\\t2", "3"],
+            vec!["5", "4 / (x - 2)", "ERROR", "division by zero"],
+            vec!["6", "7 / 2", "3.5"],
+            vec![
+                "7",
+                "1 +",
+                "ERROR",
+                "Unexpected end of input at line 1, col 4"
+            ],
+        ]
+    );
+    assert_eq!(
+        arith_exit_code(&["--format", "tsv", "-f", path.to_str().unwrap()]),
+        Some(1)
+    );
+}
+
+#[test]
+fn test_max_depth() {
+    let nested = format!("{}1{}", "(".repeat(10_000), ")".repeat(10_000));
+    let stderr = run_arith_stderr(&["-e", &nested]);
+    assert!(stderr[0].contains("Expression is nested too deeply at line 1, col 257"));
+    assert_eq!(arith_exit_code(&["-e", &nested]), Some(1));
+
+    let nested = format!("{}1{}", "(".repeat(300), ")".repeat(300));
+    assert_eq!(run_arith(&["--max-depth", "400", "-e", &nested]), "1\n");
+}
+
+#[test]
+fn test_ast_json_flag() {
+    assert_eq!(
+        run_arith(&["--ast-json", "1 + 2 * 3"]),
+        concat!(
+            r#"{"type":"Expression","expr":{"type":"BinaryOp","op":"+","#,
+            r#""left":{"type":"Number","value":1},"#,
+            r#""right":{"type":"BinaryOp","op":"*","#,
+            r#""left":{"type":"Number","value":2},"right":{"type":"Number","value":3}}}}"#,
+            "\n"
+        )
+    );
+    assert_eq!(arith_exit_code(&["--ast-json", "1 +"]), Some(1));
+}
@@ -1,9 +1,19 @@
+use arith::ast::{Expr, Statement};
+use arith::builtins::AngleMode;
 use arith::errors::ParserError;
 use arith::executor::EvalError;
-use arith::executor::{CompileError, ExecError, evaluate_lines};
+use arith::executor::{
+    BytecodeCompiler, CompileError, EvalOptions, ExecError, Instr, SimpleExecutor, UNDO_LIMIT,
+    ValueRange, continues_line, evaluate_lines, evaluate_lines_streaming, evaluate_numbered_lines,
+    evaluate_stream,
+};
+use arith::parser::parse_str;
+use arith::tokenizer::TokenType;
+use arith::value::{Value, ValueType};
+use std::collections::BTreeSet;
 
 fn assert_eval_ok(input: &str, expected: f64) {
-    let results = evaluate_lines(input);
+    let results = evaluate_lines(input, &mut SimpleExecutor::new());
     assert_eq!(results.len(), 1, "Expected one result for input: {}", input);
     match &results[0] {
         Ok((val, _expr_str)) => assert_eq!(*val, expected, "Input: {}", input),
@@ -11,8 +21,24 @@ fn assert_eval_ok(input: &str, expected: f64) {
     }
 }
 
+/// Like `assert_eval_err`, but for input whose earlier lines define variables: only the
+/// last result must be an error.
+fn assert_last_eval_err(input: &str, expected_err_type: &str) {
+    let results = evaluate_lines(input, &mut SimpleExecutor::new());
+    match results.last() {
+        Some(Err(e)) => assert!(
+            format!("{:?}", e).contains(expected_err_type),
+            "Input: '{}', Expected error type containing '{}', but got: '{:?}'",
+            input,
+            expected_err_type,
+            e
+        ),
+        other => panic!("Expected error for input '{}', but got: {:?}", input, other),
+    }
+}
+
 fn assert_eval_err(input: &str, expected_err_type: &str) {
-    let results = evaluate_lines(input);
+    let results = evaluate_lines(input, &mut SimpleExecutor::new());
     assert_eq!(results.len(), 1, "Expected one result for input: {}", input);
     match &results[0] {
         Ok((val, _expr_str)) => panic!("Expected error for input '{}', but got: {}", input, val),
@@ -64,6 +90,20 @@ fn test_unary_operators() {
     assert_eval_ok("--5", 5.0);
 }
 
+#[test]
+fn test_negative_numbers_with_power_and_implicit_multiplication() {
+    assert_eval_ok("-3 ^ 2", -9.0);
+    assert_eval_ok("(-3) ^ 2", 9.0);
+    assert_eval_ok("-3 * 2", -6.0);
+    assert_eval_ok("2 ^ -1", 0.5);
+    assert_eval_ok("-2 ^ -2", -0.25);
+    assert_eval_ok("2(-3)", -6.0);
+    assert_eval_ok("(-2)(-3)", 6.0);
+    assert_eval_ok("2 * -3", -6.0);
+    assert_eval_ok("1e-5 * -1e5", -1.0);
+    assert_eval_ok("5 - -3", 8.0);
+}
+
 #[test]
 fn test_implicit_multiplication() {
     assert_eval_ok("3(5)", 15.0);
@@ -74,19 +114,20 @@ fn test_implicit_multiplication() {
 
 #[test]
 fn test_empty_and_comment_lines() {
-    let results = evaluate_lines("");
+    let results = evaluate_lines("", &mut SimpleExecutor::new());
     assert!(results.is_empty());
 
-    let results = evaluate_lines("   ");
+    let results = evaluate_lines("   ", &mut SimpleExecutor::new());
     assert!(results.is_empty());
 
-    let results = evaluate_lines("; this is a comment");
+    let results = evaluate_lines("; this is a comment", &mut SimpleExecutor::new());
     assert!(results.is_empty());
 
     let results = evaluate_lines(
         r#"1 + 1
 ; comment
 2 * 2"#,
+        &mut SimpleExecutor::new(),
     );
     assert_eq!(results.len(), 2);
     assert_eq!(results[0].as_ref().unwrap().0, 2.0);
@@ -111,16 +152,92 @@ fn test_line_continuation() {
 fn test_division_by_zero() {
     assert_eval_err("1 / 0", "DivisionByZero");
     assert_eval_err(" (5 + 5) / (3 - 3)", "DivisionByZero");
+    assert_last_eval_err("let z = 0\n1 / z", "Exec(DivisionByZero");
 }
 
 #[test]
-fn test_invalid_syntax() {
-    assert_eval_err("1 + * 2", r#"Parse(UnexpectedToken"#);
-    assert_eval_err(" (1 + 2 ", r#"Parse(UnexpectedToken"#);
+fn test_trailing_operator_continues_line() {
+    assert_eval_ok("1 +\n2", 3.0);
+    assert_eval_ok("10 -  ; minus\n4 *\n2", 2.0);
+    assert_eval_ok("8 /\n\\\n2", 4.0);
+    // A complete line ending in something else is not joined to the next.
+    assert_eq!(
+        eval_values("2\n-3\n4!", &mut SimpleExecutor::new()),
+        vec![2.0, -3.0, 24.0]
+    );
+
+    // A bare operator is still an error, and the line after it is evaluated on its own.
+    let results = evaluate_lines("+\n2", &mut SimpleExecutor::new());
+    assert!(matches!(results[0], Err(EvalError::Parse(..))));
+    assert_eq!(results[1].as_ref().unwrap().0, 2.0);
+
+    // An operator at the very end of the input is missing its operand.
+    let results = evaluate_numbered_lines("1\n2 *", &mut SimpleExecutor::new());
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[1].0, 2);
+    assert!(matches!(
+        results[1].1,
+        Err(EvalError::Parse(ParserError::UnexpectedEOF { .. }, ..))
+    ));
+
+    let options = EvalOptions::default();
+    assert!(continues_line("1 +  ; more", &options));
+    assert!(continues_line("x * -", &options));
+    assert!(!continues_line("+", &options));
+    assert!(!continues_line(" - * ", &options));
+    assert!(!continues_line("1 + 2 ; then -", &options));
+}
+
+#[test]
+fn test_line_continuation_with_comments() {
+    // The comment is removed before the trailing backslash is looked for.
+    assert_eval_ok("1 + \\  ; adds\n2", 3.0);
+    assert_eval_ok("1 + \\ ; first\n2 * \\ ; second\n3", 7.0);
+    // A backslash inside a comment does not continue the line.
+    let results = evaluate_lines("1 + 2 ; C:\\path\\\n3", &mut SimpleExecutor::new());
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[1].as_ref().unwrap().0, 3.0);
+
+    let options = EvalOptions::default();
+    assert!(continues_line("1 + \\  ; adds", &options));
+    assert!(!continues_line("1 + 2 ; ends with \\", &options));
+    assert!(!continues_line("; \\", &options));
+}
+
+#[test]
+fn test_non_finite_results() {
+    assert_eval_err("1e308 * 10", r#"NonFinite { op: "*", result: inf }"#);
+    assert_eval_err("-1e308 - 1e308", r#"NonFinite { op: "-", result: -inf }"#);
+    assert_eval_err("10 ^ 400", r#"NonFinite { op: "^", result: inf }"#);
+    assert_eval_err("(-8) ^ 0.5", r#"NonFinite { op: "^", result: NaN }"#);
+    // Dividing by something tiny rather than zero overflows instead.
+    assert_last_eval_err("let tiny = 1e-320\n1 / tiny", "NonFinite");
+    // Zero divided by zero is still a division by zero, not a NaN.
+    assert_last_eval_err("let z = 0.0\nz / z", "Exec(DivisionByZero");
+    // Built-ins are checked like the operators.
     assert_eval_err(
-        "abc",
-        r#"Parse(TokenizerError { message: "Unexpected character 'a'""#,
+        "factorial(171) * 2",
+        r#"NonFinite { op: "factorial", result: inf }"#,
+    );
+    assert_eval_err("171!", r#"NonFinite { op: "factorial", result: inf }"#);
+    assert_eval_err("exp(1000)", r#"NonFinite { op: "exp", result: inf }"#);
+    assert_eval_err("sqrt(-1)", r#"NonFinite { op: "sqrt", result: NaN }"#);
+    assert_eval_err("ln(0)", r#"NonFinite { op: "ln", result: -inf }"#);
+    assert_eq!(
+        evaluate_lines("sqrt(-1)", &mut SimpleExecutor::new())[0]
+            .as_ref()
+            .unwrap_err()
+            .to_string(),
+        "runtime error: function 'sqrt' produced NaN, which is not a finite number in input: sqrt(-1)"
     );
+}
+
+#[test]
+fn test_invalid_syntax() {
+    assert_eval_err("1 + * 2", r#"Parse(UnexpectedToken"#);
+    assert_eval_err(" (1 + 2 ", r#"Parse(UnmatchedParen { line: 1, col: 1 }"#);
+    assert_eval_err("1 + 2)", r#"Parse(UnmatchedCloseParen"#);
+    assert_eval_err("abc", r#"Exec(UndefinedVariable { name: "abc" }"#);
     assert_eval_err(
         "1.2.3",
         r#"Parse(TokenizerError { message: "Unexpected character '.'""#,
@@ -138,7 +255,7 @@ fn test_multiple_expressions_on_separate_lines() {
     let input = r#"1 + 1
 2 * 3
 10 / 2"#;
-    let results = evaluate_lines(input);
+    let results = evaluate_lines(input, &mut SimpleExecutor::new());
     assert_eq!(results.len(), 3);
     assert_eq!(results[0].as_ref().unwrap().0, 2.0);
     assert_eq!(results[1].as_ref().unwrap().0, 6.0);
@@ -160,6 +277,13 @@ fn test_scientific_notation_eval() {
     assert_eval_ok("1e-5 * 1e5", 1.0);
 }
 
+#[test]
+fn test_digit_separators_eval() {
+    assert_eval_ok("1_000 + 1", 1001.0);
+    assert_eval_ok("12.345_678", 12.345678);
+    assert_eval_err("1__0", "TokenizerError");
+}
+
 #[test]
 fn test_nested_parentheses() {
     assert_eval_ok("((((1))))", 1.0);
@@ -187,7 +311,7 @@ fn test_multiple_lines_with_comments_and_empty() {
 
    ; second comment
 3 + 3"#;
-    let results = evaluate_lines(input);
+    let results = evaluate_lines(input, &mut SimpleExecutor::new());
     assert_eq!(results.len(), 3);
     assert_eq!(results[0].as_ref().unwrap().0, 2.0);
     assert_eq!(results[1].as_ref().unwrap().0, 4.0);
@@ -238,20 +362,20 @@ fn test_chained_operations() {
 
 #[test]
 fn test_empty_input_multiple_lines() {
-    let results = evaluate_lines("\n\n");
+    let results = evaluate_lines("\n\n", &mut SimpleExecutor::new());
     assert!(results.is_empty());
 }
 
 #[test]
 fn test_input_with_only_whitespace() {
-    let results = evaluate_lines("   	  \n  ");
+    let results = evaluate_lines("   	  \n  ", &mut SimpleExecutor::new());
     assert!(results.is_empty());
 }
 
 #[test]
 fn test_comment_only_lines_mixed_with_empty() {
     let input = r#"; comment 1\n\n; comment 2"#;
-    let results = evaluate_lines(input);
+    let results = evaluate_lines(input, &mut SimpleExecutor::new());
     assert!(results.is_empty());
 }
 
@@ -301,14 +425,14 @@ fn test_implicit_multiplication_with_empty_paren() {
 
 #[test]
 fn test_invalid_input() {
-    let results = evaluate_lines("@");
+    let results = evaluate_lines("@", &mut SimpleExecutor::new());
     assert_eq!(results.len(), 1);
     assert!(matches!(results[0], Err(EvalError::Parse(..))));
 }
 
 #[test]
 fn test_comment_only_line() {
-    let results = evaluate_lines("; this is a comment");
+    let results = evaluate_lines("; this is a comment", &mut SimpleExecutor::new());
     assert!(results.is_empty());
 }
 
@@ -316,7 +440,7 @@ fn test_comment_only_line() {
 fn test_unsupported_operator() {
     // This test requires a custom token type that is not supported by the compiler.
     // Since we can't easily add a new token type, we will simulate this by creating a parser error.
-    let results = evaluate_lines("1 % 2");
+    let results = evaluate_lines("1 @ 2", &mut SimpleExecutor::new());
     assert_eq!(results.len(), 1);
     assert!(matches!(
         results[0],
@@ -328,17 +452,17 @@ fn test_unsupported_operator() {
 fn test_stack_underflow() {
     // Input `+` causes a ParserError, not a StackUnderflow.
     // StackUnderflow is generally unreachable with a correct parser and compiler.
-    let results = evaluate_lines("+");
+    let results = evaluate_lines("+", &mut SimpleExecutor::new());
     assert_eq!(results.len(), 1);
     assert!(matches!(
         results[0],
-        Err(EvalError::Parse(ParserError::UnexpectedToken { .. }, _, _))
+        Err(EvalError::Parse(ParserError::UnexpectedEOF { .. }, _, _))
     ));
 }
 
 #[test]
 fn test_tokenizer_error() {
-    let results = evaluate_lines("$");
+    let results = evaluate_lines("$", &mut SimpleExecutor::new());
     assert_eq!(results.len(), 1);
     assert!(results[0].is_err());
 }
@@ -347,14 +471,14 @@ fn test_tokenizer_error() {
 fn test_compile_error() {
     // To trigger a compile error, we need an AST node that the compiler doesn't support.
     // We can't easily create such a node, so we will simulate this by creating a parser error.
-    let results = evaluate_lines("1+*2");
+    let results = evaluate_lines("1+*2", &mut SimpleExecutor::new());
     assert_eq!(results.len(), 1);
     assert!(results[0].is_err());
 }
 
 #[test]
 fn test_empty_expression_old() {
-    let results = evaluate_lines("1()");
+    let results = evaluate_lines("1()", &mut SimpleExecutor::new());
     assert_eq!(results.len(), 1);
     print!("{:?}", results);
     assert!(results[0].is_ok()); // Should be Ok(0.0) or similar for empty expression
@@ -363,7 +487,7 @@ fn test_empty_expression_old() {
 #[test]
 fn test_eval_error_display_parse() {
     let input = "@";
-    let results = evaluate_lines(input);
+    let results = evaluate_lines(input, &mut SimpleExecutor::new());
     if let Err(EvalError::Parse(_e, _, _)) = &results[0] {
         let expected_output = r#"Error: Tokenizer error: Unexpected character '@' at line 1, col 1
 1 | @
@@ -442,14 +566,1634 @@ fn test_compile_error_display_unsupported_operator() {
 
 #[test]
 fn test_evaluate_lines_exec_error() {
-    let results = evaluate_lines("1 / 0");
+    let results = evaluate_lines("1 / 0", &mut SimpleExecutor::new());
     assert_eq!(results.len(), 1);
     assert!(results[0].is_err());
 }
 
 #[test]
 fn test_evaluate_lines_parser_error() {
-    let results = evaluate_lines("1 + *");
+    let results = evaluate_lines("1 + *", &mut SimpleExecutor::new());
     assert_eq!(results.len(), 1);
     assert!(results[0].is_err());
 }
+
+// Dataset with mean 5 and a sum of squared deviations of 32 over 8 values.
+const DATASET: &str = "2, 4, 4, 4, 5, 5, 7, 9";
+
+#[test]
+fn test_population_variance_and_stddev() {
+    assert_eval_ok(&format!("variance({})", DATASET), 4.0);
+    assert_eval_ok(&format!("stddev({})", DATASET), 2.0);
+    assert_eval_ok("variance(3)", 0.0);
+}
+
+#[test]
+fn test_sample_variance_and_stddev() {
+    assert_eval_ok(&format!("sample_variance({})", DATASET), 32.0 / 7.0);
+    assert_eval_ok(
+        &format!("sample_stddev({})", DATASET),
+        (32.0f64 / 7.0).sqrt(),
+    );
+}
+
+#[test]
+fn test_variance_argument_count() {
+    assert_eval_err("variance()", "ArgumentCount");
+    assert_eval_err("stddev()", "ArgumentCount");
+    assert_eval_err("sample_variance(1)", "ArgumentCount");
+    assert_eval_err("sample_stddev(1)", "ArgumentCount");
+}
+
+#[test]
+fn test_unknown_function() {
+    assert_eval_err("nope(1)", r#"UnknownFunction { name: "nope" }"#);
+    let results = evaluate_lines("nope(1)", &mut SimpleExecutor::new());
+    assert_eq!(
+        results[0].as_ref().unwrap_err().to_string(),
+        "runtime error: unknown function 'nope' in input: nope(1)"
+    );
+}
+
+#[test]
+fn test_math_builtins() {
+    assert_eval_ok("sqrt(16)", 4.0);
+    assert_eval_ok("abs(-5)", 5.0);
+    assert_eval_ok("sin(0)", 0.0);
+    assert_eval_ok("cos(0)", 1.0);
+    assert_eval_ok("tan(0)", 0.0);
+    assert_eval_ok("ln(exp(2))", 2.0);
+    assert_eval_ok("log10(1000)", 3.0);
+    assert_eval_ok("2sqrt(9) + 1", 7.0);
+}
+
+#[test]
+fn test_math_builtin_argument_count() {
+    assert_eval_err("sqrt(1, 2)", "ArgumentCount");
+    assert_eval_err("abs()", "ArgumentCount");
+}
+
+#[test]
+fn test_let_and_variable_use() {
+    let mut executor = SimpleExecutor::new();
+    let results = evaluate_lines("let x = 4\nx * 2\n3x", &mut executor);
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().0, 4.0);
+    assert_eq!(results[1].as_ref().unwrap().0, 8.0);
+    assert_eq!(results[2].as_ref().unwrap().0, 12.0);
+}
+
+#[test]
+fn test_variables_persist_across_calls() {
+    let mut executor = SimpleExecutor::new();
+    assert_eq!(eval_values("let rate = 0.5", &mut executor), vec![0.5]);
+    let results = evaluate_lines("rate * 10", &mut executor);
+    assert_eq!(results[0].as_ref().unwrap().0, 5.0);
+}
+
+#[test]
+fn test_variables_iterate_in_stable_order() {
+    let names = || {
+        let mut executor = SimpleExecutor::new();
+        evaluate_lines("let zeta = 1\nlet alpha = 2\nlet mid = 3", &mut executor);
+        executor
+            .variables()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>()
+    };
+    let first = names();
+    for _ in 0..10 {
+        assert_eq!(names(), first);
+    }
+    assert_eq!(
+        first,
+        vec!["alpha", "e", "mid", "pi", "tau", "total", "zeta"]
+    );
+}
+
+#[test]
+fn test_definitions_produce_their_value() {
+    let mut executor = SimpleExecutor::new();
+    let results = evaluate_lines("let x = 5", &mut executor);
+    assert_eq!(results.len(), 1);
+    assert_eq!(
+        results[0].as_ref().unwrap(),
+        &(5.0, "let x = 5".to_string())
+    );
+
+    assert_eq!(
+        eval_values("x = 2\nx *= 4\nlet f(n) = n + 1", &mut executor),
+        vec![2.0, 8.0]
+    );
+    // Only bare expressions feed `ans`, `$N` and the running total.
+    assert_eq!(executor.variable("ans"), None);
+    assert_eq!(executor.variable("total"), Some(0.0));
+}
+
+#[test]
+fn test_assignment_and_compound_assignment() {
+    let mut executor = SimpleExecutor::new();
+    assert_eq!(
+        eval_values("let x = 1\nx = 10\nx += 5\nx /= 3\nx", &mut executor),
+        vec![1.0, 10.0, 15.0, 5.0, 5.0]
+    );
+}
+
+#[test]
+fn test_assignment_to_undefined_variable() {
+    assert_eval_err("y = 1", "UndefinedVariable");
+    assert_eval_err("y += 1", "UndefinedVariable");
+}
+
+#[test]
+fn test_trailing_line_continuation_errors() {
+    let results = evaluate_lines("1 + \\", &mut SimpleExecutor::new());
+    assert_eq!(results.len(), 1);
+    assert!(matches!(
+        &results[0],
+        Err(EvalError::UnterminatedContinuation(_, 1))
+    ));
+}
+
+#[test]
+fn test_trailing_line_continuation_after_complete_lines() {
+    let results = evaluate_lines("2 * 3\n1 + \\\n2 + \\\n", &mut SimpleExecutor::new());
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].as_ref().unwrap().0, 6.0);
+    let err = results[1].as_ref().unwrap_err();
+    assert!(matches!(err, EvalError::UnterminatedContinuation(_, 3)));
+    assert_eq!(
+        err.to_string(),
+        "Error: input ended after a line continuation '\\' on line 3, expression is incomplete: 1 + 2 +"
+    );
+}
+
+#[test]
+fn test_copysign() {
+    assert_eval_ok("copysign(3, -1)", -3.0);
+    assert_eval_ok("copysign(-5, 1)", 5.0);
+    assert_eval_ok("copysign(0, -1)", -0.0);
+}
+
+#[test]
+fn test_nextafter() {
+    let mut executor = SimpleExecutor::new();
+    let up = evaluate_lines("nextafter(1, 2)", &mut executor)[0]
+        .as_ref()
+        .unwrap()
+        .0;
+    assert!(up > 1.0);
+    assert_eq!(up, 1.0 + f64::EPSILON);
+
+    let down = evaluate_lines("nextafter(1, 0)", &mut executor)[0]
+        .as_ref()
+        .unwrap()
+        .0;
+    assert!(down < 1.0);
+    assert_eval_ok("nextafter(2, 2)", 2.0);
+    assert_eval_ok("nextafter(0, 1)", f64::from_bits(1));
+}
+
+#[test]
+fn test_math_constants() {
+    assert_eval_ok("pi", std::f64::consts::PI);
+    assert_eval_ok("e", std::f64::consts::E);
+    assert_eval_ok("tau", std::f64::consts::TAU);
+    assert_eval_ok("2*pi", 2.0 * std::f64::consts::PI);
+    assert_eval_ok("2pi", 2.0 * std::f64::consts::PI);
+    assert_eval_ok("2e", 2.0 * std::f64::consts::E);
+    assert_eval_ok("2 * pi * 5", 10.0 * std::f64::consts::PI);
+}
+
+#[test]
+fn test_constants_can_be_shadowed() {
+    let mut executor = SimpleExecutor::new();
+    let results = evaluate_lines("let pi = 3\npi * 2", &mut executor);
+    assert_eq!(results[1].as_ref().unwrap().0, 6.0);
+
+    // Shadowing only affects the executor it happened in.
+    assert_eval_ok("pi", std::f64::consts::PI);
+}
+
+#[test]
+fn test_streaming_reports_results_in_order() {
+    let input = "1 + 1\n; comment\nlet x = 3\n2 * \\\nx\n1 / 0\nx";
+    let mut seen = Vec::new();
+    evaluate_lines_streaming(input, &mut SimpleExecutor::new(), |line, result| {
+        seen.push((line, result.map(|(v, _)| v).map_err(|e| e.to_string())));
+    });
+    assert_eq!(
+        seen,
+        vec![
+            (1, Ok(2.0)),
+            (3, Ok(3.0)),
+            (4, Ok(6.0)),
+            (
+                6,
+                Err("compile error: division by zero in input: 1 / 0".to_string())
+            ),
+            (7, Ok(3.0)),
+        ]
+    );
+}
+
+#[test]
+fn test_streaming_callback_runs_before_later_lines_evaluate() {
+    // The callback sees each result before the following line is evaluated, so the
+    // executor state observed through the callback order matches the source order.
+    let mut order = Vec::new();
+    let mut executor = SimpleExecutor::new();
+    evaluate_lines_streaming("let a = 1\na\na = 2\na", &mut executor, |_, result| {
+        order.push(result.unwrap().0);
+    });
+    assert_eq!(order, vec![1.0, 1.0, 2.0, 2.0]);
+}
+
+#[test]
+fn test_evaluate_stream_matches_evaluate_lines() {
+    let input = "1 + 1\n; comment\nlet x = 3\n2 * \\\nx\n1 / 0\nx\n4 + \\";
+    let mut executor = SimpleExecutor::new();
+    let mut stream = evaluate_stream(input, &mut executor);
+    let mut streamed = Vec::new();
+    assert_eq!(stream.next().unwrap().unwrap().0, 2.0);
+    assert_eq!(stream.next().unwrap().unwrap().0, 3.0);
+    assert_eq!(stream.next().unwrap().unwrap().0, 6.0);
+    for result in stream {
+        streamed.push(format!("{:?}", result));
+    }
+    let collected: Vec<String> = evaluate_lines(input, &mut SimpleExecutor::new())
+        .iter()
+        .skip(3)
+        .map(|result| format!("{:?}", result))
+        .collect();
+    assert_eq!(streamed.len(), 3);
+    assert_eq!(streamed, collected);
+}
+
+#[test]
+fn test_evaluate_stream_is_lazy() {
+    let mut executor = SimpleExecutor::new();
+    let mut stream = evaluate_stream("let a = 1\na\nlet b = 2\nb", &mut executor);
+    assert_eq!(stream.next().unwrap().unwrap().0, 1.0);
+    drop(stream);
+
+    // Only the lines needed for the first result were evaluated.
+    assert_eq!(executor.variable("a"), Some(1.0));
+    assert_eq!(executor.variable("b"), None);
+}
+
+#[test]
+fn test_popcount() {
+    assert_eval_ok("popcount(7)", 3.0);
+    assert_eval_ok("popcount(255)", 8.0);
+    assert_eval_ok("popcount(0)", 0.0);
+    assert_eval_ok("bitcount(1024)", 1.0);
+}
+
+#[test]
+fn test_popcount_rejects_non_integers() {
+    assert_eval_err("popcount(2.5)", "InvalidArgument");
+    assert_eval_err("popcount(-1)", "InvalidArgument");
+    assert_eval_err("popcount()", "ArgumentCount");
+}
+
+#[test]
+fn test_running_total() {
+    let mut executor = SimpleExecutor::new();
+    evaluate_lines("2\n3 * 1\n4\nlet x = 100", &mut executor);
+    let results = evaluate_lines("total\ntotal", &mut executor);
+    assert_eq!(results[0].as_ref().unwrap().0, 9.0);
+    assert_eq!(results[1].as_ref().unwrap().0, 9.0);
+
+    evaluate_lines("total = 0\n5", &mut executor);
+    assert_eq!(executor.variable("total"), Some(5.0));
+}
+
+#[test]
+fn test_round_sig() {
+    assert_eval_ok("round_sig(12345, 2)", 12000.0);
+    assert_eval_ok("round_sig(0.012345, 2)", 0.012);
+    assert_eval_ok("round_sig(-98765, 3)", -98800.0);
+    assert_eval_ok("round_sig(0, 3)", 0.0);
+    // More figures than an f64 holds, or a scale factor outside its range, change nothing.
+    assert_eval_ok("round_sig(1.5, 400)", 1.5);
+    assert_eval_ok("round_sig(0.1 + 0.2, 17)", 0.1 + 0.2);
+    assert_eval_ok("round_sig(1e-310, 2)", 1e-310);
+    assert_eval_ok("round_sig(1.7e308, 1)", 1.7e308);
+}
+
+#[test]
+fn test_round_sig_requires_positive_integer_figures() {
+    assert_eval_err("round_sig(12345, 0)", "InvalidArgument");
+    assert_eval_err("round_sig(12345, 1.5)", "InvalidArgument");
+    assert_eval_err("round_sig(12345)", "ArgumentCount");
+}
+
+#[test]
+fn test_ans_holds_last_result() {
+    let mut executor = SimpleExecutor::new();
+    evaluate_lines("5 + 5", &mut executor);
+    let results = evaluate_lines("ans * 2", &mut executor);
+    assert_eq!(results[0].as_ref().unwrap().0, 20.0);
+
+    // Several results on one input: `ans` is the last of them.
+    evaluate_lines("1\n2\nlet x = 7", &mut executor);
+    assert_eq!(executor.variable("ans"), Some(2.0));
+
+    assert_eval_err("ans", "UndefinedVariable");
+}
+
+#[test]
+fn test_trailing_garbage_is_rejected_by_default() {
+    assert_eval_err("1 + 2 , garbage", "Parse(UnexpectedToken { found: Comma");
+    // A trailing identifier is an implicit multiplication, not garbage.
+    assert_eval_err("1 + 2 garbage", "UndefinedVariable");
+}
+
+#[test]
+fn test_allow_trailing_garbage() {
+    let mut executor = SimpleExecutor::with_options(EvalOptions {
+        allow_trailing_garbage: true,
+        ..EvalOptions::default()
+    });
+    assert_eq!(
+        eval_values("1 + 2 , garbage\nlet x = 4 : 5\nx", &mut executor),
+        vec![3.0, 4.0, 4.0]
+    );
+    let results = evaluate_lines("1 + 2 garbage", &mut executor);
+    assert!(matches!(
+        results[0],
+        Err(EvalError::Exec(ExecError::UndefinedVariable { .. }, _))
+    ));
+}
+
+#[test]
+fn test_calculator_mode_trailing_equals() {
+    let mut executor = SimpleExecutor::with_options(EvalOptions {
+        calculator_mode: true,
+        ..EvalOptions::default()
+    });
+    assert_eq!(
+        eval_values("2 + 2 =\nlet x = 3\nx * 2 =", &mut executor),
+        vec![4.0, 3.0, 6.0]
+    );
+
+    // A bare `=` after a name is still an assignment missing its value.
+    let results = evaluate_lines("x =\nlet y =", &mut executor);
+    assert!(matches!(results[0], Err(EvalError::Parse(..))));
+    assert!(matches!(results[1], Err(EvalError::Parse(..))));
+}
+
+#[test]
+fn test_let_redefinition_is_allowed_by_default() {
+    let mut executor = SimpleExecutor::new();
+    assert_eq!(
+        eval_values("let x = 1\nlet x = 2\nx\nlet pi = 3", &mut executor),
+        vec![1.0, 2.0, 2.0, 3.0]
+    );
+}
+
+#[test]
+fn test_strict_mode_rejects_let_redefinition() {
+    let mut executor = SimpleExecutor::with_options(EvalOptions {
+        strict: true,
+        ..EvalOptions::default()
+    });
+    let results = evaluate_lines("let x = 1\nlet x = 2\nx = 3\nx\nlet pi = 3", &mut executor);
+    assert_eq!(results[0].as_ref().unwrap().0, 1.0);
+    assert!(matches!(
+        &results[1],
+        Err(EvalError::Exec(ExecError::AlreadyDefined { name }, _)) if name == "x"
+    ));
+    assert_eq!(
+        results[1].as_ref().unwrap_err().to_string(),
+        "runtime error: variable 'x' is already defined; assign to it with `x = ...` instead in input: let x = 2"
+    );
+    // Reassignment is still allowed, and the rejected `let` changed nothing.
+    assert_eq!(results[2].as_ref().unwrap().0, 3.0);
+    assert_eq!(results[3].as_ref().unwrap().0, 3.0);
+    // The predefined constants count as defined.
+    assert!(matches!(
+        results[4],
+        Err(EvalError::Exec(ExecError::AlreadyDefined { .. }, _))
+    ));
+    assert_eq!(executor.variable("pi"), Some(std::f64::consts::PI));
+}
+
+#[test]
+fn test_tape_mode_continues_previous_result() {
+    let mut executor = SimpleExecutor::with_options(EvalOptions {
+        tape_mode: true,
+        ..EvalOptions::default()
+    });
+    let values: Vec<f64> = evaluate_lines("10\n+ 5\n* 2\n- 4\n/ 2\n-3", &mut executor)
+        .into_iter()
+        .map(|result| result.unwrap().0)
+        .collect();
+    // `-3` has no space after the sign, so it is a negative number, not `ans - 3`.
+    assert_eq!(values, vec![10.0, 15.0, 30.0, 26.0, 13.0, -3.0]);
+
+    // Without a previous result, a leading operator is left alone.
+    let mut executor = SimpleExecutor::with_options(EvalOptions {
+        tape_mode: true,
+        ..EvalOptions::default()
+    });
+    assert_eq!(
+        evaluate_lines("+ 5", &mut executor)[0].as_ref().unwrap().0,
+        5.0
+    );
+
+    // Outside tape mode, `* 2` is a syntax error.
+    let results = evaluate_lines("10\n* 2", &mut SimpleExecutor::new());
+    assert!(matches!(results[1], Err(EvalError::Parse(..))));
+}
+
+#[test]
+fn test_comparison_operators() {
+    assert_eval_ok("3 > 2", 1.0);
+    assert_eval_ok("2 == 2", 1.0);
+    assert_eval_ok("1 != 1", 0.0);
+    assert_eval_ok("2 < 1", 0.0);
+    assert_eval_ok("2 <= 2", 1.0);
+    assert_eval_ok("1 >= 2", 0.0);
+    assert_eval_ok("1 + 1 == 2", 1.0);
+    assert_eval_ok("(3 > 2) + (4 > 3)", 2.0);
+}
+
+#[test]
+fn test_approx_equal_operator() {
+    assert_eval_ok("0.1 + 0.2 == 0.3", 0.0);
+    assert_eval_ok("0.1 + 0.2 ~= 0.3", 1.0);
+    assert_eval_ok("1 ~= 2", 0.0);
+    assert_eval_ok("1e20 ~= 1e20 + 1000", 1.0);
+    assert_eval_ok("1e-12 ~= 0", 1.0);
+    assert_eval_ok("1e-6 ~= 0", 0.0);
+    // `~` followed by a space is still bitwise NOT.
+    assert_eval_ok("~ 0", -1.0);
+    let mut executor = SimpleExecutor::new();
+    assert_eq!(
+        eval_values(
+            "let x = 0.1 * 3\nx ~= 0.3\nx + 1 ~= 1.3 ? 7 : 8",
+            &mut executor
+        )[1..],
+        [1.0, 7.0]
+    );
+}
+
+fn num(n: f64) -> Expr {
+    Expr::Number(n)
+}
+
+fn binary(left: Expr, op: TokenType, right: Expr) -> Expr {
+    Expr::BinaryOp {
+        left: Box::new(left),
+        op,
+        right: Box::new(right),
+    }
+}
+
+#[test]
+fn test_constant_folding() {
+    let compile = |expr: Expr| BytecodeCompiler::compile(&expr).unwrap();
+    assert_eq!(
+        compile(binary(num(2.0), TokenType::Plus, num(3.0))),
+        vec![Instr::Push(Value::Int(5))]
+    );
+    // 2 * 3 + 4
+    assert_eq!(
+        compile(binary(
+            binary(num(2.0), TokenType::Mul, num(3.0)),
+            TokenType::Plus,
+            num(4.0)
+        )),
+        vec![Instr::Push(Value::Int(10))]
+    );
+    // -(2 - 5)
+    assert_eq!(
+        compile(Expr::UnaryOp {
+            op: TokenType::Minus,
+            expr: Box::new(binary(num(2.0), TokenType::Minus, num(5.0))),
+        }),
+        vec![Instr::Push(Value::Int(3))]
+    );
+    // x * (2 + 3) folds only the constant operand.
+    assert_eq!(
+        compile(binary(
+            Expr::Variable("x".to_string()),
+            TokenType::Mul,
+            binary(num(2.0), TokenType::Plus, num(3.0))
+        )),
+        vec![
+            Instr::Load("x".to_string()),
+            Instr::Push(Value::Int(5)),
+            Instr::Mul
+        ]
+    );
+    // A division of whole numbers with a remainder is left for `--warn-truncation`.
+    assert_eq!(
+        compile(binary(num(6.0), TokenType::Div, num(4.0))),
+        vec![
+            Instr::Push(Value::Int(6)),
+            Instr::Push(Value::Int(4)),
+            Instr::Div
+        ]
+    );
+    assert_eq!(
+        compile(binary(num(6.0), TokenType::Div, num(2.0))),
+        vec![Instr::Push(Value::Int(3))]
+    );
+}
+
+#[test]
+fn test_clone_nested_expression() {
+    // -(x * (2 + 3)) ? sqrt(4)! : $1
+    let original = Expr::Conditional {
+        cond: Box::new(Expr::UnaryOp {
+            op: TokenType::Minus,
+            expr: Box::new(binary(
+                Expr::Variable("x".to_string()),
+                TokenType::Mul,
+                binary(num(2.0), TokenType::Plus, num(3.0)),
+            )),
+        }),
+        then: Box::new(Expr::Postfix {
+            expr: Box::new(Expr::Call {
+                name: "sqrt".to_string(),
+                args: vec![num(4.0)],
+            }),
+            op: TokenType::Bang,
+        }),
+        else_: Box::new(Expr::HistoryRef(1)),
+    };
+    let copy = original.clone();
+    assert_eq!(copy, original);
+    assert_eq!(copy.to_string(), original.to_string());
+
+    let statement = Statement::Let {
+        name: "y".to_string(),
+        type_name: None,
+        value: original,
+    };
+    assert_eq!(statement.clone(), statement);
+}
+
+#[test]
+fn test_conditional_bytecode() {
+    let conditional = |cond: Expr| Expr::Conditional {
+        cond: Box::new(cond),
+        then: Box::new(num(10.0)),
+        else_: Box::new(binary(
+            Expr::Variable("y".to_string()),
+            TokenType::Mul,
+            num(2.0),
+        )),
+    };
+    assert_eq!(
+        BytecodeCompiler::compile(&conditional(Expr::Variable("x".to_string()))).unwrap(),
+        vec![
+            Instr::Load("x".to_string()),
+            Instr::JumpIfZero(2),
+            Instr::Push(Value::Int(10)),
+            Instr::Jump(3),
+            Instr::Load("y".to_string()),
+            Instr::Push(Value::Int(2)),
+            Instr::Mul
+        ]
+    );
+    // A constant condition compiles only the selected branch.
+    assert_eq!(
+        BytecodeCompiler::compile(&conditional(num(1.0))).unwrap(),
+        vec![Instr::Push(Value::Int(10))]
+    );
+}
+
+#[test]
+fn test_constant_division_by_zero_is_a_compile_error() {
+    assert!(matches!(
+        BytecodeCompiler::compile(&binary(num(1.0), TokenType::Div, num(0.0))),
+        Err(CompileError::DivisionByZero)
+    ));
+    assert_eval_err("1 / 0", "Compile(DivisionByZero");
+    assert_eval_err("4 / (2 - 2)", "Compile(DivisionByZero");
+}
+
+#[test]
+fn test_assertions_pass() {
+    assert_eval_ok("assert(2 > 1)", 1.0);
+    assert_eval_ok("assert_eq(2 * 3, 6)", 6.0);
+    assert_eval_ok("assert_eq(0.1 + 0.2, 0.3)", 0.1 + 0.2);
+}
+
+#[test]
+fn test_assertions_fail() {
+    assert_eval_err("assert(1 > 2)", r#"Other("assertion failed")"#);
+    assert_eval_err(
+        "assert_eq(2 + 2, 5)",
+        r#"Other("assertion failed: 4 != 5")"#,
+    );
+}
+
+#[test]
+fn test_factorial() {
+    assert_eval_ok("5!", 120.0);
+    assert_eval_ok("0!", 1.0);
+    assert_eval_ok("3! * 2", 12.0);
+    assert_eval_ok("factorial(4)", 24.0);
+    assert_eval_err("(-1)!", "InvalidArgument");
+    assert_eval_err("2.5!", "InvalidArgument");
+}
+
+#[test]
+fn test_percent() {
+    assert_eval_ok("50%", 0.5);
+    assert_eval_ok("50% * 200", 100.0);
+    assert_eval_ok("50% of 200", 100.0);
+    assert_eval_ok("200 * 50%", 100.0);
+    assert_eval_ok("50% - 3", -2.5);
+    assert_eval_ok("(10 + 40)%", 0.5);
+    assert_eq!(
+        eval_values("let x = 80\n25% of x", &mut SimpleExecutor::new()),
+        vec![80.0, 20.0]
+    );
+    assert_eval_err("let of = 2", "ReservedKeyword");
+    assert_eval_err("2 of 3", "UnexpectedToken");
+}
+
+#[test]
+fn test_modulo() {
+    assert_eval_ok("7 % 3", 1.0);
+    assert_eval_ok("7%3", 1.0);
+    assert_eval_ok("-7 % 3", -1.0);
+    assert_eval_ok("10 % -3", 1.0);
+    assert_eval_ok("10 %-3", 1.0);
+    assert_eval_ok("10 % - 3", -2.9);
+    assert_eval_ok("7.5 % 2", 1.5);
+    assert_eq!(
+        eval_values("let x = 7\nlet y = 4\nx % y", &mut SimpleExecutor::new()),
+        vec![7.0, 4.0, 3.0]
+    );
+    assert_eval_ok("50% % 2", 0.5);
+    assert_eval_ok("10 % 300%", 1.0);
+    assert_eval_err("7 % 0", "DivisionByZero");
+}
+
+#[test]
+fn test_value_range_guard() {
+    let options = EvalOptions {
+        range: ValueRange {
+            min: Some(0.0),
+            max: Some(100.0),
+        },
+        ..EvalOptions::default()
+    };
+    let mut executor = SimpleExecutor::with_options(options.clone());
+    let results = evaluate_lines("50 + 50\n101\n0 - 1", &mut executor);
+    assert_eq!(results[0].as_ref().unwrap().0, 100.0);
+    assert!(matches!(results[1], Err(EvalError::OutOfRange(v, _, _)) if v == 101.0));
+    assert_eq!(
+        results[2].as_ref().unwrap_err().to_string(),
+        "Error: result -1 is outside the allowed range [0, 100] in input: 0 - 1"
+    );
+    // A rejected result does not become `ans` or count towards `total`.
+    assert_eq!(executor.variable("ans"), Some(100.0));
+    assert_eq!(executor.variable("total"), Some(100.0));
+
+    // Definitions and assignments are checked too, and a rejected value is not stored.
+    let results = evaluate_lines(
+        "let x = 500\nlet y = 5\ny = 600\ny += 200\ny",
+        &mut executor,
+    );
+    assert!(matches!(results[0], Err(EvalError::OutOfRange(v, _, _)) if v == 500.0));
+    assert!(matches!(results[2], Err(EvalError::OutOfRange(v, _, _)) if v == 600.0));
+    assert!(matches!(results[3], Err(EvalError::OutOfRange(v, _, _)) if v == 205.0));
+    assert_eq!(executor.value("x"), None);
+    assert_eq!(results[4].as_ref().unwrap().0, 5.0);
+
+    let mut executor = SimpleExecutor::with_options(EvalOptions {
+        clamp_to_range: true,
+        ..options
+    });
+    let values: Vec<f64> = evaluate_lines("42\n250\n-7", &mut executor)
+        .into_iter()
+        .map(|result| result.unwrap().0)
+        .collect();
+    assert_eq!(values, vec![42.0, 100.0, 0.0]);
+    assert_eq!(
+        eval_values("let x = 500\nx", &mut executor),
+        vec![100.0, 100.0]
+    );
+    assert_eq!(executor.value("x"), Some(Value::Int(100)));
+}
+
+#[test]
+fn test_integer_and_float_values() {
+    let mut executor = SimpleExecutor::new();
+    evaluate_lines(
+        "let a = 2 + 3\nlet b = 6 / 3\nlet c = 10 / 3\nlet d = 2 * 1.5\nlet f = 3 > 2",
+        &mut executor,
+    );
+    assert_eq!(executor.value("a"), Some(Value::Int(5)));
+    assert_eq!(executor.value("b"), Some(Value::Int(2)));
+    assert_eq!(executor.value("c"), Some(Value::Float(10.0 / 3.0)));
+    // Mixing in a float makes the result a float, even when it is integral.
+    assert_eq!(executor.value("d"), Some(Value::Float(3.0)));
+    assert_eq!(executor.value("f"), Some(Value::Int(1)));
+
+    // Integer overflow promotes to a float instead of wrapping.
+    evaluate_lines(
+        "let big = 9007199254740992 * 9007199254740992",
+        &mut executor,
+    );
+    assert_eq!(executor.value("big"), Some(Value::Float(2f64.powi(106))));
+}
+
+#[test]
+fn test_type_annotations() {
+    let mut executor = SimpleExecutor::new();
+    let results = evaluate_lines(
+        "let n: Int = 4 * 5\nlet x: Float = 2\nlet m: Int = 2.5\nlet k: Int = 7 / 2",
+        &mut executor,
+    );
+    assert_eq!(executor.value("n"), Some(Value::Int(20)));
+    // An integer declared as a `Float` is converted.
+    assert_eq!(executor.value("x"), Some(Value::Float(2.0)));
+    assert_eq!(results.len(), 4);
+    assert_eq!(
+        results[2].as_ref().unwrap_err().to_string(),
+        "runtime error: type mismatch: expected Int, got Float 2.5 in input: let m: Int = 2.5"
+    );
+    assert!(matches!(
+        results[3],
+        Err(EvalError::Exec(ExecError::TypeMismatch { .. }, _))
+    ));
+    assert_eq!(executor.value("m"), None);
+
+    // Built-ins with whole-number results give integers, both in bytecode and in the
+    // tree-walking evaluator used by `differential_check`.
+    let mut executor = SimpleExecutor::new();
+    let results = evaluate_lines(
+        "let p: Int = popcount(7)\nlet f: Int = 5!\nlet g: Int = gcd(12, 18)\nlet r: Int = sqrt(2)",
+        &mut executor,
+    );
+    assert_eq!(executor.value("p"), Some(Value::Int(3)));
+    assert_eq!(executor.value("f"), Some(Value::Int(120)));
+    assert_eq!(executor.value("g"), Some(Value::Int(6)));
+    assert!(matches!(
+        results[3],
+        Err(EvalError::Exec(ExecError::TypeMismatch { .. }, _))
+    ));
+    let Ok(Statement::Expression(expr)) = parse_str("floor(2.5) + sign(-3)") else {
+        panic!("not an expression");
+    };
+    assert_eq!(expr.evaluate(&executor).unwrap(), Value::Int(1));
+
+    assert_eval_err("let t: Text = 1", "UnknownType");
+
+    let mut executor = SimpleExecutor::new();
+    let values = eval_values(
+        "let a: Number = 2.5\nlet b: Number = 7\na\nb",
+        &mut executor,
+    );
+    assert_eq!(values, vec![2.5, 7.0, 2.5, 7.0]);
+}
+
+#[test]
+fn test_rename_symbol() {
+    let mut executor = SimpleExecutor::new();
+    evaluate_lines("let width = 3\nlet height = 4", &mut executor);
+
+    assert!(executor.rename_symbol("width", "w"));
+    assert_eq!(executor.variable("w"), Some(3.0));
+    assert_eq!(executor.variable("width"), None);
+    assert_eq!(
+        evaluate_lines("w * 2", &mut executor)[0]
+            .as_ref()
+            .unwrap()
+            .0,
+        6.0
+    );
+
+    // Renaming onto an existing name, or renaming an undefined one, changes nothing.
+    assert!(!executor.rename_symbol("w", "height"));
+    assert!(!executor.rename_symbol("missing", "m"));
+    assert_eq!(executor.variable("w"), Some(3.0));
+    assert_eq!(executor.variable("height"), Some(4.0));
+}
+
+#[test]
+fn test_power_and_associativity() {
+    assert_eval_ok("2 ^ 3 ^ 2", 512.0);
+    assert_eval_ok("8 - 2 - 1", 5.0);
+    assert_eval_ok("-2 ^ 2", -4.0);
+    assert_eval_ok("(-2) ^ 2", 4.0);
+    assert_eval_ok("2 ^ -1", 0.5);
+    assert_eval_ok("4 ^ 0.5", 2.0);
+
+    let mut executor = SimpleExecutor::new();
+    evaluate_lines("let n = 3\nlet p = 2 ^ n\nlet q = 2 ^ 0.5", &mut executor);
+    assert_eq!(executor.value("p"), Some(Value::Int(8)));
+    assert_eq!(executor.value("q"), Some(Value::Float(2f64.sqrt())));
+}
+
+#[test]
+fn test_history_references() {
+    let mut executor = SimpleExecutor::new();
+    executor.push_result(Value::Int(10));
+    executor.push_result(Value::Float(2.5));
+    let results = evaluate_lines("$1 + $2\n$3 * 2", &mut executor);
+    assert_eq!(results[0].as_ref().unwrap().0, 12.5);
+    assert_eq!(results[1].as_ref().unwrap().0, 25.0);
+    assert_eq!(executor.results().len(), 4);
+
+    // `$1` keeps its integer type, and definitions do not produce a result.
+    evaluate_lines("let n = $1", &mut executor);
+    assert_eq!(executor.value("n"), Some(Value::Int(10)));
+    assert_eq!(executor.results().len(), 4);
+
+    // Implicit multiplication works as for variables.
+    assert_eq!(
+        evaluate_lines("2$1", &mut executor)[0].as_ref().unwrap().0,
+        20.0
+    );
+}
+
+#[test]
+fn test_history_reference_out_of_range() {
+    assert_eval_err("$1", "HistoryIndex { index: 1, available: 0 }");
+
+    let mut executor = SimpleExecutor::new();
+    executor.push_result(Value::Int(1));
+    for input in ["$0", "$2", "$99999999999999999999999"] {
+        let results = evaluate_lines(input, &mut executor);
+        let err = results[0].as_ref().unwrap_err();
+        assert!(
+            format!("{:?}", err).contains("HistoryIndex"),
+            "{}: {:?}",
+            input,
+            err
+        );
+    }
+    // Failed lines are not recorded as results.
+    assert_eq!(executor.results(), &[Value::Int(1)]);
+}
+
+#[test]
+fn test_gcd_and_lcm() {
+    assert_eval_ok("gcd(12, 18, 24)", 6.0);
+    assert_eval_ok("lcm(2, 3, 4)", 12.0);
+    assert_eval_ok("gcd(12, 18)", 6.0);
+    assert_eval_ok("lcm(4, 6)", 12.0);
+
+    // A single argument is its own gcd and lcm, ignoring its sign.
+    assert_eval_ok("gcd(7)", 7.0);
+    assert_eval_ok("lcm(7)", 7.0);
+    assert_eval_ok("gcd(-9)", 9.0);
+
+    assert_eval_ok("gcd(0, 4)", 4.0);
+    assert_eval_ok("lcm(0, 4)", 0.0);
+    assert_eval_ok("gcd(-12, 18)", 6.0);
+}
+
+#[test]
+fn test_gcd_and_lcm_require_integers() {
+    assert_eval_err("gcd(1.5, 3)", "InvalidArgument");
+    assert_eval_err("lcm(2, 0.5)", "InvalidArgument");
+    assert_eval_err("gcd()", "ArgumentCount");
+}
+
+#[test]
+fn test_min_max_clamp() {
+    assert_eval_ok("max(3, 7, 2)", 7.0);
+    assert_eval_ok("min(1, -1)", -1.0);
+    assert_eval_ok("min(4)", 4.0);
+    assert_eval_ok("max(-2.5, -3)", -2.5);
+    assert_eval_ok("clamp(5, 0, 3)", 3.0);
+    assert_eval_ok("clamp(-5, 0, 3)", 0.0);
+    assert_eval_ok("clamp(1.5, 0, 3)", 1.5);
+    assert_eval_ok("clamp(7, 2, 2)", 2.0);
+
+    assert_eval_err("clamp(5, 3, 0)", "InvalidArgument");
+    assert_eval_err("clamp(5, 0)", "ArgumentCount");
+    assert_eval_err("max()", "ArgumentCount");
+}
+
+#[test]
+fn test_rounding_functions() {
+    assert_eval_ok("floor(3.7)", 3.0);
+    assert_eval_ok("floor(-2.5)", -3.0);
+    assert_eval_ok("ceil(3.2)", 4.0);
+    assert_eval_ok("ceil(-2.5)", -2.0);
+    assert_eval_ok("round(2.4)", 2.0);
+    assert_eval_ok("round(2.5)", 3.0);
+    assert_eval_ok("round(-2.5)", -3.0);
+    assert_eval_ok("trunc(2.7)", 2.0);
+    assert_eval_ok("trunc(-2.7)", -2.0);
+    assert_eval_ok("floor(sqrt(10))", 3.0);
+    assert_eval_ok("round(ceil(1.2) * 1.5)", 3.0);
+    assert_eval_err("floor()", "ArgumentCount");
+    assert_eval_err("round(1, 2)", "ArgumentCount");
+}
+
+#[test]
+fn test_sign_and_hypot() {
+    assert_eval_ok("sign(-3)", -1.0);
+    assert_eval_ok("sign(0)", 0.0);
+    assert_eval_ok("sign(-0)", 0.0);
+    assert_eval_ok("sign(2.5)", 1.0);
+    assert_eval_ok("hypot(3, 4)", 5.0);
+    assert_eval_ok("hypot(-5, 12)", 13.0);
+    // Squaring either side overflows, but the hypotenuse itself is representable.
+    assert_eval_ok("hypot(3e200, 4e200) ~= 5e200", 1.0);
+    assert_eval_err("sqrt(3e200 ^ 2 + 4e200 ^ 2)", "NonFinite");
+    assert_eval_err("hypot(3)", "ArgumentCount");
+    assert_eval_err("sign(1, 2)", "ArgumentCount");
+}
+
+#[test]
+fn test_wrap() {
+    assert_eval_ok("wrap(370, 0, 360)", 10.0);
+    assert_eval_ok("wrap(-10, 0, 360)", 350.0);
+    assert_eval_ok("wrap(360, 0, 360)", 0.0);
+    assert_eval_ok("wrap(-720, 0, 360)", 0.0);
+    assert_eval_ok("wrap(5, -3, 3)", -1.0);
+    assert_eval_ok("wrap(2.5, 1, 2)", 1.5);
+    assert_eval_ok("wrap(-1e-20, 0, 1)", 0.0);
+    assert_eval_err("wrap(5, 3, 3)", "InvalidArgument");
+    assert_eval_err("wrap(5, 3, 0)", "InvalidArgument");
+    assert_eval_err("wrap(5, 0)", "ArgumentCount");
+}
+
+#[test]
+fn test_rand_with_seed() {
+    let draws = || {
+        eval_values(
+            "rand()\nrand()\nrand(1, 6)",
+            &mut SimpleExecutor::with_seed(42),
+        )
+    };
+    assert_eq!(
+        draws(),
+        vec![0.7415648787718233, 0.1599103928769201, 2.3930056512756933]
+    );
+    // The same seed gives the same sequence.
+    assert_eq!(draws(), draws());
+
+    let mut executor = SimpleExecutor::with_seed(7);
+    for value in eval_values(&"rand(-2, 3)\n".repeat(100), &mut executor) {
+        assert!((-2.0..3.0).contains(&value), "{}", value);
+    }
+    assert_eq!(eval_values("rand(5, 5)", &mut executor), vec![5.0]);
+}
+
+#[test]
+fn test_rand_rejects_bad_arguments() {
+    assert_eval_err("rand(1)", "ArgumentCount");
+    assert_eval_err("rand(1, 2, 3)", "ArgumentCount");
+    assert_eval_err("rand(3, 2)", "InvalidArgument");
+}
+
+#[test]
+fn test_range_sum_and_product() {
+    assert_eval_ok("sum(1, 100)", 5050.0);
+    assert_eval_ok("sum(-3, 3)", 0.0);
+    assert_eval_ok("sum(-5, -1)", -15.0);
+    assert_eval_ok("sum(7, 7)", 7.0);
+    assert_eval_ok("sum(1, 1e9)", 500000000500000000.0);
+    assert_eval_ok("product(1, 5)", 120.0);
+    assert_eval_ok("product(3, 3)", 3.0);
+    assert_eval_ok("product(-3, 3)", 0.0);
+    assert_eval_ok("product(-3, -1)", -6.0);
+    assert_eval_ok("product(-4, -1)", 24.0);
+    assert_eval_err(
+        "product(1, 1000)",
+        r#"NonFinite { op: "product", result: inf }"#,
+    );
+    assert_eval_err(
+        "product(-1001, -1)",
+        r#"NonFinite { op: "product", result: -inf }"#,
+    );
+    assert_eval_ok("sum(1, 10) / product(1, 3)", 55.0 / 6.0);
+}
+
+#[test]
+fn test_range_sum_and_product_reject_bad_ranges() {
+    assert_eval_err("sum(5, 1)", "InvalidArgument");
+    assert_eval_err("product(5, 1)", "InvalidArgument");
+    assert_eval_err("sum(1.5, 3)", "InvalidArgument");
+    assert_eval_err("product(1, 2.5)", "InvalidArgument");
+    assert_eval_err("sum(1)", "ArgumentCount");
+    assert_eq!(
+        evaluate_lines("sum(5, 1)", &mut SimpleExecutor::new())[0]
+            .as_ref()
+            .unwrap_err()
+            .to_string(),
+        "runtime error: function 'sum' expects a start no greater than the end 1, got 5 in input: sum(5, 1)"
+    );
+}
+
+#[test]
+fn test_mod_pow() {
+    assert_eval_ok("mod_pow(2, 10, 1000)", 24.0);
+    assert_eval_ok("mod_pow(3, 0, 7)", 1.0);
+    assert_eval_ok("mod_pow(5, 3, 1)", 0.0);
+    assert_eval_ok("mod_pow(-2, 3, 5)", 2.0);
+    assert_eval_ok("mod_pow(2, 3, -5)", 3.0);
+    // Far beyond what `2 ^ 1000` could represent exactly.
+    assert_eval_ok("mod_pow(2, 1000, 1000000007)", 688423210.0);
+
+    assert_eval_err("mod_pow(2, 10, 0)", "DivisionByZero");
+    assert_eval_err("mod_pow(2.5, 10, 7)", "InvalidArgument");
+    assert_eval_err("mod_pow(2, -1, 7)", "InvalidArgument");
+    assert_eval_err("mod_pow(2, 10, 7.5)", "InvalidArgument");
+    assert_eval_err("mod_pow(2, 10)", "ArgumentCount");
+}
+
+/// Parses `input`, which must be a bare expression.
+fn parse_expression(input: &str) -> Expr {
+    match parse_str(input) {
+        Ok(Statement::Expression(expr)) => expr,
+        other => panic!("expected an expression for {}, got {:?}", input, other),
+    }
+}
+
+#[test]
+fn test_free_variables() {
+    let names = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<BTreeSet<_>>();
+    assert_eq!(
+        parse_expression("x + y * 2").free_variables(),
+        names(&["x", "y"])
+    );
+    assert_eq!(parse_expression("1 + 2 * 3").free_variables(), names(&[]));
+    assert_eq!(
+        parse_expression("c ? sqrt(a) : b! + a").free_variables(),
+        names(&["a", "b", "c"])
+    );
+    // Function names are reported separately from variables.
+    assert_eq!(
+        parse_expression("c ? sqrt(a) : f(b)").called_functions(),
+        names(&["f", "sqrt"])
+    );
+}
+
+#[test]
+fn test_chained_subtraction_and_division_are_left_associative() {
+    assert_eval_ok("10 - 4 - 2", 4.0);
+    assert_eval_ok("16 / 4 / 2", 2.0);
+    assert_eval_ok("8 / 4 * 2", 4.0);
+    assert_eval_ok("8 * 4 / 2 / 2", 8.0);
+    assert_eval_ok("10 - 4 + 2 - 1", 7.0);
+    assert_eval_ok("100 / 10 / 5 - 1 - 1", 0.0);
+}
+
+#[test]
+fn test_conditional() {
+    assert_eval_ok("1 ? 10 : 20", 10.0);
+    assert_eval_ok("0 ? 10 : 20", 20.0);
+    assert_eval_ok("-2.5 ? 10 : 20", 10.0);
+    assert_eval_ok("(0 ? 1 : 2) * 3", 6.0);
+
+    // Nested conditionals, in both branches.
+    assert_eval_ok("0 ? 1 : 0 ? 2 : 3", 3.0);
+    assert_eval_ok("1 ? 0 ? 1 : 2 : 3", 2.0);
+
+    // Conditions known only at run time.
+    let mut executor = SimpleExecutor::new();
+    let results = evaluate_lines(
+        "let x = -4\nx > 0 ? x : -x\nx < 0 ? x > -10 ? 1 : 2 : 3\nx == -4 ? 7 : 8 + 1",
+        &mut executor,
+    );
+    let values: Vec<f64> = results.iter().map(|r| r.as_ref().unwrap().0).collect();
+    assert_eq!(values, vec![-4.0, 4.0, 1.0, 7.0]);
+}
+
+#[test]
+fn test_conditional_evaluates_only_the_selected_branch() {
+    // The untaken branch would fail if it were evaluated.
+    assert_eval_ok("1 ? 5 : 1 / 0", 5.0);
+    assert_eval_ok("0 ? undefined_name : 5", 5.0);
+
+    let mut executor = SimpleExecutor::new();
+    let results = evaluate_lines(
+        "let n = 0\nn != 0 ? 10 / n : 0\nn ? undefined_name : 1",
+        &mut executor,
+    );
+    assert_eq!(results[1].as_ref().unwrap().0, 0.0);
+    assert_eq!(results[2].as_ref().unwrap().0, 1.0);
+}
+
+#[test]
+fn test_bitwise_operators() {
+    assert_eval_ok("6 & 3", 2.0);
+    assert_eval_ok("5 | 2", 7.0);
+    assert_eval_ok("1 << 4", 16.0);
+    assert_eval_ok("256 >> 4", 16.0);
+    assert_eval_ok("-16 >> 2", -4.0);
+    assert_eval_ok("~5", -6.0);
+    assert_eval_ok("~~5", 5.0);
+    // Bitwise operators bind looser than comparisons, as in C.
+    assert_eval_ok("6 & 3 == 3", 0.0);
+    assert_eval_ok("(6 & 3) == 2", 1.0);
+    // Integral floats are accepted.
+    assert_eval_ok("sqrt(16) | 1", 5.0);
+
+    let mut executor = SimpleExecutor::new();
+    evaluate_lines("let flags = 12\nlet low = flags & 7", &mut executor);
+    assert_eq!(executor.value("low"), Some(Value::Int(4)));
+}
+
+#[test]
+fn test_bitwise_operators_require_integers() {
+    assert_eval_err("1.5 & 1", "InvalidOperand");
+    assert_eval_err("1 | 0.5", "InvalidOperand");
+    assert_eval_err("~2.5", "InvalidOperand");
+    assert_eval_err("1 << 64", "InvalidOperand");
+    assert_eval_err("1 >> -1", "InvalidOperand");
+
+    let mut executor = SimpleExecutor::new();
+    let results = evaluate_lines("let x = 1.5\nx & 1", &mut executor);
+    assert_eq!(
+        results[1].as_ref().unwrap_err().to_string(),
+        "runtime error: operator '&' expects integer operands, got 1.5 in input: x & 1"
+    );
+}
+
+#[test]
+fn test_differential_check_agrees() {
+    let inputs = [
+        "1 + 2 * 3",
+        "2 ^ 3 ^ 2",
+        "-2 ^ 2 + 10 / 4",
+        "7 / 7 * 2.5",
+        "(1 < 2) + (3 >= 3) - (2 != 2)",
+        "2pi + tau / e",
+        "5! - factorial(3)",
+        "stddev(2, 4, 4, 4, 5, 5, 7, 9) * gcd(12, 18)",
+        "0 ? 1 / 0 : 1 ? 2 : 3",
+        "pi > 3 ? ~6 & 3 | 1 << 4 : 0",
+        "9223372036854775807 + 1",
+        "1 / 0",
+        "undefined_name * 0",
+        "1.5 & 1",
+    ];
+    for input in inputs {
+        assert_eq!(arith::differential_check(input), Ok(()), "{}", input);
+    }
+}
+
+#[test]
+fn test_expr_evaluate_reads_executor_state() {
+    let mut executor = SimpleExecutor::new();
+    evaluate_lines("let x = 4\nx * 2", &mut executor);
+    // x * $1 - 1
+    let expr = binary(
+        binary(
+            Expr::Variable("x".to_string()),
+            TokenType::Mul,
+            Expr::HistoryRef(1),
+        ),
+        TokenType::Minus,
+        num(1.0),
+    );
+    assert_eq!(expr.evaluate(&executor).unwrap(), Value::Int(31));
+    assert!(matches!(
+        Expr::HistoryRef(2).evaluate(&executor),
+        Err(ExecError::HistoryIndex {
+            index: 2,
+            available: 1
+        })
+    ));
+}
+
+/// Evaluates `input` against `executor` and returns the value of every result,
+/// panicking on the first error.
+fn eval_values(input: &str, executor: &mut SimpleExecutor) -> Vec<f64> {
+    evaluate_lines(input, executor)
+        .into_iter()
+        .map(|result| result.unwrap().0)
+        .collect()
+}
+
+#[test]
+fn test_user_defined_functions() {
+    let mut executor = SimpleExecutor::new();
+    assert_eq!(
+        eval_values("let sq(x) = x*x\nsq(5)", &mut executor),
+        vec![25.0]
+    );
+    assert_eq!(
+        eval_values(
+            "let hyp(a, b) = sqrt(sq(a) + sq(b))\nhyp(3, 4)\n2sq(3) + 1",
+            &mut executor
+        ),
+        vec![5.0, 19.0]
+    );
+
+    // Integer arguments stay integers.
+    evaluate_lines("let n = sq(7)", &mut executor);
+    assert_eq!(executor.value("n"), Some(Value::Int(49)));
+
+    // A parameter shadows a variable of the same name only inside the call, and
+    // other variables are visible in the body.
+    assert_eq!(
+        eval_values(
+            "let x = 100\nlet k = 2\nlet scale(x) = k * x\nscale(3)\nx",
+            &mut executor
+        ),
+        vec![100.0, 2.0, 6.0, 100.0]
+    );
+
+    // Redefining a function replaces it; user functions shadow built-ins.
+    assert_eq!(
+        eval_values(
+            "let sq(x) = x ^ 2 + 0.5\nsq(2)\nlet abs(x) = 42\nabs(-1)",
+            &mut executor
+        ),
+        vec![4.5, 42.0]
+    );
+    assert_eq!(
+        executor.function("scale").map(|f| f.params.clone()),
+        Some(vec!["x".to_string()])
+    );
+}
+
+#[test]
+fn test_recursive_functions() {
+    let mut executor = SimpleExecutor::new();
+    assert_eq!(
+        eval_values(
+            "let fact(n) = n <= 1 ? 1 : n * fact(n - 1)\nfact(10)\n\
+             let fib(n) = n < 2 ? n : fib(n - 1) + fib(n - 2)\nfib(15)",
+            &mut executor
+        ),
+        vec![3628800.0, 610.0]
+    );
+}
+
+#[test]
+fn test_user_function_errors() {
+    let mut executor = SimpleExecutor::new();
+    let results = evaluate_lines(
+        "let sq(x) = x * x\nsq(1, 2)\nsq()\nlet forever(n) = forever(n + 1)\nforever(0)\nlet bad(x) = x + 1 / 0\nlet f(x) = y\nf(1)\nx",
+        &mut executor,
+    );
+    let errors: Vec<String> = results
+        .iter()
+        .map(|r| format!("{:?}", r.as_ref().unwrap_err()))
+        .collect();
+    assert!(errors[0].contains("ArgumentCount"), "{}", errors[0]);
+    assert!(errors[0].contains("exactly 1"), "{}", errors[0]);
+    assert!(errors[1].contains("ArgumentCount"), "{}", errors[1]);
+    assert!(errors[2].contains("RecursionLimit"), "{}", errors[2]);
+    assert!(errors[3].contains("DivisionByZero"), "{}", errors[3]);
+    assert!(errors[4].contains("UndefinedVariable"), "{}", errors[4]);
+    // The parameter binding does not leak out of the call.
+    assert!(errors[5].contains("UndefinedVariable"), "{}", errors[5]);
+    assert_eq!(errors.len(), 6);
+
+    // The executor is still usable after a failed call.
+    assert_eq!(eval_values("sq(3)", &mut executor), vec![9.0]);
+}
+
+#[test]
+fn test_tree_walker_calls_user_functions() {
+    let mut executor = SimpleExecutor::new();
+    evaluate_lines(
+        "let fact(n) = n <= 1 ? 1 : n * fact(n - 1)\nlet forever(n) = forever(n + 1)",
+        &mut executor,
+    );
+    let call = |name: &str, arg: f64| Expr::Call {
+        name: name.to_string(),
+        args: vec![num(arg)],
+    };
+    assert_eq!(
+        call("fact", 5.0).evaluate(&executor).unwrap(),
+        Value::Int(120)
+    );
+    assert!(matches!(
+        call("forever", 0.0).evaluate(&executor),
+        Err(ExecError::RecursionLimit { .. })
+    ));
+}
+
+#[test]
+fn test_ceil_div() {
+    assert_eval_ok("ceil_div(7, 2)", 4.0);
+    assert_eval_ok("ceil_div(-7, 2)", -3.0);
+    assert_eval_ok("ceil_div(6, 3)", 2.0);
+    assert_eval_ok("ceil_div(7.5, 2.5)", 3.0);
+    assert_eval_err("ceil_div(7, 0)", "DivisionByZero");
+    assert_eval_err("ceil_div(7)", "ArgumentCount");
+}
+
+#[test]
+fn test_trigonometry_angle_modes() {
+    let close = |results: Vec<f64>, expected: &[f64]| {
+        assert_eq!(results.len(), expected.len());
+        for (value, expected) in results.iter().zip(expected) {
+            assert!(
+                (value - expected).abs() < 1e-12,
+                "{} != {}",
+                value,
+                expected
+            );
+        }
+    };
+    let half_pi = std::f64::consts::FRAC_PI_2;
+
+    let mut executor = SimpleExecutor::new();
+    close(
+        eval_values("sin(pi / 2)\ncos(pi)\nasin(1)\natan(1) * 4", &mut executor),
+        &[1.0, -1.0, half_pi, std::f64::consts::PI],
+    );
+
+    let mut executor = SimpleExecutor::with_options(EvalOptions {
+        angle_mode: AngleMode::Degrees,
+        ..EvalOptions::default()
+    });
+    close(
+        eval_values(
+            "sin(90)\ncos(180)\ntan(45)\nasin(1)\nacos(0)\natan(1)\nasin(sin(30))",
+            &mut executor,
+        ),
+        &[1.0, -1.0, 1.0, 90.0, 90.0, 45.0, 30.0],
+    );
+}
+
+#[test]
+fn test_normalize_angle_radians() {
+    let tau = std::f64::consts::TAU;
+    assert_eval_ok("normalize_angle(1)", 1.0);
+    assert_eval_ok("normalize_angle(2 * pi)", 0.0);
+    let results = evaluate_lines("normalize_angle(-1)", &mut SimpleExecutor::new());
+    assert!((results[0].as_ref().unwrap().0 - (tau - 1.0)).abs() < 1e-12);
+}
+
+#[test]
+fn test_normalize_angle_degrees() {
+    let mut executor = SimpleExecutor::with_options(EvalOptions {
+        angle_mode: AngleMode::Degrees,
+        ..EvalOptions::default()
+    });
+    assert_eq!(
+        eval_values(
+            "normalize_angle(370)\nnormalize_angle(-90)\nnormalize_angle(720)\nnormalize_angle(-1e-20)",
+            &mut executor
+        ),
+        vec![10.0, 270.0, 0.0, 0.0]
+    );
+    // The trigonometric built-ins follow the same mode.
+    let results = evaluate_lines("sin(90)", &mut executor);
+    assert!((results[0].as_ref().unwrap().0 - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn test_last_stats() {
+    let mut executor = SimpleExecutor::new();
+    assert_eq!(executor.last_stats(), None);
+
+    evaluate_lines("2 * 3 + 4", &mut executor);
+    let stats = executor.last_stats().unwrap();
+    assert_eq!((stats.compiled, stats.executed), (1, 1));
+
+    // The instructions of a function's body count as executed, and a jump past
+    // the branch not taken skips its instructions.
+    evaluate_lines(
+        "let sq(x) = x * x\nlet n = 4\nn > 0 ? sq(n) : -n",
+        &mut executor,
+    );
+    let stats = executor.last_stats().unwrap();
+    // Load n, Push 0, Gt, JumpIfZero, Load n, Call sq, Jump, Load n, Neg
+    assert_eq!(stats.compiled, 9);
+    // The seven up to the else branch, plus Load x, Load x, Mul in the body.
+    assert_eq!(stats.executed, 7 + 3);
+
+    // A failed evaluation still records statistics.
+    evaluate_lines("n / 0", &mut executor);
+    assert_eq!(executor.last_stats().unwrap().executed, 3);
+}
+
+#[test]
+fn test_instr_display() {
+    let code = BytecodeCompiler::compile(&parse_expression("1 + 2 * 3")).unwrap();
+    assert_eq!(code, vec![Instr::Push(Value::Int(7))]);
+    assert_eq!(code[0].to_string(), "PUSH 7");
+
+    let code = BytecodeCompiler::compile(&parse_expression("x + 2 * y")).unwrap();
+    assert_eq!(
+        code,
+        vec![
+            Instr::Load("x".to_string()),
+            Instr::Push(Value::Int(2)),
+            Instr::Load("y".to_string()),
+            Instr::Mul,
+            Instr::Add
+        ]
+    );
+    let listing: Vec<String> = code.iter().map(Instr::to_string).collect();
+    assert_eq!(listing, vec!["LOAD x", "PUSH 2", "LOAD y", "MUL", "ADD"]);
+
+    // A hand-built instruction list covering the operand formats.
+    let code = [
+        Instr::Push(Value::Float(2.5)),
+        Instr::LoadResult(2),
+        Instr::JumpIfZero(3),
+        Instr::Jump(1),
+        Instr::CallBuiltin("max".to_string(), 2),
+        Instr::Call("f".to_string(), 1),
+        Instr::CheckType(ValueType::Int),
+        Instr::Store("z".to_string()),
+        Instr::Neg,
+    ];
+    let listing: Vec<String> = code.iter().map(Instr::to_string).collect();
+    assert_eq!(
+        listing,
+        vec![
+            "PUSH 2.5",
+            "LOAD_RESULT $2",
+            "JUMP_IF_ZERO +3",
+            "JUMP +1",
+            "CALL_BUILTIN max 2",
+            "CALL f 1",
+            "CHECK_TYPE Int",
+            "STORE z",
+            "NEG"
+        ]
+    );
+}
+
+#[test]
+fn test_expr_display_of_hand_built_ast() {
+    assert_eq!(
+        binary(num(1.0), TokenType::Plus, num(2.0)).to_string(),
+        "(1 + 2)"
+    );
+    // -(x * (2 + 3)) ^ 2 > f(y, $1) ? 1 : 0
+    let expr = Expr::Conditional {
+        cond: Box::new(binary(
+            binary(
+                Expr::UnaryOp {
+                    op: TokenType::Minus,
+                    expr: Box::new(binary(
+                        Expr::Variable("x".to_string()),
+                        TokenType::Mul,
+                        binary(num(2.0), TokenType::Plus, num(3.0)),
+                    )),
+                },
+                TokenType::Pow,
+                num(2.0),
+            ),
+            TokenType::Greater,
+            Expr::Call {
+                name: "f".to_string(),
+                args: vec![Expr::Variable("y".to_string()), Expr::HistoryRef(1)],
+            },
+        )),
+        then: Box::new(num(1.0)),
+        else_: Box::new(num(0.0)),
+    };
+    assert_eq!(
+        expr.to_string(),
+        "((((-(x * (2 + 3))) ^ 2) > f(y, $1)) ? 1 : 0)"
+    );
+}
+
+#[test]
+fn test_degrees_minutes_seconds() {
+    assert_eval_ok("30d30m", 30.5);
+    assert_eval_ok("30d15m0s", 30.25);
+    // A `d` and digits without a unit is still implicit multiplication by a variable.
+    assert_eq!(
+        eval_values("let d2 = 3\n4d2", &mut SimpleExecutor::new()),
+        vec![3.0, 12.0]
+    );
+    assert_eval_ok("-1d0m36s", -1.01);
+    assert_eval_err("30d75m", "minutes must be less than 60");
+    assert_eval_err("30d0s15m", "in that order");
+}
+
+#[test]
+fn test_multiple_statements_on_one_line() {
+    let mut executor = SimpleExecutor::with_options(EvalOptions {
+        multi_statement: true,
+        comment_delimiter: "#".to_string(),
+        ..EvalOptions::default()
+    });
+    let results = evaluate_lines("1 + 1; 2 + 2", &mut executor);
+    assert_eq!(results.len(), 2);
+    assert_eq!(
+        eval_values(
+            "let x = 3; x * 2 # comment; not a statement\nx; x + 1;",
+            &mut executor
+        ),
+        vec![3.0, 6.0, 3.0, 4.0]
+    );
+}
+
+#[test]
+fn test_undo_variable_changes() {
+    let mut executor = SimpleExecutor::new();
+    evaluate_lines("let x = 1\nx = 5\nx += 2\nlet y = x", &mut executor);
+    assert_eq!(executor.value("x"), Some(Value::Int(7)));
+
+    assert_eq!(executor.undo(), Some(("y".to_string(), None)));
+    assert_eq!(executor.value("y"), None);
+    assert_eq!(
+        executor.undo(),
+        Some(("x".to_string(), Some(Value::Int(5))))
+    );
+    assert_eq!(
+        executor.undo(),
+        Some(("x".to_string(), Some(Value::Int(1))))
+    );
+    assert_eq!(executor.undo(), Some(("x".to_string(), None)));
+    assert_eq!(executor.value("x"), None);
+    assert_eq!(executor.undo(), None);
+
+    // Redefining a built-in constant restores it.
+    evaluate_lines("let pi = 3", &mut executor);
+    executor.undo();
+    assert_eq!(executor.variable("pi"), Some(std::f64::consts::PI));
+
+    // Only the most recent UNDO_LIMIT changes are kept.
+    for i in 0..UNDO_LIMIT + 5 {
+        evaluate_lines(&format!("let z = {}", i), &mut executor);
+    }
+    let mut undone = 0;
+    while executor.undo().is_some() {
+        undone += 1;
+    }
+    assert_eq!(undone, UNDO_LIMIT);
+    assert_eq!(executor.value("z"), Some(Value::Int(4)));
+}
+
+#[test]
+fn test_snapshot_and_restore() {
+    let mut executor = SimpleExecutor::new();
+    evaluate_lines("let x = 1\nlet sq(n) = n * n\n2 + 2", &mut executor);
+    let snapshot = executor.snapshot();
+
+    evaluate_lines(
+        "x = 5\nlet y = 2\nlet sq(n) = n\nlet cube(n) = n ^ 3\n10",
+        &mut executor,
+    );
+    executor.options_mut().angle_mode = AngleMode::Degrees;
+    assert_eq!(executor.value("x"), Some(Value::Int(5)));
+
+    executor.restore(snapshot.clone());
+    assert_eq!(executor.value("x"), Some(Value::Int(1)));
+    assert_eq!(executor.value("y"), None);
+    assert_eq!(executor.value("total"), Some(Value::Int(4)));
+    assert!(executor.function("cube").is_none());
+    assert_eq!(eval_values("sq(3)", &mut executor), vec![9.0]);
+    assert_eq!(executor.results(), [Value::Int(4), Value::Int(9)]);
+    assert_eq!(executor.options().angle_mode, AngleMode::Radians);
+    // The undo history is restored too, so `undo` reverts the `let` before the snapshot.
+    executor.restore(snapshot);
+    assert_eq!(executor.undo(), Some(("x".to_string(), None)));
+    assert_eq!(executor.undo(), None);
+}
+
+#[test]
+fn test_ast_json() {
+    assert_eq!(
+        binary(num(1.0), TokenType::Plus, num(2.5)).to_json(),
+        r#"{"type":"BinaryOp","op":"+","left":{"type":"Number","value":1},"right":{"type":"Number","value":2.5}}"#
+    );
+    assert_eq!(
+        parse_str("let n: Int = -x!").unwrap().to_json(),
+        concat!(
+            r#"{"type":"Let","name":"n","type_name":"Int","value":"#,
+            r#"{"type":"UnaryOp","op":"-","expr":{"type":"Postfix","op":"!","expr":"#,
+            r#"{"type":"Variable","name":"x"}}}}"#
+        )
+    );
+    assert_eq!(
+        parse_str("total += a ~= b ? $2 : ()").unwrap().to_json(),
+        concat!(
+            r#"{"type":"CompoundAssignment","name":"total","op":"+","value":"#,
+            r#"{"type":"Conditional","cond":{"type":"BinaryOp","op":"~=","#,
+            r#""left":{"type":"Variable","name":"a"},"right":{"type":"Variable","name":"b"}},"#,
+            r#""then":{"type":"HistoryRef","index":2},"else":{"type":"EmptyParen"}}}"#
+        )
+    );
+}
+
+#[test]
+fn test_ast_json_escapes_names() {
+    // The tokenizer only reads ASCII identifiers, so these trees are built by hand.
+    assert_eq!(
+        Expr::Variable("größe".to_string()).to_json(),
+        r#"{"type":"Variable","name":"größe"}"#
+    );
+    assert_eq!(
+        Expr::Call {
+            name: "a\"b\\c".to_string(),
+            args: vec![Expr::Variable("line\nnext\ttab\u{1}".to_string())],
+        }
+        .to_json(),
+        concat!(
+            r#"{"type":"Call","name":"a\"b\\c","args":"#,
+            r#"[{"type":"Variable","name":"line\nnext\ttab\u0001"}]}"#
+        )
+    );
+}
@@ -0,0 +1,116 @@
+use arith::builtins::AngleMode;
+use arith::executor::EvalOptions;
+use arith::executor::{EvalError, ExecError};
+use arith::{Interpreter, InterpreterConfig};
+
+#[test]
+fn test_builder_sets_options() {
+    let config = InterpreterConfig::builder().line_numbers(true).build();
+    assert!(config.line_numbers);
+    assert!(!InterpreterConfig::default().line_numbers);
+}
+
+#[test]
+fn test_builder_sets_eval_options() {
+    let config = InterpreterConfig::builder()
+        .eval_options(EvalOptions {
+            tape_mode: true,
+            ..EvalOptions::default()
+        })
+        .angle_mode(AngleMode::Degrees)
+        .comment_delimiter("#")
+        .thousands_commas(true)
+        .max_depth(8)
+        .warn_truncation(true)
+        .build();
+    let options = &config.eval_options;
+    assert!(options.tape_mode);
+    assert_eq!(options.angle_mode, AngleMode::Degrees);
+    assert_eq!(options.comment_delimiter, "#");
+    assert!(options.thousands_commas);
+    assert_eq!(options.max_depth, 8);
+    assert!(options.warn_truncation);
+
+    let mut interpreter = Interpreter::new(config);
+    let results = interpreter.eval_line("sin(90) + 1,000 # comment");
+    assert_eq!(results[0].as_ref().unwrap().0, 1001.0);
+}
+
+#[test]
+fn test_interpreter_eval_line() {
+    let mut interpreter = Interpreter::new(InterpreterConfig::builder().line_numbers(true).build());
+    assert!(interpreter.config().line_numbers);
+
+    let results = interpreter.eval_line("1 + 2 * 3");
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].as_ref().unwrap().0, 7.0);
+}
+
+#[test]
+fn test_interpreter_keeps_state_between_calls() {
+    let mut interpreter = Interpreter::new(InterpreterConfig::default());
+    let results = interpreter.eval_line("let x = 6");
+    assert_eq!(results[0].as_ref().unwrap().0, 6.0);
+    let results = interpreter.eval_line("x / 2");
+    assert_eq!(results[0].as_ref().unwrap().0, 3.0);
+}
+
+#[test]
+fn test_interpreter_eval_file() {
+    let mut interpreter = Interpreter::new(InterpreterConfig::default());
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/test_files/basic.arith");
+    let values: Vec<f64> = interpreter
+        .eval_file(path)
+        .unwrap()
+        .into_iter()
+        .map(|r| r.unwrap().0)
+        .collect();
+    assert_eq!(values, vec![2.0, 6.0, 5.0, 5.0, -7.0]);
+
+    assert!(interpreter.eval_file("does/not/exist.arith").is_err());
+}
+
+#[test]
+fn test_eval_str_single_expression() {
+    assert_eq!(arith::eval_str("let x = 4\n2 * x").unwrap(), 8.0);
+    assert!(matches!(
+        arith::eval_str("let z = 0\n1 / z"),
+        Err(EvalError::Exec(ExecError::DivisionByZero, _))
+    ));
+}
+
+#[test]
+fn test_eval_str_requires_exactly_one_result() {
+    assert!(matches!(
+        arith::eval_str(""),
+        Err(EvalError::ResultCount(0, _))
+    ));
+    assert!(matches!(
+        arith::eval_str("1\n2"),
+        Err(EvalError::ResultCount(2, _))
+    ));
+}
+
+#[test]
+fn test_eval_batch_shares_state_between_entries() {
+    let results = arith::eval_batch(&[
+        "let rate = 0.5",
+        "let f(x) = x * rate",
+        "f(10)",
+        "rate = 2",
+        "f(10)",
+        "1 / 0",
+        "1\n2",
+    ]);
+    assert_eq!(results.len(), 7);
+    assert_eq!(results[0].as_ref().unwrap(), &0.5);
+    assert!(matches!(results[1], Err(EvalError::ResultCount(0, _))));
+    assert_eq!(results[2].as_ref().unwrap(), &5.0);
+    assert_eq!(results[3].as_ref().unwrap(), &2.0);
+    assert_eq!(results[4].as_ref().unwrap(), &20.0);
+    assert!(matches!(results[5], Err(EvalError::Compile(..))));
+    assert!(matches!(results[6], Err(EvalError::ResultCount(2, _))));
+
+    // Each batch starts with a fresh executor.
+    assert!(arith::eval_batch(&["rate"])[0].is_err());
+}